@@ -46,26 +46,4 @@ create view recent_sleep_event_by_user_id as
   on maxids.id = se.sleep_event_id;
 
 
--- drop table if exists checkpoint cascade;
--- create table checkpoint(
---   checkpoint_id bigserial primary key,
---   creation_time bigint not null default extract(epoch from now()) * 1000,
---   creator_user_id bigint not null
--- );
--- 
--- drop table if exists live_task cascade;
--- create table live_task(
---   checkpoint_id bigint not null references checkpoint(checkpoint_id),
---   live_task_id text not null,
---   position i64 not null,
---   value text not null
--- );
--- 
--- drop table if exists finished_task cascade;
--- create table finished_task(
---   checkpoint_id bigint not null references checkpoint(checkpoint_id),
---   finished_task_id text not null,
---   position i64 not null,
---   value text not null,
---   status bigint not null
--- );
+-- checkpoint, live_task and finished_task are created in 18-checkpoint.sql
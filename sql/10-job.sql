@@ -0,0 +1,30 @@
+\c kthg;
+
+-- Work that happens outside of requests.
+-- A worker claims a job by locking it until locked_until. If the worker dies,
+-- the job becomes available again once that passes, so it's never lost.
+drop table if exists job cascade;
+create table job(
+  job_id bigserial primary key,
+  creation_time bigint not null default extract(epoch from now()) * 1000,
+  kind text not null,
+  -- json, see manage_job.rs
+  payload text not null,
+  -- at most one unfinished job may have a given key
+  unique_key text,
+  -- not run before this time
+  run_at bigint not null,
+  attempts bigint not null default 0,
+  max_attempts bigint not null,
+  -- identifies the worker currently holding the job
+  lock_token text,
+  locked_until bigint,
+  last_error text,
+  -- null until the job succeeds or runs out of attempts
+  finish_time bigint,
+  succeeded bool
+);
+
+create index job_run_at_idx on job(run_at) where finish_time is null;
+create unique index job_unique_key_idx on job(unique_key) where finish_time is null;
+create index job_finish_time_idx on job(finish_time) where finish_time is not null;
@@ -0,0 +1,43 @@
+\c kthg;
+
+-- ephemeral messages are deleted as soon as the target has played them
+alter table user_message
+  add column ephemeral bool not null default false,
+  add column played_time bigint;
+
+-- pick up the new columns
+drop view recent_user_message_by_creator_target_id;
+create view recent_user_message_by_creator_target_id as
+  select distinct on (creator_user_id, target_user_id) um.* from user_message um
+  where um.deliver_at <= extract(epoch from now()) * 1000
+  order by creator_user_id, target_user_id, um.deliver_at desc, um.user_message_id desc;
+
+-- How long messages are kept. A policy belongs to either a user or a household.
+-- A user's policy covers their side of every message they sent or received:
+-- received messages are removed from their inbox, and a message is only deleted
+-- once the other side lets it go too. A household's covers messages between two
+-- of its members, and deletes them outright.
+-- Policies are append only: the most recent row for a user or household is the current one.
+drop table if exists retention_policy cascade;
+create table retention_policy(
+  retention_policy_id bigserial primary key,
+  creation_time bigint not null default extract(epoch from now()) * 1000,
+  creator_user_id bigint not null,
+  user_id bigint,
+  household_id bigint references household(household_id),
+  -- null means keep forever
+  keep_days bigint check (keep_days > 0),
+  -- how many messages to keep per pair of users, null means all of them
+  keep_last bigint check (keep_last > 0),
+  delete_after_played bool not null,
+  check ((user_id is null) <> (household_id is null))
+);
+
+create view recent_retention_policy as
+  select rp.* from retention_policy rp
+  inner join (
+    select max(retention_policy_id) id 
+    from retention_policy
+    group by user_id, household_id
+  ) maxids
+  on maxids.id = rp.retention_policy_id;
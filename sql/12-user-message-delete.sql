@@ -0,0 +1,14 @@
+\c kthg;
+
+-- set when the target removes the message from their inbox.
+-- the sender can still see it.
+alter table user_message
+  add column target_deleted_time bigint;
+
+-- messages the target removed don't count as recent
+drop view recent_user_message_by_creator_target_id;
+create view recent_user_message_by_creator_target_id as
+  select distinct on (creator_user_id, target_user_id) um.* from user_message um
+  where um.deliver_at <= extract(epoch from now()) * 1000
+  and um.target_deleted_time is null
+  order by creator_user_id, target_user_id, um.deliver_at desc, um.user_message_id desc;
@@ -0,0 +1,13 @@
+\c kthg;
+
+-- the message this one answers, if any
+alter table user_message
+  add column reply_to_user_message_id bigint references user_message(user_message_id) on delete set null;
+
+-- pick up the new column
+drop view recent_user_message_by_creator_target_id;
+create view recent_user_message_by_creator_target_id as
+  select distinct on (creator_user_id, target_user_id) um.* from user_message um
+  where um.deliver_at <= extract(epoch from now()) * 1000
+  and um.target_deleted_time is null
+  order by creator_user_id, target_user_id, um.deliver_at desc, um.user_message_id desc;
@@ -0,0 +1,12 @@
+\c kthg;
+
+-- a short bit of text or an emoji sent back on a message instead of a whole reply
+drop table if exists user_message_reaction cascade;
+create table user_message_reaction(
+  user_message_reaction_id bigserial primary key,
+  creation_time bigint not null default extract(epoch from now()) * 1000,
+  creator_user_id bigint not null,
+  user_message_id bigint not null references user_message(user_message_id) on delete cascade,
+  reaction text not null,
+  unique (user_message_id, creator_user_id, reaction)
+);
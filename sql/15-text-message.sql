@@ -0,0 +1,22 @@
+\c kthg;
+
+-- a message can carry text alongside its audio, or be nothing but text.
+-- kind 0 is audio, 1 is text.
+drop view recent_user_message_by_creator_target_id;
+
+alter table user_message
+  add column kind bigint not null default 0,
+  add column text_body text,
+  alter column audio_blob_key drop not null,
+  alter column audio_size drop not null,
+  alter column audio_sha256 drop not null,
+  add constraint user_message_kind_check check (
+    (kind = 0 and audio_blob_key is not null and audio_size is not null and audio_sha256 is not null)
+    or (kind = 1 and audio_blob_key is null and text_body is not null)
+  );
+
+create view recent_user_message_by_creator_target_id as
+  select distinct on (creator_user_id, target_user_id) um.* from user_message um
+  where um.deliver_at <= extract(epoch from now()) * 1000
+  and um.target_deleted_time is null
+  order by creator_user_id, target_user_id, um.deliver_at desc, um.user_message_id desc;
@@ -0,0 +1,13 @@
+\c kthg;
+
+-- what was said in a recording, worked out in the background after it's stored.
+-- copies of a message share their audio, so they share its transcript too.
+drop table if exists audio_transcript cascade;
+create table audio_transcript(
+  audio_sha256 text primary key references audio_blob(audio_sha256) on delete cascade,
+  creation_time bigint not null default extract(epoch from now()) * 1000,
+  transcriber text not null,
+  text text not null,
+  language text,
+  confidence double precision
+);
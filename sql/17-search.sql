@@ -0,0 +1,9 @@
+\c kthg;
+
+-- full text search over what messages say, whether typed or transcribed.
+-- queries have to use these exact expressions for the indexes to apply.
+create index user_message_text_body_search_idx on user_message
+  using gin (to_tsvector('english', text_body));
+
+create index audio_transcript_text_search_idx on audio_transcript
+  using gin (to_tsvector('english', text));
@@ -0,0 +1,35 @@
+\c kthg;
+
+-- A checkpoint is a snapshot of a user's task list: what's still to do, in order,
+-- and what has been finished. Every save is a new checkpoint, so past ones stay around.
+drop table if exists checkpoint cascade;
+create table checkpoint(
+  checkpoint_id bigserial primary key,
+  creation_time bigint not null default extract(epoch from now()) * 1000,
+  creator_user_id bigint not null
+);
+
+create index checkpoint_creator_user_id_idx on checkpoint(creator_user_id);
+
+-- task ids are chosen by the client and stay the same from one checkpoint to the next
+drop table if exists live_task cascade;
+create table live_task(
+  checkpoint_id bigint not null references checkpoint(checkpoint_id) on delete cascade,
+  live_task_id text not null,
+  position bigint not null,
+  value text not null,
+  primary key (checkpoint_id, live_task_id),
+  unique (checkpoint_id, position)
+);
+
+drop table if exists finished_task cascade;
+create table finished_task(
+  checkpoint_id bigint not null references checkpoint(checkpoint_id) on delete cascade,
+  finished_task_id text not null,
+  position bigint not null,
+  value text not null,
+  -- how the task ended, as defined by the client
+  status bigint not null,
+  primary key (checkpoint_id, finished_task_id),
+  unique (checkpoint_id, position)
+);
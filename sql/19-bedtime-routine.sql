@@ -0,0 +1,41 @@
+\c kthg;
+
+-- A bedtime routine is an ordered template of tasks a user means to finish before sleeping.
+-- Routines are append only: the most recent one for a user is the current one.
+drop table if exists bedtime_routine cascade;
+create table bedtime_routine(
+  bedtime_routine_id bigserial primary key,
+  creation_time bigint not null default extract(epoch from now()) * 1000,
+  creator_user_id bigint not null
+);
+
+create view recent_bedtime_routine as
+  select br.* from bedtime_routine br
+  inner join (
+    select max(bedtime_routine_id) id 
+    from bedtime_routine
+    group by creator_user_id
+  ) maxids
+  on maxids.id = br.bedtime_routine_id;
+
+-- the task id is the one the task has on the user's task list
+drop table if exists bedtime_routine_task cascade;
+create table bedtime_routine_task(
+  bedtime_routine_id bigint not null references bedtime_routine(bedtime_routine_id) on delete cascade,
+  bedtime_routine_task_id text not null,
+  position bigint not null,
+  value text not null,
+  primary key (bedtime_routine_id, bedtime_routine_task_id),
+  unique (bedtime_routine_id, position)
+);
+
+-- how much of the routine was finished when the user went to sleep
+drop table if exists bedtime_routine_snapshot cascade;
+create table bedtime_routine_snapshot(
+  sleep_event_id bigint primary key references sleep_event(sleep_event_id) on delete cascade,
+  bedtime_routine_id bigint not null references bedtime_routine(bedtime_routine_id),
+  -- the task list the routine was checked against, null if none was saved that night
+  checkpoint_id bigint references checkpoint(checkpoint_id) on delete set null,
+  task_count bigint not null,
+  finished_count bigint not null check (finished_count between 0 and task_count)
+);
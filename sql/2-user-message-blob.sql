@@ -0,0 +1,15 @@
+\c kthg;
+
+-- Audio moves out of postgres and into the blob store.
+-- user_message keeps only a reference to the blob, plus its size and checksum.
+--
+-- Apply this, then run `kthg --database-url=... migrate-blobs` to copy any
+-- existing audio_data into the blob store, then apply 3-user-message-blob-finish.sql.
+-- The server reads audio_blob_key as nullable, so it can keep running in between:
+-- audio that hasn't been moved yet just can't be played until it is.
+
+alter table user_message
+  add column audio_blob_key text,
+  add column audio_size bigint,
+  add column audio_sha256 text,
+  alter column audio_data drop not null;
@@ -0,0 +1,31 @@
+\c kthg;
+
+-- events can be backfilled, so the most recent one is the latest in time, not the last added
+drop view recent_sleep_event_by_user_id;
+create view recent_sleep_event_by_user_id as
+  select distinct on (creator_user_id) se.* from sleep_event se
+  order by creator_user_id, se.creation_time desc, se.sleep_event_id desc;
+
+create index sleep_event_creator_user_id_creation_time_idx on sleep_event(creator_user_id, creation_time);
+
+-- Every backfill, correction and deletion of a sleep event.
+-- sleep_event_id isn't a foreign key so the history outlives deleted events.
+drop table if exists sleep_event_edit cascade;
+create table sleep_event_edit(
+  sleep_event_edit_id bigserial primary key,
+  creation_time bigint not null default extract(epoch from now()) * 1000,
+  creator_user_id bigint not null,
+  sleep_event_id bigint not null,
+  -- action: 0 is a backfill, 1 is a correction, 2 is a deletion
+  action bigint not null check (action in (0, 1, 2)),
+  -- what the event was before, null for backfills
+  old_kind bigint,
+  old_time bigint,
+  -- what the event is now, null for deletions
+  new_kind bigint,
+  new_time bigint,
+  check ((old_kind is null) = (action = 0)),
+  check ((new_kind is null) = (action = 2))
+);
+
+create index sleep_event_edit_creator_user_id_idx on sleep_event_edit(creator_user_id, sleep_event_id);
@@ -0,0 +1,24 @@
+\c kthg;
+
+-- A user lets another user see whether they are asleep right now.
+-- Shares are append only: the most recent row for a pair of users is the current one.
+drop table if exists sleep_status_share cascade;
+create table sleep_status_share(
+  sleep_status_share_id bigserial primary key,
+  creation_time bigint not null default extract(epoch from now()) * 1000,
+  creator_user_id bigint not null,
+  target_user_id bigint not null,
+  active bool not null
+);
+
+create index sleep_status_share_creator_user_id on sleep_status_share(creator_user_id);
+create index sleep_status_share_target_user_id on sleep_status_share(target_user_id);
+
+create view recent_sleep_status_share as
+  select sss.* from sleep_status_share sss
+  inner join (
+    select max(sleep_status_share_id) id
+    from sleep_status_share
+    group by creator_user_id, target_user_id
+  ) maxids
+  on maxids.id = sss.sleep_status_share_id;
@@ -0,0 +1,64 @@
+\c kthg;
+
+-- source: 0 is the app, 1 is a device, 2 is a backfill, 3 is an import
+alter table sleep_event
+  add column source bigint not null default 0 check (source in (0, 1, 2, 3));
+
+-- imported events can't be told apart from backfilled ones, so they all count as backfills
+update sleep_event set source = 2
+where sleep_event_id in (select sleep_event_id from sleep_event_edit where action = 0);
+
+drop view recent_sleep_event_by_user_id;
+create view recent_sleep_event_by_user_id as
+  select distinct on (creator_user_id) se.* from sleep_event se
+  order by creator_user_id, se.creation_time desc, se.sleep_event_id desc;
+
+-- Things to do when the user goes to sleep or wakes up.
+drop table if exists sleep_rule cascade;
+create table sleep_rule(
+  sleep_rule_id bigserial primary key,
+  creation_time bigint not null default extract(epoch from now()) * 1000,
+  creator_user_id bigint not null,
+  -- the kind of sleep event that sets the rule off
+  kind bigint not null check (kind in (0, 1)),
+  -- null for events from any source
+  source bigint check (source in (0, 1, 2, 3)),
+  -- iana name, like Europe/Berlin. only needed for a window
+  time_zone text,
+  -- minutes since local midnight. the window may wrap past midnight, and null means all day
+  window_start_minute bigint check (window_start_minute between 0 and 1439),
+  window_end_minute bigint check (window_end_minute between 0 and 1439),
+  active bool not null
+);
+
+create index sleep_rule_creator_user_id_idx on sleep_rule(creator_user_id);
+
+-- what a rule does, in order
+-- action: 0 pushes a notice to target_user_id, 1 plays user_message_id once it's in the user's inbox,
+-- 2 holds incoming messages for duration_millis, or until the user wakes up if null
+drop table if exists sleep_rule_action cascade;
+create table sleep_rule_action(
+  sleep_rule_id bigint not null references sleep_rule(sleep_rule_id) on delete cascade,
+  position bigint not null,
+  action bigint not null check (action in (0, 1, 2)),
+  target_user_id bigint,
+  text_body text,
+  user_message_id bigint references user_message(user_message_id) on delete cascade,
+  duration_millis bigint,
+  primary key (sleep_rule_id, position)
+);
+
+-- While a user is in do not disturb, messages that would be delivered right away are held.
+-- The most recent row for a user is the current one.
+drop table if exists do_not_disturb cascade;
+create table do_not_disturb(
+  do_not_disturb_id bigserial primary key,
+  creation_time bigint not null default extract(epoch from now()) * 1000,
+  creator_user_id bigint not null,
+  -- the rule that turned it on
+  sleep_rule_id bigint references sleep_rule(sleep_rule_id) on delete set null,
+  -- null means until the user's next wake event
+  end_time bigint
+);
+
+create index do_not_disturb_creator_user_id_idx on do_not_disturb(creator_user_id);
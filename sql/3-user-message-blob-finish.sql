@@ -0,0 +1,21 @@
+\c kthg;
+
+-- Only apply after `kthg migrate-blobs` has finished:
+-- every user_message must have an audio_blob_key by now.
+
+drop view if exists recent_user_message_by_creator_target_id;
+
+alter table user_message
+  drop column audio_data,
+  alter column audio_blob_key set not null,
+  alter column audio_size set not null,
+  alter column audio_sha256 set not null;
+
+create view recent_user_message_by_creator_target_id as
+  select um.* from user_message um
+  inner join (
+    select max(user_message_id) id 
+    from user_message
+    group by creator_user_id, target_user_id
+  ) maxids
+  on maxids.id = um.user_message_id;
@@ -0,0 +1,7 @@
+\c kthg;
+
+-- how long the audio runs for, as reported by the client or measured while recording
+alter table user_message
+  add column audio_duration_millis bigint;
+
+create index user_message_creator_user_id_idx on user_message(creator_user_id);
@@ -0,0 +1,41 @@
+\c kthg;
+
+-- Audio is content addressed: every distinct recording is stored once,
+-- and shared between all the user messages that carry it.
+
+drop table if exists audio_blob cascade;
+create table audio_blob(
+  audio_sha256 text primary key,
+  creation_time bigint not null default extract(epoch from now()) * 1000,
+  audio_blob_key text not null,
+  audio_size bigint not null,
+  reference_count bigint not null
+);
+
+-- blobs that nothing references anymore, waiting to be deleted from the blob store
+drop table if exists audio_blob_garbage cascade;
+create table audio_blob_garbage(
+  audio_blob_key text primary key,
+  creation_time bigint not null default extract(epoch from now()) * 1000
+);
+
+-- deduplicate the audio we already have
+insert into audio_blob(audio_sha256, audio_blob_key, audio_size, reference_count)
+  select audio_sha256, min(audio_blob_key), min(audio_size), count(*)
+  from user_message
+  group by audio_sha256;
+
+insert into audio_blob_garbage(audio_blob_key)
+  select distinct um.audio_blob_key
+  from user_message um
+  inner join audio_blob ab on ab.audio_sha256 = um.audio_sha256
+  where um.audio_blob_key <> ab.audio_blob_key;
+
+update user_message um
+  set audio_blob_key = ab.audio_blob_key
+  from audio_blob ab
+  where ab.audio_sha256 = um.audio_sha256
+  and um.audio_blob_key <> ab.audio_blob_key;
+
+alter table user_message
+  add foreign key (audio_sha256) references audio_blob(audio_sha256);
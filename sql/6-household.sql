@@ -0,0 +1,46 @@
+\c kthg;
+
+-- Households group users who belong together, like a family.
+-- Membership is append only: the most recent row for a user in a household is the current one.
+
+drop table if exists household cascade;
+create table household(
+  household_id bigserial primary key,
+  creation_time bigint not null default extract(epoch from now()) * 1000,
+  creator_user_id bigint not null,
+  name text not null
+);
+
+-- role: 0 is admin, 1 is member
+drop table if exists household_membership cascade;
+create table household_membership(
+  household_membership_id bigserial primary key,
+  creation_time bigint not null default extract(epoch from now()) * 1000,
+  creator_user_id bigint not null,
+  household_id bigint not null references household(household_id),
+  user_id bigint not null,
+  role bigint not null check (role in (0, 1)),
+  active bool not null
+);
+
+create view recent_household_membership as
+  select hm.* from household_membership hm
+  inner join (
+    select max(household_membership_id) id 
+    from household_membership
+    group by household_id, user_id
+  ) maxids
+  on maxids.id = hm.household_membership_id;
+
+-- invite codes are single use, and stop working after expiry_time
+drop table if exists household_invite cascade;
+create table household_invite(
+  household_invite_code text primary key,
+  creation_time bigint not null default extract(epoch from now()) * 1000,
+  creator_user_id bigint not null,
+  household_id bigint not null references household(household_id),
+  role bigint not null check (role in (0, 1)),
+  expiry_time bigint not null,
+  accepted_user_id bigint,
+  accepted_time bigint
+);
@@ -0,0 +1,55 @@
+\c kthg;
+
+-- A user asks another user to pair. Once accepted, they are each other's contacts.
+drop table if exists contact_request cascade;
+create table contact_request(
+  contact_request_id bigserial primary key,
+  creation_time bigint not null default extract(epoch from now()) * 1000,
+  creator_user_id bigint not null,
+  target_user_id bigint not null,
+  -- null until the target responds
+  response_time bigint,
+  accepted bool
+);
+
+create index contact_request_creator_user_id on contact_request(creator_user_id);
+create index contact_request_target_user_id on contact_request(target_user_id);
+
+-- both sides of every accepted pairing
+create view contact as
+  select distinct on (user_id, contact_user_id) * from (
+    select
+      creator_user_id user_id,
+      target_user_id contact_user_id,
+      response_time creation_time,
+      contact_request_id
+    from contact_request where accepted
+    union all
+    select
+      target_user_id user_id,
+      creator_user_id contact_user_id,
+      response_time creation_time,
+      contact_request_id
+    from contact_request where accepted
+  ) c
+  order by user_id, contact_user_id, creation_time;
+
+-- Either side can block the other, which stops messages in both directions.
+-- Blocks are append only: the most recent row for a pair of users is the current one.
+drop table if exists contact_block cascade;
+create table contact_block(
+  contact_block_id bigserial primary key,
+  creation_time bigint not null default extract(epoch from now()) * 1000,
+  creator_user_id bigint not null,
+  target_user_id bigint not null,
+  active bool not null
+);
+
+create view recent_contact_block as
+  select cb.* from contact_block cb
+  inner join (
+    select max(contact_block_id) id 
+    from contact_block
+    group by creator_user_id, target_user_id
+  ) maxids
+  on maxids.id = cb.contact_block_id;
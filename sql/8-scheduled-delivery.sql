@@ -0,0 +1,31 @@
+\c kthg;
+
+-- kind: 0 is going to sleep, 1 is waking up
+alter table sleep_event
+  add column kind bigint not null default 0 check (kind in (0, 1));
+
+drop view recent_sleep_event_by_user_id;
+create view recent_sleep_event_by_user_id as
+  select se.* from sleep_event se
+  inner join (
+    select max(sleep_event_id) id 
+    from sleep_event
+    group by creator_user_id
+  ) maxids
+  on maxids.id = se.sleep_event_id;
+
+-- when the target gets to see the message.
+-- null means it's waiting for the target's next wake event.
+alter table user_message
+  add column deliver_at bigint;
+
+update user_message set deliver_at = creation_time;
+
+create index user_message_target_user_id_deliver_at_idx on user_message(target_user_id, deliver_at);
+
+-- only messages that have already been delivered count as recent
+drop view recent_user_message_by_creator_target_id;
+create view recent_user_message_by_creator_target_id as
+  select distinct on (creator_user_id, target_user_id) um.* from user_message um
+  where um.deliver_at <= extract(epoch from now()) * 1000
+  order by creator_user_id, target_user_id, um.deliver_at desc, um.user_message_id desc;
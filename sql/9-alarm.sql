@@ -0,0 +1,34 @@
+\c kthg;
+
+-- Alarms play stored messages on the target's devices, over and over on a schedule.
+drop table if exists alarm cascade;
+create table alarm(
+  alarm_id bigserial primary key,
+  creation_time bigint not null default extract(epoch from now()) * 1000,
+  creator_user_id bigint not null,
+  target_user_id bigint not null,
+  -- RRULE style, see recurrence.rs
+  recurrence text not null,
+  -- iana name, like Europe/Berlin
+  time_zone text not null,
+  -- occurrences are counted from here
+  start_time bigint not null,
+  active bool not null,
+  -- null once there are no more occurrences
+  next_fire_time bigint,
+  snooze_until bigint,
+  last_fire_time bigint
+);
+
+create index alarm_target_user_id_idx on alarm(target_user_id);
+create index alarm_next_fire_time_idx on alarm(next_fire_time) where active;
+create index alarm_snooze_until_idx on alarm(snooze_until) where active;
+
+-- the messages an alarm plays, in order
+drop table if exists alarm_user_message cascade;
+create table alarm_user_message(
+  alarm_id bigint not null references alarm(alarm_id) on delete cascade,
+  position bigint not null,
+  user_message_id bigint not null references user_message(user_message_id) on delete cascade,
+  primary key (alarm_id, position)
+);
@@ -0,0 +1,155 @@
+use super::db_types::*;
+use tokio_postgres::GenericClient;
+
+impl From<tokio_postgres::row::Row> for Alarm {
+    // select * from alarm order only, otherwise it will fail
+    fn from(row: tokio_postgres::Row) -> Alarm {
+        Alarm {
+            alarm_id: row.get("alarm_id"),
+            creation_time: row.get("creation_time"),
+            creator_user_id: row.get("creator_user_id"),
+            target_user_id: row.get("target_user_id"),
+            recurrence: row.get("recurrence"),
+            time_zone: row.get("time_zone"),
+            start_time: row.get("start_time"),
+            active: row.get("active"),
+            next_fire_time: row.get("next_fire_time"),
+            snooze_until: row.get("snooze_until"),
+            last_fire_time: row.get("last_fire_time"),
+        }
+    }
+}
+
+pub async fn add(
+    con: &mut impl GenericClient,
+    creator_user_id: i64,
+    target_user_id: i64,
+    recurrence: String,
+    time_zone: String,
+    start_time: i64,
+    next_fire_time: Option<i64>,
+) -> Result<Alarm, tokio_postgres::Error> {
+    let row = con
+        .query_one(
+            "INSERT INTO
+             alarm(
+                 creator_user_id,
+                 target_user_id,
+                 recurrence,
+                 time_zone,
+                 start_time,
+                 active,
+                 next_fire_time
+             )
+             VALUES($1, $2, $3, $4, $5, TRUE, $6)
+             RETURNING *
+            ",
+            &[
+                &creator_user_id,
+                &target_user_id,
+                &recurrence,
+                &time_zone,
+                &start_time,
+                &next_fire_time,
+            ],
+        )
+        .await?;
+
+    Ok(row.into())
+}
+
+pub async fn get_by_alarm_id(
+    con: &mut impl GenericClient,
+    alarm_id: i64,
+) -> Result<Option<Alarm>, tokio_postgres::Error> {
+    let result = con
+        .query_opt("SELECT * FROM alarm WHERE alarm_id=$1", &[&alarm_id])
+        .await?
+        .map(|x| x.into());
+    Ok(result)
+}
+
+// alarms the user set, or that ring for them
+pub async fn get_by_user_id(
+    con: &mut impl GenericClient,
+    user_id: i64,
+) -> Result<Vec<Alarm>, tokio_postgres::Error> {
+    let result = con
+        .query(
+            "SELECT * FROM alarm
+             WHERE creator_user_id=$1 OR target_user_id=$1
+             ORDER BY alarm_id
+            ",
+            &[&user_id],
+        )
+        .await?
+        .into_iter()
+        .map(|x| x.into())
+        .collect();
+    Ok(result)
+}
+
+// saves every mutable field of the alarm
+pub async fn update(
+    con: &mut impl GenericClient,
+    alarm: &Alarm,
+) -> Result<(), tokio_postgres::Error> {
+    con.execute(
+        "UPDATE alarm
+         SET recurrence = $2,
+             time_zone = $3,
+             start_time = $4,
+             active = $5,
+             next_fire_time = $6,
+             snooze_until = $7,
+             last_fire_time = $8
+         WHERE alarm_id = $1
+        ",
+        &[
+            &alarm.alarm_id,
+            &alarm.recurrence,
+            &alarm.time_zone,
+            &alarm.start_time,
+            &alarm.active,
+            &alarm.next_fire_time,
+            &alarm.snooze_until,
+            &alarm.last_fire_time,
+        ],
+    )
+    .await?;
+    Ok(())
+}
+
+pub async fn delete(
+    con: &mut impl GenericClient,
+    alarm_id: i64,
+) -> Result<(), tokio_postgres::Error> {
+    con.execute("DELETE FROM alarm WHERE alarm_id=$1", &[&alarm_id])
+        .await?;
+    Ok(())
+}
+
+// active alarms that need to ring now, locked until the end of the transaction.
+// alarms another scheduler is already working on are skipped.
+pub async fn lock_due(
+    con: &mut impl GenericClient,
+    current_time: i64,
+    limit: i64,
+) -> Result<Vec<Alarm>, tokio_postgres::Error> {
+    let result = con
+        .query(
+            "SELECT * FROM alarm
+             WHERE active
+             AND (next_fire_time <= $1 OR snooze_until <= $1)
+             ORDER BY alarm_id
+             LIMIT $2
+             FOR UPDATE SKIP LOCKED
+            ",
+            &[&current_time, &limit],
+        )
+        .await?
+        .into_iter()
+        .map(|x| x.into())
+        .collect();
+    Ok(result)
+}
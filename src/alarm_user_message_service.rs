@@ -0,0 +1,50 @@
+use tokio_postgres::GenericClient;
+
+// replaces the messages an alarm plays
+pub async fn set_by_alarm_id(
+    con: &mut impl GenericClient,
+    alarm_id: i64,
+    user_message_ids: &[i64],
+) -> Result<(), tokio_postgres::Error> {
+    con.execute(
+        "DELETE FROM alarm_user_message WHERE alarm_id=$1",
+        &[&alarm_id],
+    )
+    .await?;
+
+    for (position, user_message_id) in user_message_ids.iter().enumerate() {
+        con.execute(
+            "INSERT INTO
+             alarm_user_message(
+                 alarm_id,
+                 position,
+                 user_message_id
+             )
+             VALUES($1, $2, $3)
+            ",
+            &[&alarm_id, &(position as i64), user_message_id],
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+// the messages an alarm plays, in order
+pub async fn get_by_alarm_id(
+    con: &mut impl GenericClient,
+    alarm_id: i64,
+) -> Result<Vec<i64>, tokio_postgres::Error> {
+    let result = con
+        .query(
+            "SELECT user_message_id FROM alarm_user_message
+             WHERE alarm_id=$1
+             ORDER BY position
+            ",
+            &[&alarm_id],
+        )
+        .await?
+        .into_iter()
+        .map(|x| x.get("user_message_id"))
+        .collect();
+    Ok(result)
+}
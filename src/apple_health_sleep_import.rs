@@ -0,0 +1,164 @@
+use std::io::{BufReader, Read};
+
+use chrono::DateTime;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::reader::Reader;
+
+use crate::sleep_import::{SleepImportError, SleepSession};
+
+const SLEEP_ANALYSIS: &str = "HKCategoryTypeIdentifierSleepAnalysis";
+const IN_BED: &str = "HKCategoryValueSleepAnalysisInBed";
+// core, deep, rem, or unspecified on older watches
+const ASLEEP_PREFIX: &str = "HKCategoryValueSleepAnalysisAsleep";
+
+// apple health always writes the offset
+const TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S %z";
+
+// stages with less than this awake between them are the same night
+const MAX_GAP_MILLIS: i64 = 30 * 60 * 1000;
+
+// the sleep analysis record's value and times, or none for any other record
+fn parse_record(e: &BytesStart) -> Result<Option<(String, SleepSession)>, SleepImportError> {
+    let mut kind = None;
+    let mut value = None;
+    let mut start_time = None;
+    let mut end_time = None;
+    for attr in e.attributes() {
+        let attr = attr.map_err(quick_xml::Error::from)?;
+        let v = attr.unescape_value()?.into_owned();
+        match attr.key.as_ref() {
+            b"type" => kind = Some(v),
+            b"value" => value = Some(v),
+            b"startDate" => start_time = Some(v),
+            b"endDate" => end_time = Some(v),
+            _ => {}
+        }
+    }
+
+    if kind.as_deref() != Some(SLEEP_ANALYSIS) {
+        return Ok(None);
+    }
+
+    let parse_time = |s: Option<String>| {
+        let s = s.ok_or_else(|| SleepImportError::Malformed("record missing a date".into()))?;
+        DateTime::parse_from_str(&s, TIME_FORMAT)
+            .map(|x| x.timestamp_millis())
+            .map_err(|_| SleepImportError::Malformed(format!("invalid time: {}", s)))
+    };
+
+    Ok(Some((
+        value.unwrap_or_default(),
+        SleepSession {
+            start_time: parse_time(start_time)?,
+            end_time: parse_time(end_time)?,
+        },
+    )))
+}
+
+// joins overlapping stretches, and ones with a short gap between them
+fn merge(mut stretches: Vec<SleepSession>) -> Vec<SleepSession> {
+    stretches.sort_by_key(|x| x.start_time);
+    let mut merged: Vec<SleepSession> = vec![];
+    for stretch in stretches {
+        match merged.last_mut() {
+            Some(last) if stretch.start_time <= last.end_time + MAX_GAP_MILLIS => {
+                last.end_time = last.end_time.max(stretch.end_time);
+            }
+            _ => merged.push(stretch),
+        }
+    }
+    merged
+}
+
+// a session is a run of asleep stages, awake stages in between are skipped over.
+// time in bed is only used for nights nothing recorded the stages of, like before watches tracked them.
+pub fn parse(data: impl Read) -> Result<Vec<SleepSession>, SleepImportError> {
+    let mut reader = Reader::from_reader(BufReader::new(data));
+
+    let mut asleep = vec![];
+    let mut in_bed = vec![];
+
+    let mut buf = vec![];
+    loop {
+        match reader.read_event_into(&mut buf)? {
+            Event::Start(e) | Event::Empty(e) if e.name().as_ref() == b"Record" => {
+                match parse_record(&e)? {
+                    Some((value, stretch)) if value.starts_with(ASLEEP_PREFIX) => {
+                        asleep.push(stretch)
+                    }
+                    Some((value, stretch)) if value == IN_BED => in_bed.push(stretch),
+                    _ => {}
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    let asleep = merge(asleep);
+    let in_bed = merge(in_bed)
+        .into_iter()
+        .filter(|x| {
+            !asleep
+                .iter()
+                .any(|y| y.start_time < x.end_time && x.start_time < y.end_time)
+        })
+        .collect();
+
+    let mut sessions = [asleep, in_bed].concat();
+    sessions.sort_by_key(|x| x.start_time);
+    Ok(sessions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn joins_stages_into_nights() {
+        let data = include_bytes!("../fixtures/sleep_import/apple-health.xml");
+        let sessions = parse(&data[..]).unwrap();
+        assert_eq!(
+            sessions,
+            vec![
+                // core, awake, deep and rem from 22:41:30 to 06:48, the phone's time in bed is dropped
+                SleepSession {
+                    start_time: 1704350490000,
+                    end_time: 1704379680000,
+                },
+                // an afternoon nap
+                SleepSession {
+                    start_time: 1704405900000,
+                    end_time: 1704407700000,
+                },
+                SleepSession {
+                    start_time: 1704438900000,
+                    end_time: 1704465000000,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn falls_back_to_time_in_bed() {
+        let data = include_bytes!("../fixtures/sleep_import/apple-health-in-bed.xml");
+        let sessions = parse(&data[..]).unwrap();
+        assert_eq!(
+            sessions,
+            vec![SleepSession {
+                start_time: 1559342400000,
+                end_time: 1559370600000,
+            }]
+        );
+    }
+
+    #[test]
+    fn rejects_bad_times() {
+        let data = br#"<HealthData><Record type="HKCategoryTypeIdentifierSleepAnalysis" startDate="last night" endDate="2024-01-04 07:00:00 -0800" value="HKCategoryValueSleepAnalysisInBed"/></HealthData>"#;
+        assert!(matches!(
+            parse(&data[..]),
+            Err(SleepImportError::Malformed(_))
+        ));
+    }
+}
@@ -0,0 +1,132 @@
+use super::db_types::*;
+use tokio_postgres::GenericClient;
+
+impl From<tokio_postgres::row::Row> for AudioBlob {
+    // select * from audio_blob order only, otherwise it will fail
+    fn from(row: tokio_postgres::Row) -> AudioBlob {
+        AudioBlob {
+            audio_sha256: row.get("audio_sha256"),
+            creation_time: row.get("creation_time"),
+            audio_blob_key: row.get("audio_blob_key"),
+            audio_size: row.get("audio_size"),
+            reference_count: row.get("reference_count"),
+        }
+    }
+}
+
+pub async fn get_by_audio_sha256(
+    con: &mut impl GenericClient,
+    audio_sha256: String,
+) -> Result<Option<AudioBlob>, tokio_postgres::Error> {
+    let result = con
+        .query_opt(
+            "SELECT * FROM audio_blob WHERE audio_sha256=$1",
+            &[&audio_sha256],
+        )
+        .await?
+        .map(|x| x.into());
+    Ok(result)
+}
+
+// takes references to the blob with this content, registering ours if there isn't one yet.
+// returns the blob that should be used, which might not be the one we passed in.
+pub async fn acquire(
+    con: &mut impl GenericClient,
+    audio_sha256: String,
+    audio_blob_key: String,
+    audio_size: i64,
+    references: i64,
+) -> Result<AudioBlob, tokio_postgres::Error> {
+    let row = con
+        .query_one(
+            "INSERT INTO
+             audio_blob(
+                 audio_sha256,
+                 audio_blob_key,
+                 audio_size,
+                 reference_count
+             )
+             VALUES($1, $2, $3, $4)
+             ON CONFLICT (audio_sha256)
+             DO UPDATE SET reference_count = audio_blob.reference_count + $4
+             RETURNING *
+            ",
+            &[&audio_sha256, &audio_blob_key, &audio_size, &references],
+        )
+        .await?;
+
+    Ok(row.into())
+}
+
+// takes references to the blob with this content, only if it already exists
+pub async fn acquire_existing(
+    con: &mut impl GenericClient,
+    audio_sha256: String,
+    references: i64,
+) -> Result<Option<AudioBlob>, tokio_postgres::Error> {
+    let result = con
+        .query_opt(
+            "UPDATE audio_blob
+             SET reference_count = reference_count + $2
+             WHERE audio_sha256 = $1
+             RETURNING *
+            ",
+            &[&audio_sha256, &references],
+        )
+        .await?
+        .map(|x| x.into());
+    Ok(result)
+}
+
+// gives up a reference to the blob with this content.
+// once nobody references it, the blob is queued up for deletion from the blob store.
+pub async fn release(
+    con: &mut impl GenericClient,
+    audio_sha256: String,
+) -> Result<(), tokio_postgres::Error> {
+    con.execute(
+        "WITH removed AS (
+             DELETE FROM audio_blob
+             WHERE audio_sha256 = $1 AND reference_count <= 1
+             RETURNING audio_blob_key
+         ), released AS (
+             UPDATE audio_blob
+             SET reference_count = reference_count - 1
+             WHERE audio_sha256 = $1 AND reference_count > 1
+         )
+         INSERT INTO audio_blob_garbage(audio_blob_key)
+         SELECT audio_blob_key FROM removed
+        ",
+        &[&audio_sha256],
+    )
+    .await?;
+    Ok(())
+}
+
+pub async fn get_garbage(
+    con: &mut impl GenericClient,
+    limit: i64,
+) -> Result<Vec<String>, tokio_postgres::Error> {
+    let result = con
+        .query(
+            "SELECT audio_blob_key FROM audio_blob_garbage ORDER BY creation_time LIMIT $1",
+            &[&limit],
+        )
+        .await?
+        .into_iter()
+        .map(|x| x.get("audio_blob_key"))
+        .collect();
+    Ok(result)
+}
+
+pub async fn remove_garbage(
+    con: &mut impl GenericClient,
+    audio_blob_key: String,
+) -> Result<(), tokio_postgres::Error> {
+    con.execute(
+        "DELETE FROM audio_blob_garbage WHERE audio_blob_key = $1",
+        &[&audio_blob_key],
+    )
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,79 @@
+use super::db_types::*;
+use tokio_postgres::GenericClient;
+
+impl From<tokio_postgres::row::Row> for AudioTranscript {
+    // select * from audio_transcript order only, otherwise it will fail
+    fn from(row: tokio_postgres::Row) -> AudioTranscript {
+        AudioTranscript {
+            audio_sha256: row.get("audio_sha256"),
+            creation_time: row.get("creation_time"),
+            transcriber: row.get("transcriber"),
+            text: row.get("text"),
+            language: row.get("language"),
+            confidence: row.get("confidence"),
+        }
+    }
+}
+
+// stores the transcript, unless the audio has been deleted or already has one.
+// returns none in either case.
+pub async fn add(
+    con: &mut impl GenericClient,
+    audio_sha256: String,
+    transcriber: String,
+    text: String,
+    language: Option<String>,
+    confidence: Option<f64>,
+) -> Result<Option<AudioTranscript>, tokio_postgres::Error> {
+    let result = con
+        .query_opt(
+            "INSERT INTO
+             audio_transcript(
+                 audio_sha256,
+                 transcriber,
+                 text,
+                 language,
+                 confidence
+             )
+             SELECT audio_sha256, $2, $3, $4, $5
+             FROM audio_blob
+             WHERE audio_sha256 = $1
+             ON CONFLICT (audio_sha256) DO NOTHING
+             RETURNING *
+            ",
+            &[&audio_sha256, &transcriber, &text, &language, &confidence],
+        )
+        .await?
+        .map(|x| x.into());
+    Ok(result)
+}
+
+pub async fn get_by_audio_sha256(
+    con: &mut impl GenericClient,
+    audio_sha256: String,
+) -> Result<Option<AudioTranscript>, tokio_postgres::Error> {
+    let result = con
+        .query_opt(
+            "SELECT * FROM audio_transcript WHERE audio_sha256=$1",
+            &[&audio_sha256],
+        )
+        .await?
+        .map(|x| x.into());
+    Ok(result)
+}
+
+pub async fn get_by_audio_sha256s(
+    con: &mut impl GenericClient,
+    audio_sha256s: &[String],
+) -> Result<Vec<AudioTranscript>, tokio_postgres::Error> {
+    let result = con
+        .query(
+            "SELECT * FROM audio_transcript WHERE audio_sha256 = ANY($1)",
+            &[&audio_sha256s],
+        )
+        .await?
+        .into_iter()
+        .map(|x| x.into())
+        .collect();
+    Ok(result)
+}
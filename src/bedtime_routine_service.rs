@@ -0,0 +1,52 @@
+use super::db_types::*;
+use tokio_postgres::GenericClient;
+
+impl From<tokio_postgres::row::Row> for BedtimeRoutine {
+    // select * from bedtime_routine order only, otherwise it will fail
+    fn from(row: tokio_postgres::Row) -> BedtimeRoutine {
+        BedtimeRoutine {
+            bedtime_routine_id: row.get("bedtime_routine_id"),
+            creation_time: row.get("creation_time"),
+            creator_user_id: row.get("creator_user_id"),
+        }
+    }
+}
+
+pub async fn add(
+    con: &mut impl GenericClient,
+    creator_user_id: i64,
+) -> Result<BedtimeRoutine, tokio_postgres::Error> {
+    let row = con
+        .query_one(
+            "INSERT INTO
+             bedtime_routine(
+                 creator_user_id
+             )
+             VALUES($1)
+             RETURNING bedtime_routine_id, creation_time
+            ",
+            &[&creator_user_id],
+        )
+        .await?;
+
+    // return bedtime routine
+    Ok(BedtimeRoutine {
+        bedtime_routine_id: row.get(0),
+        creation_time: row.get(1),
+        creator_user_id,
+    })
+}
+
+pub async fn get_recent_by_creator_user_id(
+    con: &mut impl GenericClient,
+    creator_user_id: i64,
+) -> Result<Option<BedtimeRoutine>, tokio_postgres::Error> {
+    let result = con
+        .query_opt(
+            "SELECT * FROM recent_bedtime_routine WHERE creator_user_id=$1",
+            &[&creator_user_id],
+        )
+        .await?
+        .map(|x| x.into());
+    Ok(result)
+}
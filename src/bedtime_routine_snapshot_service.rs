@@ -0,0 +1,114 @@
+use super::db_types::*;
+use tokio_postgres::GenericClient;
+
+impl From<tokio_postgres::row::Row> for BedtimeRoutineSnapshot {
+    // select * from bedtime_routine_snapshot order only, otherwise it will fail
+    fn from(row: tokio_postgres::Row) -> BedtimeRoutineSnapshot {
+        BedtimeRoutineSnapshot {
+            sleep_event_id: row.get("sleep_event_id"),
+            bedtime_routine_id: row.get("bedtime_routine_id"),
+            checkpoint_id: row.get("checkpoint_id"),
+            task_count: row.get("task_count"),
+            finished_count: row.get("finished_count"),
+        }
+    }
+}
+
+impl From<tokio_postgres::row::Row> for RoutineNight {
+    // select bedtime_routine_snapshot.*, sleep_time and wake_time only, otherwise it will fail
+    fn from(row: tokio_postgres::Row) -> RoutineNight {
+        RoutineNight {
+            sleep_time: row.get("sleep_time"),
+            wake_time: row.get("wake_time"),
+            snapshot: row.into(),
+        }
+    }
+}
+
+pub async fn add(
+    con: &mut impl GenericClient,
+    sleep_event_id: i64,
+    bedtime_routine_id: i64,
+    checkpoint_id: Option<i64>,
+    task_count: i64,
+    finished_count: i64,
+) -> Result<BedtimeRoutineSnapshot, tokio_postgres::Error> {
+    con.execute(
+        "INSERT INTO
+         bedtime_routine_snapshot(
+             sleep_event_id,
+             bedtime_routine_id,
+             checkpoint_id,
+             task_count,
+             finished_count
+         )
+         VALUES($1, $2, $3, $4, $5)
+        ",
+        &[
+            &sleep_event_id,
+            &bedtime_routine_id,
+            &checkpoint_id,
+            &task_count,
+            &finished_count,
+        ],
+    )
+    .await?;
+
+    // return bedtime routine snapshot
+    Ok(BedtimeRoutineSnapshot {
+        sleep_event_id,
+        bedtime_routine_id,
+        checkpoint_id,
+        task_count,
+        finished_count,
+    })
+}
+
+// every night the user slept with a routine, oldest first.
+// a night ends at the user's next sleep event, if that is waking up.
+pub async fn get_nights_by_creator_user_id(
+    con: &mut impl GenericClient,
+    creator_user_id: i64,
+    min_creation_time: Option<i64>,
+    max_creation_time: Option<i64>,
+) -> Result<Vec<RoutineNight>, tokio_postgres::Error> {
+    let result = con
+        .query(
+            "SELECT
+                 brs.*,
+                 se.creation_time AS sleep_time,
+                 CASE WHEN next.kind = 1 THEN next.creation_time END AS wake_time
+             FROM bedtime_routine_snapshot brs
+             INNER JOIN sleep_event se ON se.sleep_event_id = brs.sleep_event_id
+             LEFT JOIN LATERAL (
+                 SELECT n.kind, n.creation_time FROM sleep_event n
+                 WHERE n.creator_user_id = se.creator_user_id
+                 AND (n.creation_time, n.sleep_event_id) > (se.creation_time, se.sleep_event_id)
+                 ORDER BY n.creation_time, n.sleep_event_id
+                 LIMIT 1
+             ) next ON TRUE
+             WHERE se.creator_user_id = $1
+             AND ($2::bigint IS NULL OR se.creation_time >= $2)
+             AND ($3::bigint IS NULL OR se.creation_time <= $3)
+             ORDER BY se.creation_time, se.sleep_event_id
+            ",
+            &[&creator_user_id, &min_creation_time, &max_creation_time],
+        )
+        .await?
+        .into_iter()
+        .map(|x| x.into())
+        .collect();
+    Ok(result)
+}
+
+pub async fn delete_by_sleep_event_id(
+    con: &mut impl GenericClient,
+    sleep_event_id: i64,
+) -> Result<(), tokio_postgres::Error> {
+    con.execute(
+        "DELETE FROM bedtime_routine_snapshot WHERE sleep_event_id=$1",
+        &[&sleep_event_id],
+    )
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,69 @@
+use super::db_types::*;
+use tokio_postgres::GenericClient;
+
+impl From<tokio_postgres::row::Row> for BedtimeRoutineTask {
+    // select * from bedtime_routine_task order only, otherwise it will fail
+    fn from(row: tokio_postgres::Row) -> BedtimeRoutineTask {
+        BedtimeRoutineTask {
+            bedtime_routine_id: row.get("bedtime_routine_id"),
+            bedtime_routine_task_id: row.get("bedtime_routine_task_id"),
+            position: row.get("position"),
+            value: row.get("value"),
+        }
+    }
+}
+
+pub async fn add(
+    con: &mut impl GenericClient,
+    bedtime_routine_id: i64,
+    bedtime_routine_task_id: String,
+    position: i64,
+    value: String,
+) -> Result<BedtimeRoutineTask, tokio_postgres::Error> {
+    con.execute(
+        "INSERT INTO
+         bedtime_routine_task(
+             bedtime_routine_id,
+             bedtime_routine_task_id,
+             position,
+             value
+         )
+         VALUES($1, $2, $3, $4)
+        ",
+        &[
+            &bedtime_routine_id,
+            &bedtime_routine_task_id,
+            &position,
+            &value,
+        ],
+    )
+    .await?;
+
+    // return bedtime routine task
+    Ok(BedtimeRoutineTask {
+        bedtime_routine_id,
+        bedtime_routine_task_id,
+        position,
+        value,
+    })
+}
+
+// the routine's tasks, in order
+pub async fn get_by_bedtime_routine_id(
+    con: &mut impl GenericClient,
+    bedtime_routine_id: i64,
+) -> Result<Vec<BedtimeRoutineTask>, tokio_postgres::Error> {
+    let result = con
+        .query(
+            "SELECT * FROM bedtime_routine_task
+             WHERE bedtime_routine_id=$1
+             ORDER BY position
+            ",
+            &[&bedtime_routine_id],
+        )
+        .await?
+        .into_iter()
+        .map(|x| x.into())
+        .collect();
+    Ok(result)
+}
@@ -0,0 +1,162 @@
+use derive_more::Display;
+use sha2::{Digest, Sha256};
+
+use crate::local_blob_store::{LocalBlobReader, LocalBlobStore, LocalBlobWriter};
+use crate::s3_blob_store::{S3BlobReader, S3BlobStore, S3BlobWriter};
+
+#[derive(Debug, Display)]
+pub enum BlobStoreError {
+    #[display(fmt = "blob not found")]
+    NotFound,
+    #[display(fmt = "io: {}", _0)]
+    Io(std::io::Error),
+    #[display(fmt = "http: {}", _0)]
+    Http(reqwest::Error),
+    #[display(fmt = "unexpected status {}: {}", _0, _1)]
+    Status(u16, String),
+    #[display(fmt = "malformed response: {}", _0)]
+    Malformed(String),
+}
+
+impl std::error::Error for BlobStoreError {}
+
+impl From<std::io::Error> for BlobStoreError {
+    fn from(e: std::io::Error) -> BlobStoreError {
+        match e.kind() {
+            std::io::ErrorKind::NotFound => BlobStoreError::NotFound,
+            _ => BlobStoreError::Io(e),
+        }
+    }
+}
+
+impl From<reqwest::Error> for BlobStoreError {
+    fn from(e: reqwest::Error) -> BlobStoreError {
+        BlobStoreError::Http(e)
+    }
+}
+
+// what we learned about a blob while writing it out
+#[derive(Clone, Debug)]
+pub struct BlobInfo {
+    pub size: i64,
+    pub sha256: String,
+}
+
+// where the audio data of user messages actually lives.
+// the database only holds the key, size and checksum of each blob.
+#[derive(Clone)]
+pub enum BlobStore {
+    Local(LocalBlobStore),
+    S3(S3BlobStore),
+}
+
+enum BlobSink {
+    Local(LocalBlobWriter),
+    S3(S3BlobWriter),
+}
+
+// writes a blob out chunk by chunk, without holding all of it in memory.
+// the blob must be either finished or aborted.
+pub struct BlobWriter {
+    sink: BlobSink,
+    hasher: Sha256,
+    size: i64,
+}
+
+enum BlobSource {
+    Local(LocalBlobReader),
+    S3(S3BlobReader),
+}
+
+// reads a blob back chunk by chunk, fetching from storage on demand
+pub struct BlobReader {
+    source: BlobSource,
+}
+
+impl BlobStore {
+    pub async fn writer(&self, key: &str) -> Result<BlobWriter, BlobStoreError> {
+        let sink = match self {
+            BlobStore::Local(s) => BlobSink::Local(s.writer(key).await?),
+            BlobStore::S3(s) => BlobSink::S3(s.writer(key).await?),
+        };
+        Ok(BlobWriter {
+            sink,
+            hasher: Sha256::new(),
+            size: 0,
+        })
+    }
+
+    pub async fn reader(&self, key: &str) -> Result<BlobReader, BlobStoreError> {
+        let source = match self {
+            BlobStore::Local(s) => BlobSource::Local(s.reader(key).await?),
+            BlobStore::S3(s) => BlobSource::S3(s.reader(key).await?),
+        };
+        Ok(BlobReader { source })
+    }
+
+    pub async fn delete(&self, key: &str) -> Result<(), BlobStoreError> {
+        match self {
+            BlobStore::Local(s) => s.delete(key).await,
+            BlobStore::S3(s) => s.delete(key).await,
+        }
+    }
+
+    // writes out a blob we already have in memory
+    pub async fn put(&self, key: &str, data: &[u8]) -> Result<BlobInfo, BlobStoreError> {
+        let mut writer = self.writer(key).await?;
+        if let Err(e) = writer.write(data).await {
+            let _ = writer.abort().await;
+            return Err(e);
+        }
+        writer.finish().await
+    }
+}
+
+impl BlobWriter {
+    pub async fn write(&mut self, chunk: &[u8]) -> Result<(), BlobStoreError> {
+        self.hasher.update(chunk);
+        self.size += chunk.len() as i64;
+        match &mut self.sink {
+            BlobSink::Local(w) => w.write(chunk).await,
+            BlobSink::S3(w) => w.write(chunk).await,
+        }
+    }
+
+    // number of bytes written so far
+    pub fn size(&self) -> i64 {
+        self.size
+    }
+
+    pub async fn finish(self) -> Result<BlobInfo, BlobStoreError> {
+        match self.sink {
+            BlobSink::Local(w) => w.finish().await?,
+            BlobSink::S3(w) => w.finish().await?,
+        };
+        Ok(BlobInfo {
+            size: self.size,
+            sha256: format!("{:x}", self.hasher.finalize()),
+        })
+    }
+
+    pub async fn abort(self) -> Result<(), BlobStoreError> {
+        match self.sink {
+            BlobSink::Local(w) => w.abort().await,
+            BlobSink::S3(w) => w.abort().await,
+        }
+    }
+}
+
+impl BlobReader {
+    // returns the next chunk of at most max_len bytes, or None once the blob is exhausted
+    pub async fn read(&mut self, max_len: usize) -> Result<Option<Vec<u8>>, BlobStoreError> {
+        match &mut self.source {
+            BlobSource::Local(r) => r.read(max_len).await,
+            BlobSource::S3(r) => r.read(max_len).await,
+        }
+    }
+}
+
+// creates a fresh key for a new audio blob
+pub fn new_audio_key() -> String {
+    format!("audio/{}", crate::utils::random_string())
+}
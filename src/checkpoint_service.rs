@@ -0,0 +1,120 @@
+use super::db_types::*;
+use tokio_postgres::GenericClient;
+
+impl From<tokio_postgres::row::Row> for Checkpoint {
+    // select * from checkpoint order only, otherwise it will fail
+    fn from(row: tokio_postgres::Row) -> Checkpoint {
+        Checkpoint {
+            checkpoint_id: row.get("checkpoint_id"),
+            creation_time: row.get("creation_time"),
+            creator_user_id: row.get("creator_user_id"),
+        }
+    }
+}
+
+pub async fn add(
+    con: &mut impl GenericClient,
+    creator_user_id: i64,
+) -> Result<Checkpoint, tokio_postgres::Error> {
+    let row = con
+        .query_one(
+            "INSERT INTO
+             checkpoint(
+                 creator_user_id
+             )
+             VALUES($1)
+             RETURNING checkpoint_id, creation_time
+            ",
+            &[&creator_user_id],
+        )
+        .await?;
+
+    // return checkpoint
+    Ok(Checkpoint {
+        checkpoint_id: row.get(0),
+        creation_time: row.get(1),
+        creator_user_id,
+    })
+}
+
+pub async fn get_by_checkpoint_id(
+    con: &mut impl GenericClient,
+    checkpoint_id: i64,
+) -> Result<Option<Checkpoint>, tokio_postgres::Error> {
+    let result = con
+        .query_opt(
+            "SELECT * FROM checkpoint WHERE checkpoint_id=$1",
+            &[&checkpoint_id],
+        )
+        .await?
+        .map(|x| x.into());
+    Ok(result)
+}
+
+pub async fn get_recent_by_creator_user_id(
+    con: &mut impl GenericClient,
+    creator_user_id: i64,
+) -> Result<Option<Checkpoint>, tokio_postgres::Error> {
+    let result = con
+        .query_opt(
+            "SELECT * FROM checkpoint
+             WHERE creator_user_id=$1
+             ORDER BY checkpoint_id DESC
+             LIMIT 1
+            ",
+            &[&creator_user_id],
+        )
+        .await?
+        .map(|x| x.into());
+    Ok(result)
+}
+
+// the creator's checkpoints, newest first
+pub async fn get_by_creator_user_id(
+    con: &mut impl GenericClient,
+    creator_user_id: i64,
+    min_creation_time: Option<i64>,
+    max_creation_time: Option<i64>,
+    limit: i64,
+) -> Result<Vec<Checkpoint>, tokio_postgres::Error> {
+    let result = con
+        .query(
+            "SELECT * FROM checkpoint
+             WHERE creator_user_id=$1
+             AND ($2::bigint IS NULL OR creation_time >= $2)
+             AND ($3::bigint IS NULL OR creation_time <= $3)
+             ORDER BY checkpoint_id DESC
+             LIMIT $4
+            ",
+            &[
+                &creator_user_id,
+                &min_creation_time,
+                &max_creation_time,
+                &limit,
+            ],
+        )
+        .await?
+        .into_iter()
+        .map(|x| x.into())
+        .collect();
+    Ok(result)
+}
+
+pub async fn get_by_checkpoint_ids(
+    con: &mut impl GenericClient,
+    checkpoint_ids: &[i64],
+) -> Result<Vec<Checkpoint>, tokio_postgres::Error> {
+    let result = con
+        .query(
+            "SELECT * FROM checkpoint
+             WHERE checkpoint_id = ANY($1)
+             ORDER BY checkpoint_id
+            ",
+            &[&checkpoint_ids],
+        )
+        .await?
+        .into_iter()
+        .map(|x| x.into())
+        .collect();
+    Ok(result)
+}
@@ -0,0 +1,119 @@
+use std::path::PathBuf;
+use std::process::Stdio;
+
+use serde::Deserialize;
+use tokio::io::AsyncWriteExt;
+
+use crate::blob_store::BlobReader;
+use crate::transcriber::{Transcribe, Transcript, TranscriptionError};
+use crate::utils;
+
+// stands in for the path of the audio in the command's arguments
+const AUDIO_PLACEHOLDER: &str = "{audio}";
+
+// how much of stderr is kept when the command fails
+const MAX_STDERR_CHARS: usize = 1000;
+
+// how much audio is read from storage at a time when writing it out for the command
+const READ_BLOCK_SIZE: usize = 64 * 1024;
+
+// runs a local program, such as a whisper.cpp binary, on each recording.
+// the audio is written to a temporary file whose path replaces {audio} in the arguments,
+// or is passed last if no argument mentions it.
+// the program prints either the bare transcript, or a json object with text, language and confidence.
+#[derive(Clone, Debug)]
+pub struct CommandTranscriber {
+    program: String,
+    args: Vec<String>,
+}
+
+// a temporary file that is removed again however we leave, even if the job times out
+struct TempFile {
+    path: PathBuf,
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+#[derive(Deserialize)]
+struct CommandOutput {
+    text: String,
+    language: Option<String>,
+    confidence: Option<f64>,
+}
+
+impl CommandTranscriber {
+    pub fn new(program: String, args: Vec<String>) -> CommandTranscriber {
+        CommandTranscriber { program, args }
+    }
+
+    fn args_for(&self, audio_path: &str) -> Vec<String> {
+        let mut args: Vec<String> = self
+            .args
+            .iter()
+            .map(|x| x.replace(AUDIO_PLACEHOLDER, audio_path))
+            .collect();
+        if !self.args.iter().any(|x| x.contains(AUDIO_PLACEHOLDER)) {
+            args.push(audio_path.to_string());
+        }
+        args
+    }
+}
+
+fn parse_output(stdout: Vec<u8>) -> Result<Transcript, TranscriptionError> {
+    let stdout = String::from_utf8(stdout)
+        .map_err(|_| TranscriptionError::Malformed(String::from("not utf-8")))?;
+
+    match serde_json::from_str::<CommandOutput>(&stdout) {
+        Ok(output) => Ok(Transcript {
+            text: output.text.trim().to_string(),
+            language: output.language,
+            confidence: output.confidence,
+        }),
+        Err(_) => Ok(Transcript {
+            text: stdout.trim().to_string(),
+            language: None,
+            confidence: None,
+        }),
+    }
+}
+
+impl Transcribe for CommandTranscriber {
+    fn name(&self) -> &'static str {
+        "command"
+    }
+
+    async fn transcribe(&self, mut audio: BlobReader) -> Result<Transcript, TranscriptionError> {
+        let file = TempFile {
+            path: std::env::temp_dir().join(format!("kthg-transcribe-{}", utils::random_string())),
+        };
+        let mut out = tokio::fs::File::create(&file.path).await?;
+        while let Some(block) = audio.read(READ_BLOCK_SIZE).await? {
+            out.write_all(&block).await?;
+        }
+        out.flush().await?;
+        drop(out);
+
+        let output = tokio::process::Command::new(&self.program)
+            .args(self.args_for(&file.path.to_string_lossy()))
+            .stdin(Stdio::null())
+            .kill_on_drop(true)
+            .output()
+            .await?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let stderr = stderr.trim();
+            let skip = stderr.chars().count().saturating_sub(MAX_STDERR_CHARS);
+            return Err(TranscriptionError::Failed(
+                output.status,
+                stderr.chars().skip(skip).collect(),
+            ));
+        }
+
+        parse_output(output.stdout)
+    }
+}
@@ -0,0 +1,87 @@
+use super::db_types::*;
+use tokio_postgres::GenericClient;
+
+impl From<tokio_postgres::row::Row> for ContactBlock {
+    // select * from contact_block order only, otherwise it will fail
+    fn from(row: tokio_postgres::Row) -> ContactBlock {
+        ContactBlock {
+            contact_block_id: row.get("contact_block_id"),
+            creation_time: row.get("creation_time"),
+            creator_user_id: row.get("creator_user_id"),
+            target_user_id: row.get("target_user_id"),
+            active: row.get("active"),
+        }
+    }
+}
+
+pub async fn add(
+    con: &mut impl GenericClient,
+    creator_user_id: i64,
+    target_user_id: i64,
+    active: bool,
+) -> Result<ContactBlock, tokio_postgres::Error> {
+    let row = con
+        .query_one(
+            "INSERT INTO
+             contact_block(
+                 creator_user_id,
+                 target_user_id,
+                 active
+             )
+             VALUES($1, $2, $3)
+             RETURNING contact_block_id, creation_time
+            ",
+            &[&creator_user_id, &target_user_id, &active],
+        )
+        .await?;
+
+    // return contact block
+    Ok(ContactBlock {
+        contact_block_id: row.get(0),
+        creation_time: row.get(1),
+        creator_user_id,
+        target_user_id,
+        active,
+    })
+}
+
+// the users this user currently blocks
+pub async fn get_active_by_creator_user_id(
+    con: &mut impl GenericClient,
+    creator_user_id: i64,
+) -> Result<Vec<ContactBlock>, tokio_postgres::Error> {
+    let result = con
+        .query(
+            "SELECT * FROM recent_contact_block
+             WHERE creator_user_id=$1 AND active
+             ORDER BY contact_block_id
+            ",
+            &[&creator_user_id],
+        )
+        .await?
+        .into_iter()
+        .map(|x| x.into())
+        .collect();
+    Ok(result)
+}
+
+// whether either user currently blocks the other
+pub async fn is_blocked_between(
+    con: &mut impl GenericClient,
+    user_id: i64,
+    other_user_id: i64,
+) -> Result<bool, tokio_postgres::Error> {
+    let row = con
+        .query_one(
+            "SELECT EXISTS(
+                 SELECT 1 FROM recent_contact_block
+                 WHERE active
+                 AND ((creator_user_id=$1 AND target_user_id=$2)
+                   OR (creator_user_id=$2 AND target_user_id=$1))
+             )
+            ",
+            &[&user_id, &other_user_id],
+        )
+        .await?;
+    Ok(row.get(0))
+}
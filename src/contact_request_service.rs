@@ -0,0 +1,123 @@
+use super::db_types::*;
+use tokio_postgres::GenericClient;
+
+impl From<tokio_postgres::row::Row> for ContactRequest {
+    // select * from contact_request order only, otherwise it will fail
+    fn from(row: tokio_postgres::Row) -> ContactRequest {
+        ContactRequest {
+            contact_request_id: row.get("contact_request_id"),
+            creation_time: row.get("creation_time"),
+            creator_user_id: row.get("creator_user_id"),
+            target_user_id: row.get("target_user_id"),
+            response_time: row.get("response_time"),
+            accepted: row.get("accepted"),
+        }
+    }
+}
+
+pub async fn add(
+    con: &mut impl GenericClient,
+    creator_user_id: i64,
+    target_user_id: i64,
+) -> Result<ContactRequest, tokio_postgres::Error> {
+    let row = con
+        .query_one(
+            "INSERT INTO
+             contact_request(
+                 creator_user_id,
+                 target_user_id
+             )
+             VALUES($1, $2)
+             RETURNING contact_request_id, creation_time
+            ",
+            &[&creator_user_id, &target_user_id],
+        )
+        .await?;
+
+    // return contact request
+    Ok(ContactRequest {
+        contact_request_id: row.get(0),
+        creation_time: row.get(1),
+        creator_user_id,
+        target_user_id,
+        response_time: None,
+        accepted: None,
+    })
+}
+
+pub async fn get_by_contact_request_id(
+    con: &mut impl GenericClient,
+    contact_request_id: i64,
+) -> Result<Option<ContactRequest>, tokio_postgres::Error> {
+    let result = con
+        .query_opt(
+            "SELECT * FROM contact_request WHERE contact_request_id=$1",
+            &[&contact_request_id],
+        )
+        .await?
+        .map(|x| x.into());
+    Ok(result)
+}
+
+// a request from creator to target that is still waiting for an answer
+pub async fn get_pending(
+    con: &mut impl GenericClient,
+    creator_user_id: i64,
+    target_user_id: i64,
+) -> Result<Option<ContactRequest>, tokio_postgres::Error> {
+    let result = con
+        .query_opt(
+            "SELECT * FROM contact_request
+             WHERE creator_user_id=$1 AND target_user_id=$2 AND response_time IS NULL
+             ORDER BY contact_request_id
+             LIMIT 1
+            ",
+            &[&creator_user_id, &target_user_id],
+        )
+        .await?
+        .map(|x| x.into());
+    Ok(result)
+}
+
+// requests waiting for an answer that the user either sent or received
+pub async fn get_pending_by_user_id(
+    con: &mut impl GenericClient,
+    user_id: i64,
+) -> Result<Vec<ContactRequest>, tokio_postgres::Error> {
+    let result = con
+        .query(
+            "SELECT * FROM contact_request
+             WHERE (creator_user_id=$1 OR target_user_id=$1) AND response_time IS NULL
+             ORDER BY contact_request_id
+            ",
+            &[&user_id],
+        )
+        .await?
+        .into_iter()
+        .map(|x| x.into())
+        .collect();
+    Ok(result)
+}
+
+// records the answer, only if the request hasn't been answered yet
+pub async fn respond(
+    con: &mut impl GenericClient,
+    contact_request_id: i64,
+    accepted: bool,
+    response_time: i64,
+) -> Result<Option<ContactRequest>, tokio_postgres::Error> {
+    let result = con
+        .query_opt(
+            "UPDATE contact_request
+             SET response_time = $3,
+                 accepted = $2
+             WHERE contact_request_id = $1
+             AND response_time IS NULL
+             RETURNING *
+            ",
+            &[&contact_request_id, &accepted, &response_time],
+        )
+        .await?
+        .map(|x| x.into());
+    Ok(result)
+}
@@ -0,0 +1,45 @@
+use super::db_types::*;
+use tokio_postgres::GenericClient;
+
+impl From<tokio_postgres::row::Row> for Contact {
+    // select * from contact order only, otherwise it will fail
+    fn from(row: tokio_postgres::Row) -> Contact {
+        Contact {
+            user_id: row.get("user_id"),
+            contact_user_id: row.get("contact_user_id"),
+            creation_time: row.get("creation_time"),
+            contact_request_id: row.get("contact_request_id"),
+        }
+    }
+}
+
+pub async fn get_by_user_id(
+    con: &mut impl GenericClient,
+    user_id: i64,
+) -> Result<Vec<Contact>, tokio_postgres::Error> {
+    let result = con
+        .query(
+            "SELECT * FROM contact WHERE user_id=$1 ORDER BY contact_user_id",
+            &[&user_id],
+        )
+        .await?
+        .into_iter()
+        .map(|x| x.into())
+        .collect();
+    Ok(result)
+}
+
+pub async fn get_by_user_id_and_contact_user_id(
+    con: &mut impl GenericClient,
+    user_id: i64,
+    contact_user_id: i64,
+) -> Result<Option<Contact>, tokio_postgres::Error> {
+    let result = con
+        .query_opt(
+            "SELECT * FROM contact WHERE user_id=$1 AND contact_user_id=$2",
+            &[&user_id, &contact_user_id],
+        )
+        .await?
+        .map(|x| x.into());
+    Ok(result)
+}
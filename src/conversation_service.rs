@@ -0,0 +1,109 @@
+use super::db_types::*;
+use tokio_postgres::GenericClient;
+
+impl From<tokio_postgres::row::Row> for Conversation {
+    // select user_message.*, other_user_id and unread_count only, otherwise it will fail
+    fn from(row: tokio_postgres::Row) -> Conversation {
+        Conversation {
+            other_user_id: row.get("other_user_id"),
+            unread_count: row.get("unread_count"),
+            last_user_message: row.into(),
+        }
+    }
+}
+
+// every conversation the user has, most recently active first.
+// only messages the user sent, or that have been delivered to them and are still in their inbox, count.
+pub async fn get_by_user_id(
+    con: &mut impl GenericClient,
+    user_id: i64,
+    current_time: i64,
+) -> Result<Vec<Conversation>, tokio_postgres::Error> {
+    let result = con
+        .query(
+            "WITH visible AS (
+                 SELECT
+                     um.*,
+                     CASE WHEN um.creator_user_id = $1
+                         THEN um.target_user_id
+                         ELSE um.creator_user_id
+                     END AS other_user_id
+                 FROM user_message um
+                 WHERE um.creator_user_id = $1
+                 OR (um.target_user_id = $1 AND um.deliver_at <= $2 AND um.target_deleted_time IS NULL)
+             ), unread AS (
+                 SELECT other_user_id, COUNT(*) AS unread_count
+                 FROM visible
+                 WHERE target_user_id = $1 AND played_time IS NULL
+                 GROUP BY other_user_id
+             )
+             SELECT c.*, COALESCE(unread.unread_count, 0) AS unread_count
+             FROM (
+                 SELECT DISTINCT ON (other_user_id) *
+                 FROM visible
+                 ORDER BY other_user_id, user_message_id DESC
+             ) c
+             LEFT JOIN unread ON unread.other_user_id = c.other_user_id
+             ORDER BY c.user_message_id DESC
+            ",
+            &[&user_id, &current_time],
+        )
+        .await?
+        .into_iter()
+        .map(|x| x.into())
+        .collect();
+    Ok(result)
+}
+
+// up to limit messages between the two users, oldest first.
+// with after_user_message_id, the ones right after it. otherwise the ones right before
+// before_user_message_id, or the newest ones if that isn't given either.
+pub async fn get_page(
+    con: &mut impl GenericClient,
+    user_id: i64,
+    other_user_id: i64,
+    current_time: i64,
+    before_user_message_id: Option<i64>,
+    after_user_message_id: Option<i64>,
+    limit: i64,
+) -> Result<Vec<UserMessage>, tokio_postgres::Error> {
+    let sql = [
+        "SELECT * FROM (",
+        " SELECT um.* FROM user_message um",
+        " WHERE (",
+        "   (um.creator_user_id = $1 AND um.target_user_id = $2)",
+        "   OR (um.creator_user_id = $2 AND um.target_user_id = $1",
+        "       AND um.deliver_at <= $3 AND um.target_deleted_time IS NULL)",
+        " )",
+        " AND ($4::bigint IS NULL OR um.user_message_id < $4)",
+        " AND ($5::bigint IS NULL OR um.user_message_id > $5)",
+        if after_user_message_id.is_some() {
+            " ORDER BY um.user_message_id ASC"
+        } else {
+            " ORDER BY um.user_message_id DESC"
+        },
+        " LIMIT $6",
+        ") page ORDER BY user_message_id ASC",
+    ]
+    .join("");
+
+    let stmnt = con.prepare(&sql).await?;
+
+    let result = con
+        .query(
+            &stmnt,
+            &[
+                &user_id,
+                &other_user_id,
+                &current_time,
+                &before_user_message_id,
+                &after_user_message_id,
+                &limit,
+            ],
+        )
+        .await?
+        .into_iter()
+        .map(|x| x.into())
+        .collect();
+    Ok(result)
+}
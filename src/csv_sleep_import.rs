@@ -0,0 +1,123 @@
+use std::io::Read;
+
+use chrono::{DateTime, NaiveDateTime};
+use chrono_tz::Tz;
+
+use crate::sleep_import::{self, SleepImportError, SleepSession};
+
+// header names are matched ignoring case, other columns are ignored
+const START_COLUMNS: [&str; 4] = ["start", "start_time", "sleep_time", "bedtime"];
+const END_COLUMNS: [&str; 3] = ["end", "end_time", "wake_time"];
+
+// tried in order after milliseconds since the epoch and rfc 3339
+const LOCAL_TIME_FORMATS: [&str; 4] = [
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%d %H:%M",
+    "%Y-%m-%dT%H:%M:%S",
+    "%Y-%m-%dT%H:%M",
+];
+
+fn parse_time(s: &str, time_zone: Tz) -> Result<i64, SleepImportError> {
+    if let Ok(millis) = s.parse::<i64>() {
+        return Ok(millis);
+    }
+    if let Ok(time) = DateTime::parse_from_rfc3339(s) {
+        return Ok(time.timestamp_millis());
+    }
+    for format in LOCAL_TIME_FORMATS {
+        if let Ok(naive) = NaiveDateTime::parse_from_str(s, format) {
+            return sleep_import::local_time_millis(naive, time_zone);
+        }
+    }
+    Err(SleepImportError::Malformed(format!(
+        "invalid time: {:?}",
+        s
+    )))
+}
+
+// one session per row
+pub fn parse(data: impl Read, time_zone: Tz) -> Result<Vec<SleepSession>, SleepImportError> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(data);
+
+    let headers = reader.headers()?.clone();
+    let column = |names: &[&str]| {
+        headers
+            .iter()
+            .position(|h| names.iter().any(|n| h.eq_ignore_ascii_case(n)))
+            .ok_or_else(|| SleepImportError::Malformed(format!("no {} column", names[0])))
+    };
+    let start_column = column(&START_COLUMNS)?;
+    let end_column = column(&END_COLUMNS)?;
+
+    let mut sessions = vec![];
+    for record in reader.records() {
+        let record = record?;
+        let line = record.position().map(|x| x.line()).unwrap_or_default();
+        let field = |i| {
+            record
+                .get(i)
+                .filter(|x: &&str| !x.is_empty())
+                .ok_or_else(|| SleepImportError::Malformed(format!("line {}: missing time", line)))
+        };
+        let time = |i| {
+            parse_time(field(i)?, time_zone)
+                .map_err(|e| SleepImportError::Malformed(format!("line {}: {}", line, e)))
+        };
+        sessions.push(SleepSession {
+            start_time: time(start_column)?,
+            end_time: time(end_column)?,
+        });
+    }
+    Ok(sessions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_each_time_format() {
+        let data = include_bytes!("../fixtures/sleep_import/sleep.csv");
+        let sessions = parse(&data[..], chrono_tz::America::New_York).unwrap();
+        assert_eq!(
+            sessions,
+            vec![
+                // local times
+                SleepSession {
+                    start_time: 1706845500000,
+                    end_time: 1706873400000,
+                },
+                // rfc 3339
+                SleepSession {
+                    start_time: 1706933400000,
+                    end_time: 1706961900000,
+                },
+                // milliseconds
+                SleepSession {
+                    start_time: 1707022800000,
+                    end_time: 1707051600000,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_the_bad_line() {
+        let data = include_bytes!("../fixtures/sleep_import/sleep-missing-end.csv");
+        match parse(&data[..], chrono_tz::UTC) {
+            Err(SleepImportError::Malformed(e)) => assert_eq!(e, "line 3: missing time"),
+            x => panic!("expected a malformed line, got {:?}", x),
+        }
+    }
+
+    #[test]
+    fn needs_start_and_end_columns() {
+        let data = b"from,to\n2024-02-01 22:45,2024-02-02 06:30\n";
+        assert!(matches!(
+            parse(&data[..], chrono_tz::UTC),
+            Err(SleepImportError::Malformed(_))
+        ));
+    }
+}
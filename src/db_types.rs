@@ -1,10 +1,61 @@
+use serde::{Deserialize, Serialize};
+
 #[derive(Clone, Debug)]
 pub struct UserMessage {
     pub user_message_id: i64,
     pub creation_time: i64,
     pub creator_user_id: i64,
     pub target_user_id: i64,
-    pub audio_data: Vec<u8>,
+    pub kind: UserMessageKind,
+    // only audio messages have audio. legacy audio also has no key until
+    // `kthg migrate-blobs` has moved it out of postgres, so never assume it's there
+    pub audio_blob_key: Option<String>,
+    pub audio_size: Option<i64>,
+    pub audio_sha256: Option<String>,
+    pub audio_duration_millis: Option<i64>,
+    // required for text messages, a caption on audio ones
+    pub text_body: Option<String>,
+    pub deliver_at: Option<i64>,
+    pub ephemeral: bool,
+    pub played_time: Option<i64>,
+    pub target_deleted_time: Option<i64>,
+    pub reply_to_user_message_id: Option<i64>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum UserMessageKind {
+    Audio,
+    Text,
+}
+
+impl From<UserMessageKind> for i64 {
+    fn from(kind: UserMessageKind) -> i64 {
+        match kind {
+            UserMessageKind::Audio => 0,
+            UserMessageKind::Text => 1,
+        }
+    }
+}
+
+impl TryFrom<i64> for UserMessageKind {
+    type Error = i64;
+    fn try_from(kind: i64) -> Result<UserMessageKind, i64> {
+        match kind {
+            0 => Ok(UserMessageKind::Audio),
+            1 => Ok(UserMessageKind::Text),
+            x => Err(x),
+        }
+    }
+}
+
+// the messages between a user and somebody else
+#[derive(Clone, Debug)]
+pub struct Conversation {
+    pub other_user_id: i64,
+    pub last_user_message: UserMessage,
+    // delivered to the user but not played yet
+    pub unread_count: i64,
 }
 
 #[derive(Clone, Debug)]
@@ -12,4 +63,411 @@ pub struct SleepEvent {
     pub sleep_event_id: i64,
     pub creation_time: i64,
     pub creator_user_id: i64,
+    pub kind: SleepEventKind,
+    pub source: SleepEventSource,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum SleepEventKind {
+    Sleep,
+    Wake,
+}
+
+impl From<SleepEventKind> for i64 {
+    fn from(kind: SleepEventKind) -> i64 {
+        match kind {
+            SleepEventKind::Sleep => 0,
+            SleepEventKind::Wake => 1,
+        }
+    }
+}
+
+impl TryFrom<i64> for SleepEventKind {
+    type Error = i64;
+    fn try_from(kind: i64) -> Result<SleepEventKind, i64> {
+        match kind {
+            0 => Ok(SleepEventKind::Sleep),
+            1 => Ok(SleepEventKind::Wake),
+            x => Err(x),
+        }
+    }
+}
+
+// how a sleep event came to be recorded
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum SleepEventSource {
+    // the app, as it happened
+    App,
+    // a device posting to /public/query_params_sleep_event_new, as it happened
+    Device,
+    // added afterwards by the user
+    Backfill,
+    // brought in from another app's export
+    Import,
+}
+
+impl From<SleepEventSource> for i64 {
+    fn from(source: SleepEventSource) -> i64 {
+        match source {
+            SleepEventSource::App => 0,
+            SleepEventSource::Device => 1,
+            SleepEventSource::Backfill => 2,
+            SleepEventSource::Import => 3,
+        }
+    }
+}
+
+impl TryFrom<i64> for SleepEventSource {
+    type Error = i64;
+    fn try_from(source: i64) -> Result<SleepEventSource, i64> {
+        match source {
+            0 => Ok(SleepEventSource::App),
+            1 => Ok(SleepEventSource::Device),
+            2 => Ok(SleepEventSource::Backfill),
+            3 => Ok(SleepEventSource::Import),
+            x => Err(x),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SleepEventEdit {
+    pub sleep_event_edit_id: i64,
+    pub creation_time: i64,
+    pub creator_user_id: i64,
+    pub sleep_event_id: i64,
+    pub action: SleepEventEditAction,
+    // none for backfills
+    pub old_kind: Option<SleepEventKind>,
+    pub old_time: Option<i64>,
+    // none for deletions
+    pub new_kind: Option<SleepEventKind>,
+    pub new_time: Option<i64>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum SleepEventEditAction {
+    Backfill,
+    Update,
+    Delete,
+}
+
+impl From<SleepEventEditAction> for i64 {
+    fn from(action: SleepEventEditAction) -> i64 {
+        match action {
+            SleepEventEditAction::Backfill => 0,
+            SleepEventEditAction::Update => 1,
+            SleepEventEditAction::Delete => 2,
+        }
+    }
+}
+
+impl TryFrom<i64> for SleepEventEditAction {
+    type Error = i64;
+    fn try_from(action: i64) -> Result<SleepEventEditAction, i64> {
+        match action {
+            0 => Ok(SleepEventEditAction::Backfill),
+            1 => Ok(SleepEventEditAction::Update),
+            2 => Ok(SleepEventEditAction::Delete),
+            x => Err(x),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct AudioBlob {
+    pub audio_sha256: String,
+    pub creation_time: i64,
+    pub audio_blob_key: String,
+    pub audio_size: i64,
+    pub reference_count: i64,
+}
+
+#[derive(Clone, Debug)]
+pub struct Household {
+    pub household_id: i64,
+    pub creation_time: i64,
+    pub creator_user_id: i64,
+    pub name: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum HouseholdRole {
+    Admin,
+    Member,
+}
+
+impl From<HouseholdRole> for i64 {
+    fn from(role: HouseholdRole) -> i64 {
+        match role {
+            HouseholdRole::Admin => 0,
+            HouseholdRole::Member => 1,
+        }
+    }
+}
+
+impl TryFrom<i64> for HouseholdRole {
+    type Error = i64;
+    fn try_from(role: i64) -> Result<HouseholdRole, i64> {
+        match role {
+            0 => Ok(HouseholdRole::Admin),
+            1 => Ok(HouseholdRole::Member),
+            x => Err(x),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct HouseholdMembership {
+    pub household_membership_id: i64,
+    pub creation_time: i64,
+    pub creator_user_id: i64,
+    pub household_id: i64,
+    pub user_id: i64,
+    pub role: HouseholdRole,
+    pub active: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct HouseholdInvite {
+    pub household_invite_code: String,
+    pub creation_time: i64,
+    pub creator_user_id: i64,
+    pub household_id: i64,
+    pub role: HouseholdRole,
+    pub expiry_time: i64,
+    pub accepted_user_id: Option<i64>,
+    pub accepted_time: Option<i64>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ContactRequest {
+    pub contact_request_id: i64,
+    pub creation_time: i64,
+    pub creator_user_id: i64,
+    pub target_user_id: i64,
+    pub response_time: Option<i64>,
+    pub accepted: Option<bool>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Contact {
+    pub user_id: i64,
+    pub contact_user_id: i64,
+    pub creation_time: i64,
+    pub contact_request_id: i64,
+}
+
+#[derive(Clone, Debug)]
+pub struct ContactBlock {
+    pub contact_block_id: i64,
+    pub creation_time: i64,
+    pub creator_user_id: i64,
+    pub target_user_id: i64,
+    pub active: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct SleepStatusShare {
+    pub sleep_status_share_id: i64,
+    pub creation_time: i64,
+    pub creator_user_id: i64,
+    pub target_user_id: i64,
+    pub active: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct SleepRule {
+    pub sleep_rule_id: i64,
+    pub creation_time: i64,
+    pub creator_user_id: i64,
+    pub kind: SleepEventKind,
+    pub source: Option<SleepEventSource>,
+    pub time_zone: Option<String>,
+    pub window_start_minute: Option<i64>,
+    pub window_end_minute: Option<i64>,
+    pub active: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum SleepRuleActionKind {
+    // tell another user, over their push feed
+    Push,
+    // play a message in the user's inbox on their devices
+    DeliverUserMessage,
+    // hold the user's incoming messages for a while
+    DoNotDisturb,
+}
+
+impl From<SleepRuleActionKind> for i64 {
+    fn from(action: SleepRuleActionKind) -> i64 {
+        match action {
+            SleepRuleActionKind::Push => 0,
+            SleepRuleActionKind::DeliverUserMessage => 1,
+            SleepRuleActionKind::DoNotDisturb => 2,
+        }
+    }
+}
+
+impl TryFrom<i64> for SleepRuleActionKind {
+    type Error = i64;
+    fn try_from(action: i64) -> Result<SleepRuleActionKind, i64> {
+        match action {
+            0 => Ok(SleepRuleActionKind::Push),
+            1 => Ok(SleepRuleActionKind::DeliverUserMessage),
+            2 => Ok(SleepRuleActionKind::DoNotDisturb),
+            x => Err(x),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SleepRuleAction {
+    pub sleep_rule_id: i64,
+    pub position: i64,
+    pub action: SleepRuleActionKind,
+    // for pushes
+    pub target_user_id: Option<i64>,
+    pub text_body: Option<String>,
+    // for deliveries
+    pub user_message_id: Option<i64>,
+    // for do not disturb, none means until the user wakes up
+    pub duration_millis: Option<i64>,
+}
+
+#[derive(Clone, Debug)]
+pub struct DoNotDisturb {
+    pub do_not_disturb_id: i64,
+    pub creation_time: i64,
+    pub creator_user_id: i64,
+    pub sleep_rule_id: Option<i64>,
+    pub end_time: Option<i64>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Alarm {
+    pub alarm_id: i64,
+    pub creation_time: i64,
+    pub creator_user_id: i64,
+    pub target_user_id: i64,
+    pub recurrence: String,
+    pub time_zone: String,
+    pub start_time: i64,
+    pub active: bool,
+    pub next_fire_time: Option<i64>,
+    pub snooze_until: Option<i64>,
+    pub last_fire_time: Option<i64>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Job {
+    pub job_id: i64,
+    pub creation_time: i64,
+    pub kind: String,
+    pub payload: String,
+    pub unique_key: Option<String>,
+    pub run_at: i64,
+    pub attempts: i64,
+    pub max_attempts: i64,
+    pub lock_token: Option<String>,
+    pub locked_until: Option<i64>,
+    pub last_error: Option<String>,
+    pub finish_time: Option<i64>,
+    pub succeeded: Option<bool>,
+}
+
+#[derive(Clone, Debug)]
+pub struct RetentionPolicy {
+    pub retention_policy_id: i64,
+    pub creation_time: i64,
+    pub creator_user_id: i64,
+    pub user_id: Option<i64>,
+    pub household_id: Option<i64>,
+    pub keep_days: Option<i64>,
+    pub keep_last: Option<i64>,
+    pub delete_after_played: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct UserMessageReaction {
+    pub user_message_reaction_id: i64,
+    pub creation_time: i64,
+    pub creator_user_id: i64,
+    pub user_message_id: i64,
+    pub reaction: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct AudioTranscript {
+    pub audio_sha256: String,
+    pub creation_time: i64,
+    // which backend produced it
+    pub transcriber: String,
+    pub text: String,
+    pub language: Option<String>,
+    // between 0 and 1, if the backend reports it
+    pub confidence: Option<f64>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Checkpoint {
+    pub checkpoint_id: i64,
+    pub creation_time: i64,
+    pub creator_user_id: i64,
+}
+
+#[derive(Clone, Debug)]
+pub struct LiveTask {
+    pub checkpoint_id: i64,
+    pub live_task_id: String,
+    pub position: i64,
+    pub value: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct FinishedTask {
+    pub checkpoint_id: i64,
+    pub finished_task_id: String,
+    pub position: i64,
+    pub value: String,
+    pub status: i64,
+}
+
+#[derive(Clone, Debug)]
+pub struct BedtimeRoutine {
+    pub bedtime_routine_id: i64,
+    pub creation_time: i64,
+    pub creator_user_id: i64,
+}
+
+#[derive(Clone, Debug)]
+pub struct BedtimeRoutineTask {
+    pub bedtime_routine_id: i64,
+    // the id the task has on the user's task list
+    pub bedtime_routine_task_id: String,
+    pub position: i64,
+    pub value: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct BedtimeRoutineSnapshot {
+    pub sleep_event_id: i64,
+    pub bedtime_routine_id: i64,
+    pub checkpoint_id: Option<i64>,
+    pub task_count: i64,
+    pub finished_count: i64,
+}
+
+// a night the user went to sleep with a bedtime routine, and when they woke up
+#[derive(Clone, Debug)]
+pub struct RoutineNight {
+    pub sleep_time: i64,
+    // none if they haven't woken up since, or slept again without waking up first
+    pub wake_time: Option<i64>,
+    pub snapshot: BedtimeRoutineSnapshot,
 }
@@ -0,0 +1,57 @@
+use super::db_types::*;
+use tokio_postgres::GenericClient;
+
+impl From<tokio_postgres::row::Row> for DoNotDisturb {
+    // select * from do_not_disturb order only, otherwise it will fail
+    fn from(row: tokio_postgres::Row) -> DoNotDisturb {
+        DoNotDisturb {
+            do_not_disturb_id: row.get("do_not_disturb_id"),
+            creation_time: row.get("creation_time"),
+            creator_user_id: row.get("creator_user_id"),
+            sleep_rule_id: row.get("sleep_rule_id"),
+            end_time: row.get("end_time"),
+        }
+    }
+}
+
+pub async fn add(
+    con: &mut impl GenericClient,
+    creator_user_id: i64,
+    sleep_rule_id: Option<i64>,
+    end_time: Option<i64>,
+) -> Result<DoNotDisturb, tokio_postgres::Error> {
+    let row = con
+        .query_one(
+            "INSERT INTO
+             do_not_disturb(
+                 creator_user_id,
+                 sleep_rule_id,
+                 end_time
+             )
+             VALUES($1, $2, $3)
+             RETURNING *
+            ",
+            &[&creator_user_id, &sleep_rule_id, &end_time],
+        )
+        .await?;
+
+    Ok(row.into())
+}
+
+pub async fn get_recent_by_creator_user_id(
+    con: &mut impl GenericClient,
+    creator_user_id: i64,
+) -> Result<Option<DoNotDisturb>, tokio_postgres::Error> {
+    let result = con
+        .query_opt(
+            "SELECT * FROM do_not_disturb
+             WHERE creator_user_id=$1
+             ORDER BY do_not_disturb_id DESC
+             LIMIT 1
+            ",
+            &[&creator_user_id],
+        )
+        .await?
+        .map(|x| x.into());
+    Ok(result)
+}
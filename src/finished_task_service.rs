@@ -0,0 +1,136 @@
+use super::db_types::*;
+use tokio_postgres::GenericClient;
+
+impl From<tokio_postgres::row::Row> for FinishedTask {
+    // select * from finished_task order only, otherwise it will fail
+    fn from(row: tokio_postgres::Row) -> FinishedTask {
+        FinishedTask {
+            checkpoint_id: row.get("checkpoint_id"),
+            finished_task_id: row.get("finished_task_id"),
+            position: row.get("position"),
+            value: row.get("value"),
+            status: row.get("status"),
+        }
+    }
+}
+
+pub async fn add(
+    con: &mut impl GenericClient,
+    checkpoint_id: i64,
+    finished_task_id: String,
+    position: i64,
+    value: String,
+    status: i64,
+) -> Result<FinishedTask, tokio_postgres::Error> {
+    con.execute(
+        "INSERT INTO
+         finished_task(
+             checkpoint_id,
+             finished_task_id,
+             position,
+             value,
+             status
+         )
+         VALUES($1, $2, $3, $4, $5)
+        ",
+        &[
+            &checkpoint_id,
+            &finished_task_id,
+            &position,
+            &value,
+            &status,
+        ],
+    )
+    .await?;
+
+    // return finished task
+    Ok(FinishedTask {
+        checkpoint_id,
+        finished_task_id,
+        position,
+        value,
+        status,
+    })
+}
+
+// the checkpoint's finished tasks, in order
+pub async fn get_by_checkpoint_id(
+    con: &mut impl GenericClient,
+    checkpoint_id: i64,
+) -> Result<Vec<FinishedTask>, tokio_postgres::Error> {
+    let result = con
+        .query(
+            "SELECT * FROM finished_task
+             WHERE checkpoint_id=$1
+             ORDER BY position
+            ",
+            &[&checkpoint_id],
+        )
+        .await?
+        .into_iter()
+        .map(|x| x.into())
+        .collect();
+    Ok(result)
+}
+
+// every checkpoint of the creator's that has this task finished, oldest first
+pub async fn get_by_creator_user_id_and_finished_task_id(
+    con: &mut impl GenericClient,
+    creator_user_id: i64,
+    finished_task_id: String,
+) -> Result<Vec<FinishedTask>, tokio_postgres::Error> {
+    let result = con
+        .query(
+            "SELECT ft.* FROM finished_task ft
+             INNER JOIN checkpoint c ON c.checkpoint_id = ft.checkpoint_id
+             WHERE c.creator_user_id=$1
+             AND ft.finished_task_id=$2
+             ORDER BY ft.checkpoint_id
+            ",
+            &[&creator_user_id, &finished_task_id],
+        )
+        .await?
+        .into_iter()
+        .map(|x| x.into())
+        .collect();
+    Ok(result)
+}
+
+// the tasks that were finished on the creator's last checkpoint before min_creation_time,
+// and have stayed finished on every checkpoint they saved from then until max_creation_time
+pub async fn get_ids_finished_throughout(
+    con: &mut impl GenericClient,
+    creator_user_id: i64,
+    min_creation_time: i64,
+    max_creation_time: i64,
+) -> Result<Vec<String>, tokio_postgres::Error> {
+    let result = con
+        .query(
+            "SELECT ft.finished_task_id FROM finished_task ft
+             WHERE ft.checkpoint_id = (
+                 SELECT checkpoint_id FROM checkpoint
+                 WHERE creator_user_id=$1
+                 AND creation_time < $2
+                 ORDER BY creation_time DESC, checkpoint_id DESC
+                 LIMIT 1
+             )
+             AND NOT EXISTS (
+                 SELECT 1 FROM checkpoint c
+                 WHERE c.creator_user_id=$1
+                 AND c.creation_time >= $2
+                 AND c.creation_time <= $3
+                 AND NOT EXISTS (
+                     SELECT 1 FROM finished_task f
+                     WHERE f.checkpoint_id = c.checkpoint_id
+                     AND f.finished_task_id = ft.finished_task_id
+                 )
+             )
+            ",
+            &[&creator_user_id, &min_creation_time, &max_creation_time],
+        )
+        .await?
+        .into_iter()
+        .map(|x| x.get(0))
+        .collect();
+    Ok(result)
+}
@@ -0,0 +1,96 @@
+use std::io::Read;
+
+use chrono::NaiveDateTime;
+use chrono_tz::Tz;
+use serde::Deserialize;
+
+use crate::sleep_import::{self, SleepImportError, SleepSession};
+
+// fitbit writes local times without an offset
+const TIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.f";
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FitbitSleep {
+    start_time: String,
+    end_time: String,
+}
+
+// data exports hold a bare list, the web api wraps it in an object
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum FitbitSleepFile {
+    Export(Vec<FitbitSleep>),
+    Api { sleep: Vec<FitbitSleep> },
+}
+
+fn parse_time(s: &str, time_zone: Tz) -> Result<i64, SleepImportError> {
+    let naive = NaiveDateTime::parse_from_str(s, TIME_FORMAT)
+        .map_err(|_| SleepImportError::Malformed(format!("invalid time: {}", s)))?;
+    sleep_import::local_time_millis(naive, time_zone)
+}
+
+// every sleep log is a session, naps included
+pub fn parse(data: impl Read, time_zone: Tz) -> Result<Vec<SleepSession>, SleepImportError> {
+    let logs = match serde_json::from_reader(std::io::BufReader::new(data))? {
+        FitbitSleepFile::Export(logs) => logs,
+        FitbitSleepFile::Api { sleep } => sleep,
+    };
+
+    logs.iter()
+        .map(|x| {
+            Ok(SleepSession {
+                start_time: parse_time(&x.start_time, time_zone)?,
+                end_time: parse_time(&x.end_time, time_zone)?,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_data_export() {
+        let data = include_bytes!("../fixtures/sleep_import/fitbit.json");
+        let sessions = parse(&data[..], chrono_tz::America::New_York).unwrap();
+        assert_eq!(
+            sessions,
+            vec![
+                // 2023-03-11 23:04:30 EST to 2023-03-12 07:15:00 EDT, across the clocks going forward
+                SleepSession {
+                    start_time: 1678593870000,
+                    end_time: 1678619700000,
+                },
+                // a nap, 2023-03-12 14:00 to 14:45 EDT
+                SleepSession {
+                    start_time: 1678644000000,
+                    end_time: 1678646700000,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_api_response() {
+        let data = include_bytes!("../fixtures/sleep_import/fitbit-api.json");
+        let sessions = parse(&data[..], chrono_tz::UTC).unwrap();
+        assert_eq!(
+            sessions,
+            vec![SleepSession {
+                start_time: 1704150000000,
+                end_time: 1704178800000,
+            }]
+        );
+    }
+
+    #[test]
+    fn rejects_bad_times() {
+        let data = br#"[{"startTime": "yesterday", "endTime": "2024-01-02T07:00:00.000"}]"#;
+        assert!(matches!(
+            parse(&data[..], chrono_tz::UTC),
+            Err(SleepImportError::Malformed(_))
+        ));
+    }
+}
@@ -1,5 +1,6 @@
 use super::AppData;
 
+use actix_web::error::JsonPayloadError;
 use actix_web::rt;
 use actix_web::{
     http::StatusCode, web, Error, HttpRequest, HttpResponse, Responder, ResponseError,
@@ -7,14 +8,48 @@ use actix_web::{
 use auth_service_api::response::{AuthError, User};
 use base64::Engine;
 use derive_more::Display;
+use futures_util::StreamExt;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tokio_postgres::GenericClient;
 
-use crate::db_types::SleepEvent;
-use crate::db_types::UserMessage;
+use crate::alarm_service;
+use crate::alarm_user_message_service;
+use crate::audio_transcript_service;
+use crate::blob_store::BlobStoreError;
+use crate::checkpoint_service;
+use crate::contact_block_service;
+use crate::contact_request_service;
+use crate::contact_service;
+use crate::conversation_service;
+use crate::db_types::Alarm;
+use crate::db_types::AudioTranscript;
+use crate::db_types::RetentionPolicy;
+use crate::db_types::RoutineNight;
+use crate::db_types::{BedtimeRoutine, BedtimeRoutineTask};
+use crate::db_types::{Checkpoint, FinishedTask, LiveTask};
+use crate::db_types::{Contact, ContactBlock, ContactRequest};
+use crate::db_types::{Conversation, UserMessage, UserMessageReaction};
+use crate::db_types::{DoNotDisturb, SleepRule, SleepRuleAction};
+use crate::db_types::{Household, HouseholdInvite, HouseholdMembership, HouseholdRole};
+use crate::db_types::{
+    SleepEvent, SleepEventEdit, SleepEventKind, SleepEventSource, SleepStatusShare,
+};
+use crate::household_membership_service;
+use crate::household_service;
 use crate::response;
 use crate::sleep_event_service;
+use crate::sleep_import::SleepImportError;
+use crate::sleep_status_share_service;
+use crate::transcriber::TranscriptionError;
+use crate::user_message_reaction_service;
 use crate::user_message_service;
-use crate::{manage_user_message, request};
+use crate::utils;
+use crate::{
+    manage_alarm, manage_bedtime_routine, manage_checkpoint, manage_contact, manage_household,
+    manage_push, manage_reaction, manage_retention, manage_sleep_event, manage_sleep_export,
+    manage_sleep_import, manage_sleep_rule, manage_sleep_status, manage_user_message, request,
+};
 
 #[derive(Clone, Debug, Serialize, Deserialize, Display)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
@@ -22,8 +57,13 @@ pub enum AppError {
     DecodeError,
     InternalServerError,
     Unauthorized,
+    Forbidden,
     BadRequest,
     NotFound,
+    MessageTooLarge,
+    MessageTooLong,
+    QuotaExceeded,
+    ChecksumMismatch,
     Unknown,
 }
 
@@ -36,8 +76,13 @@ impl ResponseError for AppError {
             AppError::DecodeError => StatusCode::BAD_GATEWAY,
             AppError::InternalServerError => StatusCode::INTERNAL_SERVER_ERROR,
             AppError::Unauthorized => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden => StatusCode::FORBIDDEN,
             AppError::BadRequest => StatusCode::BAD_REQUEST,
             AppError::NotFound => StatusCode::NOT_FOUND,
+            AppError::MessageTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            AppError::MessageTooLong => StatusCode::PAYLOAD_TOO_LARGE,
+            AppError::QuotaExceeded => StatusCode::INSUFFICIENT_STORAGE,
+            AppError::ChecksumMismatch => StatusCode::UNPROCESSABLE_ENTITY,
             AppError::Unknown => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
@@ -68,6 +113,36 @@ pub fn report_base64_err(e: base64::DecodeError) -> AppError {
     AppError::DecodeError
 }
 
+pub fn report_blob_store_err(e: BlobStoreError) -> AppError {
+    match e {
+        BlobStoreError::NotFound => AppError::NotFound,
+        e => {
+            log::error!("blob store: {}", e);
+            AppError::InternalServerError
+        }
+    }
+}
+
+pub fn report_transcription_err(e: TranscriptionError) -> AppError {
+    log::error!("transcription: {}", e);
+    AppError::InternalServerError
+}
+
+pub fn report_sleep_import_err(e: SleepImportError) -> AppError {
+    log::info!("sleep import: {}", e);
+    AppError::BadRequest
+}
+
+// json bodies that are too big are most likely oversized audio
+pub fn json_error_handler(e: JsonPayloadError, _req: &HttpRequest) -> Error {
+    match e {
+        JsonPayloadError::Overflow { .. } | JsonPayloadError::OverflowKnownLength { .. } => {
+            AppError::MessageTooLarge.into()
+        }
+        e => e.into(),
+    }
+}
+
 pub fn report_auth_err(e: AuthError) -> AppError {
     match e {
         AuthError::ApiKeyNonexistent => AppError::Unauthorized,
@@ -96,19 +171,460 @@ pub async fn get_user_if_api_key_valid(
         .map_err(report_auth_err)
 }
 
-pub fn fill_user_message(x: UserMessage) -> response::UserMessage {
+// what is sent along with a message, besides the message itself
+#[derive(Clone, Debug, Default)]
+pub struct UserMessageMetadata {
+    pub reactions: Vec<UserMessageReaction>,
+    pub transcript: Option<AudioTranscript>,
+}
+
+pub fn fill_user_message(x: UserMessage, metadata: UserMessageMetadata) -> response::UserMessage {
     response::UserMessage {
+        user_message_id: x.user_message_id,
         creation_time: x.creation_time,
         creator_user_id: x.creator_user_id,
         target_user_id: x.target_user_id,
-        audio_data: base64::engine::general_purpose::STANDARD_NO_PAD.encode(&x.audio_data),
+        kind: x.kind,
+        audio_size: x.audio_size,
+        audio_sha256: x.audio_sha256,
+        audio_duration_millis: x.audio_duration_millis,
+        text_body: x.text_body,
+        deliver_at: x.deliver_at,
+        ephemeral: x.ephemeral,
+        played_time: x.played_time,
+        reply_to_user_message_id: x.reply_to_user_message_id,
+        reactions: metadata
+            .reactions
+            .into_iter()
+            .map(fill_user_message_reaction)
+            .collect(),
+        transcript: metadata.transcript.map(fill_audio_transcript),
+    }
+}
+
+pub fn fill_user_message_reaction(x: UserMessageReaction) -> response::UserMessageReaction {
+    response::UserMessageReaction {
+        user_message_reaction_id: x.user_message_reaction_id,
+        creation_time: x.creation_time,
+        creator_user_id: x.creator_user_id,
+        user_message_id: x.user_message_id,
+        reaction: x.reaction,
+    }
+}
+
+pub fn fill_audio_transcript(x: AudioTranscript) -> response::AudioTranscript {
+    response::AudioTranscript {
+        creation_time: x.creation_time,
+        transcriber: x.transcriber,
+        text: x.text,
+        language: x.language,
+        confidence: x.confidence,
+    }
+}
+
+// the metadata of each of these messages, all loaded at once
+async fn get_metadata_by_user_message_id(
+    con: &mut impl GenericClient,
+    ums: &[&UserMessage],
+) -> Result<HashMap<i64, UserMessageMetadata>, AppError> {
+    let ids: Vec<i64> = ums.iter().map(|x| x.user_message_id).collect();
+    let audio_sha256s: Vec<String> = ums.iter().filter_map(|x| x.audio_sha256.clone()).collect();
+
+    let mut metadata: HashMap<i64, UserMessageMetadata> = HashMap::new();
+    for x in user_message_reaction_service::get_by_user_message_ids(con, &ids)
+        .await
+        .map_err(report_postgres_err)?
+    {
+        metadata
+            .entry(x.user_message_id)
+            .or_default()
+            .reactions
+            .push(x);
+    }
+
+    // copies of a message share their audio, and so their transcript
+    let transcripts: HashMap<String, AudioTranscript> =
+        audio_transcript_service::get_by_audio_sha256s(con, &audio_sha256s)
+            .await
+            .map_err(report_postgres_err)?
+            .into_iter()
+            .map(|x| (x.audio_sha256.clone(), x))
+            .collect();
+    for um in ums {
+        if let Some(t) = um.audio_sha256.as_ref().and_then(|x| transcripts.get(x)) {
+            metadata.entry(um.user_message_id).or_default().transcript = Some(t.clone());
+        }
+    }
+
+    Ok(metadata)
+}
+
+pub async fn fill_user_messages_with_metadata(
+    con: &mut impl GenericClient,
+    ums: Vec<UserMessage>,
+) -> Result<Vec<response::UserMessage>, AppError> {
+    let mut metadata =
+        get_metadata_by_user_message_id(con, &ums.iter().collect::<Vec<_>>()).await?;
+    Ok(ums
+        .into_iter()
+        .map(|x| {
+            let m = metadata.remove(&x.user_message_id).unwrap_or_default();
+            fill_user_message(x, m)
+        })
+        .collect())
+}
+
+pub async fn fill_user_message_with_metadata(
+    con: &mut impl GenericClient,
+    x: UserMessage,
+) -> Result<response::UserMessage, AppError> {
+    let mut metadata = get_metadata_by_user_message_id(con, &[&x]).await?;
+    let m = metadata.remove(&x.user_message_id).unwrap_or_default();
+    Ok(fill_user_message(x, m))
+}
+
+pub async fn fill_conversations_with_metadata(
+    con: &mut impl GenericClient,
+    conversations: Vec<Conversation>,
+) -> Result<Vec<response::Conversation>, AppError> {
+    let ums: Vec<&UserMessage> = conversations.iter().map(|x| &x.last_user_message).collect();
+    let mut metadata = get_metadata_by_user_message_id(con, &ums).await?;
+    Ok(conversations
+        .into_iter()
+        .map(|x| {
+            let m = metadata
+                .remove(&x.last_user_message.user_message_id)
+                .unwrap_or_default();
+            fill_conversation(x, m)
+        })
+        .collect())
+}
+
+pub fn fill_conversation(x: Conversation, metadata: UserMessageMetadata) -> response::Conversation {
+    response::Conversation {
+        other_user_id: x.other_user_id,
+        last_user_message: fill_user_message(x.last_user_message, metadata),
+        unread_count: x.unread_count,
     }
 }
 
 pub fn fill_sleep_event(x: SleepEvent) -> response::SleepEvent {
     response::SleepEvent {
+        sleep_event_id: x.sleep_event_id,
+        creation_time: x.creation_time,
+        creator_user_id: x.creator_user_id,
+        kind: x.kind,
+        source: x.source,
+    }
+}
+
+pub fn fill_sleep_event_edit(x: SleepEventEdit) -> response::SleepEventEdit {
+    response::SleepEventEdit {
+        sleep_event_edit_id: x.sleep_event_edit_id,
+        creation_time: x.creation_time,
+        creator_user_id: x.creator_user_id,
+        sleep_event_id: x.sleep_event_id,
+        action: x.action,
+        old_kind: x.old_kind,
+        old_time: x.old_time,
+        new_kind: x.new_kind,
+        new_time: x.new_time,
+    }
+}
+
+pub fn fill_sleep_import_report(
+    x: manage_sleep_import::SleepImportReport,
+) -> response::SleepImportReport {
+    response::SleepImportReport {
+        imported_count: x.imported.len() as i64,
+        skipped_count: x.skipped.len() as i64,
+        imported: x
+            .imported
+            .into_iter()
+            .map(|x| response::ImportedSleepSession {
+                start_time: x.session.start_time,
+                end_time: x.session.end_time,
+                sleep_event_id: x.sleep_event.sleep_event_id,
+                wake_event_id: x.wake_event.sleep_event_id,
+            })
+            .collect(),
+        skipped: x
+            .skipped
+            .into_iter()
+            .map(|x| response::SkippedSleepSession {
+                start_time: x.session.start_time,
+                end_time: x.session.end_time,
+                reason: x.reason,
+            })
+            .collect(),
+    }
+}
+
+pub fn fill_household(x: Household) -> response::Household {
+    response::Household {
+        household_id: x.household_id,
+        creation_time: x.creation_time,
+        creator_user_id: x.creator_user_id,
+        name: x.name,
+    }
+}
+
+pub fn fill_household_membership(x: HouseholdMembership) -> response::HouseholdMembership {
+    response::HouseholdMembership {
+        household_membership_id: x.household_membership_id,
+        creation_time: x.creation_time,
+        creator_user_id: x.creator_user_id,
+        household_id: x.household_id,
+        user_id: x.user_id,
+        role: x.role,
+        active: x.active,
+    }
+}
+
+pub fn fill_household_invite(x: HouseholdInvite) -> response::HouseholdInvite {
+    response::HouseholdInvite {
+        household_invite_code: x.household_invite_code,
+        creation_time: x.creation_time,
+        creator_user_id: x.creator_user_id,
+        household_id: x.household_id,
+        role: x.role,
+        expiry_time: x.expiry_time,
+    }
+}
+
+pub fn fill_contact_request(x: ContactRequest) -> response::ContactRequest {
+    response::ContactRequest {
+        contact_request_id: x.contact_request_id,
+        creation_time: x.creation_time,
+        creator_user_id: x.creator_user_id,
+        target_user_id: x.target_user_id,
+        response_time: x.response_time,
+        accepted: x.accepted,
+    }
+}
+
+pub fn fill_contact(x: Contact) -> response::Contact {
+    response::Contact {
+        contact_user_id: x.contact_user_id,
+        creation_time: x.creation_time,
+    }
+}
+
+pub fn fill_contact_block(x: ContactBlock) -> response::ContactBlock {
+    response::ContactBlock {
+        contact_block_id: x.contact_block_id,
+        creation_time: x.creation_time,
+        creator_user_id: x.creator_user_id,
+        target_user_id: x.target_user_id,
+        active: x.active,
+    }
+}
+
+pub fn fill_sleep_status_share(x: SleepStatusShare) -> response::SleepStatusShare {
+    response::SleepStatusShare {
+        sleep_status_share_id: x.sleep_status_share_id,
+        creation_time: x.creation_time,
+        creator_user_id: x.creator_user_id,
+        target_user_id: x.target_user_id,
+        active: x.active,
+    }
+}
+
+pub fn fill_sleep_rule(x: SleepRule, actions: Vec<SleepRuleAction>) -> response::SleepRule {
+    response::SleepRule {
+        sleep_rule_id: x.sleep_rule_id,
+        creation_time: x.creation_time,
+        creator_user_id: x.creator_user_id,
+        kind: x.kind,
+        source: x.source,
+        time_zone: x.time_zone,
+        window_start_minute: x.window_start_minute,
+        window_end_minute: x.window_end_minute,
+        active: x.active,
+        actions: actions
+            .into_iter()
+            .map(|x| response::SleepRuleAction {
+                action: x.action,
+                target_user_id: x.target_user_id,
+                text_body: x.text_body,
+                user_message_id: x.user_message_id,
+                duration_millis: x.duration_millis,
+            })
+            .collect(),
+    }
+}
+
+pub fn fill_do_not_disturb(x: DoNotDisturb) -> response::DoNotDisturb {
+    response::DoNotDisturb {
+        do_not_disturb_id: x.do_not_disturb_id,
+        creation_time: x.creation_time,
+        creator_user_id: x.creator_user_id,
+        sleep_rule_id: x.sleep_rule_id,
+        end_time: x.end_time,
+    }
+}
+
+pub fn fill_alarm(x: Alarm, user_message_ids: Vec<i64>) -> response::Alarm {
+    response::Alarm {
+        alarm_id: x.alarm_id,
+        creation_time: x.creation_time,
+        creator_user_id: x.creator_user_id,
+        target_user_id: x.target_user_id,
+        recurrence: x.recurrence,
+        time_zone: x.time_zone,
+        start_time: x.start_time,
+        active: x.active,
+        next_fire_time: x.next_fire_time,
+        snooze_until: x.snooze_until,
+        last_fire_time: x.last_fire_time,
+        user_message_ids,
+    }
+}
+
+pub fn fill_retention_policy(x: RetentionPolicy) -> response::RetentionPolicy {
+    response::RetentionPolicy {
+        retention_policy_id: x.retention_policy_id,
+        creation_time: x.creation_time,
+        creator_user_id: x.creator_user_id,
+        user_id: x.user_id,
+        household_id: x.household_id,
+        keep_days: x.keep_days,
+        keep_last: x.keep_last,
+        delete_after_played: x.delete_after_played,
+    }
+}
+
+pub fn fill_checkpoint(
+    x: Checkpoint,
+    live_tasks: Vec<LiveTask>,
+    finished_tasks: Vec<FinishedTask>,
+) -> response::Checkpoint {
+    response::Checkpoint {
+        checkpoint_id: x.checkpoint_id,
+        creation_time: x.creation_time,
+        creator_user_id: x.creator_user_id,
+        live_tasks: live_tasks.into_iter().map(fill_live_task).collect(),
+        finished_tasks: finished_tasks.into_iter().map(fill_finished_task).collect(),
+    }
+}
+
+pub fn fill_live_task(x: LiveTask) -> response::LiveTask {
+    response::LiveTask {
+        live_task_id: x.live_task_id,
+        position: x.position,
+        value: x.value,
+    }
+}
+
+pub fn fill_finished_task(x: FinishedTask) -> response::FinishedTask {
+    response::FinishedTask {
+        finished_task_id: x.finished_task_id,
+        position: x.position,
+        value: x.value,
+        status: x.status,
+    }
+}
+
+pub fn fill_task_state(x: manage_checkpoint::TaskState) -> response::TaskState {
+    response::TaskState {
+        task_id: x.task_id,
+        position: x.position,
+        value: x.value,
+        status: x.status,
+    }
+}
+
+pub fn fill_task_move(x: manage_checkpoint::TaskMove) -> response::TaskMove {
+    response::TaskMove {
+        task_id: x.task_id,
+        from_position: x.from_position,
+        to_position: x.to_position,
+    }
+}
+
+pub fn fill_task_edit(x: manage_checkpoint::TaskEdit) -> response::TaskEdit {
+    response::TaskEdit {
+        from: fill_task_state(x.from),
+        to: fill_task_state(x.to),
+    }
+}
+
+pub fn fill_checkpoint_diff(x: manage_checkpoint::CheckpointDiff) -> response::CheckpointDiff {
+    response::CheckpointDiff {
+        from_checkpoint_id: x.from_checkpoint_id,
+        to_checkpoint_id: x.to_checkpoint_id,
+        added: x.added.into_iter().map(fill_task_state).collect(),
+        removed: x.removed.into_iter().map(fill_task_state).collect(),
+        completed: x.completed.into_iter().map(fill_task_state).collect(),
+        reopened: x.reopened.into_iter().map(fill_task_state).collect(),
+        reordered: x.reordered.into_iter().map(fill_task_move).collect(),
+        edited: x.edited.into_iter().map(fill_task_edit).collect(),
+    }
+}
+
+pub fn fill_task_history(x: manage_checkpoint::TaskHistory) -> response::TaskHistory {
+    response::TaskHistory {
+        task_id: x.task_id,
+        entries: x
+            .entries
+            .into_iter()
+            .map(|e| response::TaskHistoryEntry {
+                checkpoint_id: e.checkpoint_id,
+                creation_time: e.creation_time,
+                state: fill_task_state(e.state),
+            })
+            .collect(),
+        finished_time: x.finished_time,
+        time_to_finish_millis: x.time_to_finish_millis,
+    }
+}
+
+pub fn fill_bedtime_routine(
+    x: BedtimeRoutine,
+    tasks: Vec<BedtimeRoutineTask>,
+) -> response::BedtimeRoutine {
+    response::BedtimeRoutine {
+        bedtime_routine_id: x.bedtime_routine_id,
         creation_time: x.creation_time,
         creator_user_id: x.creator_user_id,
+        tasks: tasks.into_iter().map(fill_bedtime_routine_task).collect(),
+    }
+}
+
+pub fn fill_bedtime_routine_task(x: BedtimeRoutineTask) -> response::BedtimeRoutineTask {
+    response::BedtimeRoutineTask {
+        bedtime_routine_task_id: x.bedtime_routine_task_id,
+        position: x.position,
+        value: x.value,
+    }
+}
+
+pub fn fill_routine_night(x: RoutineNight) -> response::RoutineNight {
+    response::RoutineNight {
+        sleep_event_id: x.snapshot.sleep_event_id,
+        sleep_time: x.sleep_time,
+        wake_time: x.wake_time,
+        sleep_millis: x.wake_time.map(|wake_time| wake_time - x.sleep_time),
+        bedtime_routine_id: x.snapshot.bedtime_routine_id,
+        checkpoint_id: x.snapshot.checkpoint_id,
+        task_count: x.snapshot.task_count,
+        finished_count: x.snapshot.finished_count,
+    }
+}
+
+pub fn fill_routine_bucket(x: manage_bedtime_routine::RoutineBucket) -> response::RoutineBucket {
+    response::RoutineBucket {
+        night_count: x.night_count,
+        average_sleep_millis: x.average_sleep_millis,
+    }
+}
+
+pub fn fill_routine_stats(x: manage_bedtime_routine::RoutineStats) -> response::RoutineStats {
+    response::RoutineStats {
+        nights: x.nights.into_iter().map(fill_routine_night).collect(),
+        complete: fill_routine_bucket(x.complete),
+        partial: fill_routine_bucket(x.partial),
+        skipped: fill_routine_bucket(x.skipped),
     }
 }
 
@@ -126,34 +642,103 @@ pub async fn info(data: web::Data<AppData>) -> Result<impl Responder, AppError>
     }));
 }
 
+// stores a message whose audio, if there is any, came base64 encoded.
+// without audio it's a text message.
+async fn add_encoded_user_message(
+    data: &AppData,
+    submission: manage_user_message::Submission,
+    audio_data: Option<&str>,
+) -> Result<Vec<UserMessage>, AppError> {
+    match audio_data {
+        Some(audio_data) => {
+            let audio_data = base64::engine::general_purpose::STANDARD_NO_PAD
+                .decode(audio_data)
+                .map_err(report_base64_err)?;
+            manage_user_message::add_user_message(data, submission, &audio_data).await
+        }
+        None => manage_user_message::add_text_user_message(data, submission).await,
+    }
+}
+
+// stores a message whose audio is the request body, writing it out as it arrives
+async fn add_uploaded_user_message(
+    data: &AppData,
+    submission: manage_user_message::Submission,
+    mut payload: web::Payload,
+) -> Result<Vec<UserMessage>, AppError> {
+    let mut upload = manage_user_message::AudioUpload::start(data, submission).await?;
+
+    while let Some(chunk) = payload.next().await {
+        let result = match chunk {
+            Ok(chunk) => upload.write(&chunk).await,
+            Err(e) => {
+                log::info!("{}", e);
+                Err(AppError::BadRequest)
+            }
+        };
+        if let Err(e) = result {
+            upload.abort().await;
+            return Err(e);
+        }
+    }
+
+    upload.finish(data, None).await
+}
+
+// a single target gets a single message
+fn only_user_message(ums: Vec<UserMessage>) -> Result<response::UserMessage, AppError> {
+    ums.into_iter()
+        .next()
+        .map(|x| fill_user_message(x, UserMessageMetadata::default()))
+        .ok_or(AppError::InternalServerError)
+}
+
+fn fill_new_user_messages(ums: Vec<UserMessage>) -> Vec<response::UserMessage> {
+    ums.into_iter()
+        .map(|x| fill_user_message(x, UserMessageMetadata::default()))
+        .collect()
+}
+
 pub async fn user_message_new(
     req: web::Json<request::UserMessageNewProps>,
     data: web::Data<AppData>,
 ) -> Result<impl Responder, AppError> {
-    // validate api key
-    let user = get_user_if_api_key_valid(&data.auth_service, req.api_key.clone()).await?;
-
-    // validate that the other user exists in the first place
-    let target_user = data
-        .auth_service
-        .get_user_by_id(req.target_user_id)
-        .await
-        .map_err(report_auth_err)?;
+    let submission = manage_user_message::authorize_submission(&data, (&*req).into()).await?;
+    let ums = add_encoded_user_message(&data, submission, req.audio_data.as_deref()).await?;
+    Ok(web::Json(only_user_message(ums)?))
+}
 
-    let con: &mut tokio_postgres::Client = &mut *data.pool.get().await.map_err(report_pool_err)?;
+// send the same message to a list of targets and/or a household
+pub async fn user_message_broadcast(
+    req: web::Json<request::UserMessageBroadcastProps>,
+    data: web::Data<AppData>,
+) -> Result<impl Responder, AppError> {
+    let submission = manage_user_message::authorize_submission(&data, (&*req).into()).await?;
+    let ums = add_encoded_user_message(&data, submission, req.audio_data.as_deref()).await?;
+    Ok(web::Json(fill_new_user_messages(ums)))
+}
 
-    let um = user_message_service::add(
-        &mut *con,
-        user.user_id,
-        target_user.user_id,
-        base64::engine::general_purpose::STANDARD_NO_PAD
-            .decode(&req.audio_data)
-            .map_err(report_base64_err)?,
-    )
-    .await
-    .map_err(report_postgres_err)?;
+// submit a user message as a raw binary body (which may use chunked transfer encoding)
+pub async fn user_message_upload(
+    query: web::Query<request::UserMessageUploadProps>,
+    payload: web::Payload,
+    data: web::Data<AppData>,
+) -> Result<impl Responder, AppError> {
+    let submission = manage_user_message::authorize_submission(&data, (&*query).into()).await?;
+    let ums = add_uploaded_user_message(&data, submission, payload).await?;
+    Ok(web::Json(only_user_message(ums)?))
+}
 
-    return Ok(web::Json(fill_user_message(um)));
+// like user_message_upload, but to a list of targets and/or a household
+pub async fn user_message_broadcast_upload(
+    query: web::Query<request::UserMessageSubmitProps>,
+    payload: web::Payload,
+    data: web::Data<AppData>,
+) -> Result<impl Responder, AppError> {
+    let submission =
+        manage_user_message::authorize_submission(&data, (&*query).try_into()?).await?;
+    let ums = add_uploaded_user_message(&data, submission, payload).await?;
+    Ok(web::Json(fill_new_user_messages(ums)))
 }
 
 pub async fn sleep_event_new(
@@ -165,9 +750,14 @@ pub async fn sleep_event_new(
 
     let con: &mut tokio_postgres::Client = &mut *data.pool.get().await.map_err(report_pool_err)?;
 
-    let um = sleep_event_service::add(&mut *con, user.user_id)
-        .await
-        .map_err(report_postgres_err)?;
+    let um = manage_sleep_event::add_sleep_event(
+        con,
+        &data.push_hub,
+        user.user_id,
+        req.kind.unwrap_or(SleepEventKind::Sleep),
+        SleepEventSource::App,
+    )
+    .await?;
 
     return Ok(web::Json(fill_sleep_event(um)));
 }
@@ -177,37 +767,109 @@ pub async fn user_message_view(
     data: web::Data<AppData>,
 ) -> Result<impl Responder, AppError> {
     // api key verification required
-    let _ = get_user_if_api_key_valid(&data.auth_service, req.api_key.clone()).await?;
+    let user = get_user_if_api_key_valid(&data.auth_service, req.api_key.clone()).await?;
 
     // get connection
     let con: &mut tokio_postgres::Client = &mut *data.pool.get().await.map_err(report_pool_err)?;
 
     // get user messages
-    let user_messages = user_message_service::query(con, req.into_inner())
-        .await
-        .map_err(report_postgres_err)?;
+    let user_messages = user_message_service::query(
+        con,
+        user.user_id,
+        utils::current_time_millis(),
+        req.into_inner(),
+    )
+    .await
+    .map_err(report_postgres_err)?;
 
     // return
-    let mut resp_user_messages = vec![];
-    for u in user_messages.into_iter() {
-        resp_user_messages.push(fill_user_message(u));
-    }
+    let resp_user_messages = fill_user_messages_with_metadata(con, user_messages).await?;
 
     Ok(web::Json(resp_user_messages))
 }
 
+// find messages by what was typed or said in them
+pub async fn user_message_search(
+    req: web::Json<request::UserMessageSearchProps>,
+    data: web::Data<AppData>,
+) -> Result<impl Responder, AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&data.auth_service, req.api_key.clone()).await?;
+
+    let con: &mut tokio_postgres::Client = &mut *data.pool.get().await.map_err(report_pool_err)?;
+
+    let user_messages = manage_user_message::search_user_messages(con, user.user_id, &req).await?;
+
+    Ok(web::Json(
+        fill_user_messages_with_metadata(con, user_messages).await?,
+    ))
+}
+
+// how much audio is read from storage at a time when serving it over http
+const AUDIO_BLOCK_SIZE: usize = 64 * 1024;
+
+// fetch the audio of a user message as raw bytes
+pub async fn user_message_audio(
+    query: web::Query<request::UserMessageAudioProps>,
+    data: web::Data<AppData>,
+) -> Result<impl Responder, AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&data.auth_service, query.api_key.clone()).await?;
+
+    let con: &mut tokio_postgres::Client = &mut *data.pool.get().await.map_err(report_pool_err)?;
+
+    let um = user_message_service::get_by_user_message_id(con, query.user_message_id)
+        .await
+        .map_err(report_postgres_err)?
+        .ok_or(AppError::NotFound)?;
+
+    // only the people in the conversation may listen, and the target only while it's in their inbox
+    let is_target = um.target_user_id == user.user_id
+        && manage_user_message::is_visible_to_target(&um, utils::current_time_millis());
+    if um.creator_user_id != user.user_id && !is_target {
+        return Err(AppError::Unauthorized);
+    }
+
+    // text messages have nothing to listen to
+    let (audio_blob_key, audio_size) = match (um.audio_blob_key, um.audio_size) {
+        (Some(audio_blob_key), Some(audio_size)) => (audio_blob_key, audio_size),
+        _ => return Err(AppError::NotFound),
+    };
+
+    let reader = data
+        .blob_store
+        .reader(&audio_blob_key)
+        .await
+        .map_err(report_blob_store_err)?;
+
+    // stream the audio out of storage rather than loading all of it
+    let body = futures_util::stream::unfold(Some(reader), |reader| async move {
+        let mut reader = reader?;
+        match reader.read(AUDIO_BLOCK_SIZE).await {
+            Ok(Some(block)) => Some((Ok(web::Bytes::from(block)), Some(reader))),
+            Ok(None) => None,
+            Err(e) => Some((Err(e), None)),
+        }
+    });
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/octet-stream")
+        .no_chunking(audio_size as u64)
+        .streaming(body))
+}
+
 pub async fn sleep_event_view(
     req: web::Json<request::SleepEventViewProps>,
     data: web::Data<AppData>,
 ) -> Result<impl Responder, AppError> {
     // api key verification required
-    let _ = get_user_if_api_key_valid(&data.auth_service, req.api_key.clone()).await?;
+    let user = get_user_if_api_key_valid(&data.auth_service, req.api_key.clone()).await?;
 
     // get connection
     let con: &mut tokio_postgres::Client = &mut *data.pool.get().await.map_err(report_pool_err)?;
 
     // get user messages
-    let sleep_events = sleep_event_service::query(con, req.into_inner())
+    let sleep_events = sleep_event_service::query(con, user.user_id, req.into_inner())
         .await
         .map_err(report_postgres_err)?;
 
@@ -220,19 +882,158 @@ pub async fn sleep_event_view(
     Ok(web::Json(resp_sleep_events))
 }
 
-// allows you to submit the message in parts
-pub async fn ws_submit_user_message(
+pub async fn sleep_event_backfill(
+    req: web::Json<request::SleepEventBackfillProps>,
     data: web::Data<AppData>,
-    req: HttpRequest,
-    stream: web::Payload,
-    query: web::Query<request::UserMessageSubmitProps>,
-) -> Result<impl Responder, Error> {
-    let (res, session, msg_stream) = actix_ws::handle(&req, stream)?;
-    // spawn websocket handler (and don't await it) so that the response is returned immediately
-    rt::spawn(manage_user_message::submit_user_message_ws(
-        data, session, msg_stream, query,
-    ));
-    Ok(res)
+) -> Result<impl Responder, AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&data.auth_service, req.api_key.clone()).await?;
+
+    let con: &mut tokio_postgres::Client = &mut *data.pool.get().await.map_err(report_pool_err)?;
+
+    let sleep_event = manage_sleep_event::backfill_sleep_event(
+        con,
+        &data.push_hub,
+        user.user_id,
+        req.kind,
+        req.creation_time,
+    )
+    .await?;
+
+    Ok(web::Json(fill_sleep_event(sleep_event)))
+}
+
+pub async fn sleep_event_update(
+    req: web::Json<request::SleepEventUpdateProps>,
+    data: web::Data<AppData>,
+) -> Result<impl Responder, AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&data.auth_service, req.api_key.clone()).await?;
+
+    let con: &mut tokio_postgres::Client = &mut *data.pool.get().await.map_err(report_pool_err)?;
+
+    let sleep_event =
+        manage_sleep_event::update_sleep_event(con, &data.push_hub, user.user_id, &req).await?;
+
+    Ok(web::Json(fill_sleep_event(sleep_event)))
+}
+
+pub async fn sleep_event_delete(
+    req: web::Json<request::SleepEventDeleteProps>,
+    data: web::Data<AppData>,
+) -> Result<impl Responder, AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&data.auth_service, req.api_key.clone()).await?;
+
+    let con: &mut tokio_postgres::Client = &mut *data.pool.get().await.map_err(report_pool_err)?;
+
+    let sleep_event = manage_sleep_event::delete_sleep_event(
+        con,
+        &data.push_hub,
+        user.user_id,
+        req.sleep_event_id,
+    )
+    .await?;
+
+    Ok(web::Json(fill_sleep_event(sleep_event)))
+}
+
+// import sleep from another app's export, sent as the raw body
+pub async fn sleep_event_import(
+    query: web::Query<request::SleepEventImportProps>,
+    payload: web::Payload,
+    data: web::Data<AppData>,
+) -> Result<impl Responder, AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&data.auth_service, query.api_key.clone()).await?;
+
+    let sessions = manage_sleep_import::parse_upload(
+        query.format,
+        query.time_zone.as_deref().unwrap_or("UTC"),
+        payload,
+    )
+    .await?;
+
+    let con: &mut tokio_postgres::Client = &mut *data.pool.get().await.map_err(report_pool_err)?;
+
+    let report = manage_sleep_import::import_sessions(con, user.user_id, sessions)
+        .await
+        .map_err(report_postgres_err)?;
+
+    // an import can bring in a night later than anything recorded so far
+    if !report.imported.is_empty() {
+        manage_sleep_status::publish_status(con, &data.push_hub, user.user_id).await;
+    }
+
+    Ok(web::Json(fill_sleep_import_report(report)))
+}
+
+// download the user's sleep as a file, streamed out as it is read
+pub async fn sleep_event_export(
+    query: web::Query<request::SleepEventExportProps>,
+    data: web::Data<AppData>,
+) -> Result<impl Responder, AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&data.auth_service, query.api_key.clone()).await?;
+
+    let export = manage_sleep_export::SleepExport::new(user.user_id, &query)?;
+    let format = export.format;
+
+    Ok(HttpResponse::Ok()
+        .content_type(format.content_type())
+        .insert_header((
+            "Content-Disposition",
+            format!("attachment; filename=\"{}\"", format.file_name()),
+        ))
+        .streaming(manage_sleep_export::export_sleep(data.pool.clone(), export)))
+}
+
+// backfills, corrections and deletions of the user's sleep events
+pub async fn sleep_event_history(
+    req: web::Json<request::SleepEventHistoryProps>,
+    data: web::Data<AppData>,
+) -> Result<impl Responder, AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&data.auth_service, req.api_key.clone()).await?;
+
+    let con: &mut tokio_postgres::Client = &mut *data.pool.get().await.map_err(report_pool_err)?;
+
+    let edits =
+        manage_sleep_event::get_sleep_event_history(con, user.user_id, req.sleep_event_id).await?;
+
+    Ok(web::Json(
+        edits
+            .into_iter()
+            .map(fill_sleep_event_edit)
+            .collect::<Vec<_>>(),
+    ))
+}
+
+// allows you to submit the message in parts
+pub async fn ws_submit_user_message(
+    data: web::Data<AppData>,
+    req: HttpRequest,
+    stream: web::Payload,
+    query: web::Query<request::UserMessageSubmitProps>,
+) -> Result<impl Responder, Error> {
+    // refuse the upgrade outright if the message can't be sent
+    let submission =
+        manage_user_message::authorize_submission(&data, (&*query).try_into()?).await?;
+    let upload = manage_user_message::AudioUpload::start(&data, submission).await?;
+
+    // a failed handshake must not leave the partial blob behind
+    let (res, session, msg_stream) = match actix_ws::handle(&req, stream) {
+        Ok(x) => x,
+        Err(e) => {
+            upload.abort().await;
+            return Err(e);
+        }
+    };
+    // spawn websocket handler (and don't await it) so that the response is returned immediately
+    rt::spawn(manage_user_message::submit_user_message_ws(
+        data, session, msg_stream, upload,
+    ));
+    Ok(res)
 }
 
 // allows you to receive the message in parts
@@ -242,10 +1043,17 @@ pub async fn ws_receive_user_message(
     stream: web::Payload,
     query: web::Query<request::UserMessageReceiveProps>,
 ) -> Result<impl Responder, Error> {
+    // refuse the upgrade outright if we don't know who's listening
+    let user = get_user_if_api_key_valid(&data.auth_service, query.api_key.clone()).await?;
+
     let (res, session, msg_stream) = actix_ws::handle(&req, stream)?;
     // spawn websocket handler (and don't await it) so that the response is returned immediately
     rt::spawn(manage_user_message::receive_user_message_ws(
-        data, session, msg_stream, query,
+        data,
+        session,
+        msg_stream,
+        user.user_id,
+        query,
     ));
     Ok(res)
 }
@@ -268,11 +1076,1009 @@ pub async fn query_params_sleep_event_new(
     data: web::Data<AppData>,
     query: web::Query<request::QueryParamsSleepEventProps>,
 ) -> Result<impl Responder, Error> {
+    let kind = query.kind.unwrap_or(SleepEventKind::Sleep);
+
+    let sleep_event = match &query.api_key {
+        // the event runs the user's rules, so it has to really be them
+        Some(api_key) => {
+            let user = get_user_if_api_key_valid(&data.auth_service, api_key.clone()).await?;
+            if query.creator_user_id.is_some_and(|x| x != user.user_id) {
+                return Err(AppError::BadRequest.into());
+            }
+
+            let con: &mut tokio_postgres::Client =
+                &mut *data.pool.get().await.map_err(report_pool_err)?;
+            manage_sleep_event::add_sleep_event(
+                con,
+                &data.push_hub,
+                user.user_id,
+                kind,
+                SleepEventSource::Device,
+            )
+            .await?
+        }
+        // older devices only send the user's id, which anybody could
+        None => {
+            let creator_user_id = query.creator_user_id.ok_or(AppError::BadRequest)?;
+
+            let con: &mut tokio_postgres::Client =
+                &mut *data.pool.get().await.map_err(report_pool_err)?;
+            manage_sleep_event::add_unverified_sleep_event(
+                con,
+                creator_user_id,
+                kind,
+                SleepEventSource::Device,
+            )
+            .await?
+        }
+    };
+    // just return the number
+    Ok(web::Json(fill_sleep_event(sleep_event)))
+}
+
+pub async fn household_new(
+    req: web::Json<request::HouseholdNewProps>,
+    data: web::Data<AppData>,
+) -> Result<impl Responder, AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&data.auth_service, req.api_key.clone()).await?;
+
     let con: &mut tokio_postgres::Client = &mut *data.pool.get().await.map_err(report_pool_err)?;
-    // get user messages
-    let sleep_event = sleep_event_service::add(con, query.creator_user_id)
+
+    let household = manage_household::create_household(con, user.user_id, req.name.clone()).await?;
+
+    Ok(web::Json(fill_household(household)))
+}
+
+// the households you belong to
+pub async fn household_view(
+    req: web::Json<request::HouseholdViewProps>,
+    data: web::Data<AppData>,
+) -> Result<impl Responder, AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&data.auth_service, req.api_key.clone()).await?;
+
+    let con: &mut tokio_postgres::Client = &mut *data.pool.get().await.map_err(report_pool_err)?;
+
+    let households = household_service::get_by_member_user_id(con, user.user_id)
         .await
         .map_err(report_postgres_err)?;
-    // just return the number
-    Ok(web::Json(fill_sleep_event(sleep_event)))
+
+    Ok(web::Json(
+        households
+            .into_iter()
+            .map(fill_household)
+            .collect::<Vec<_>>(),
+    ))
+}
+
+pub async fn household_leave(
+    req: web::Json<request::HouseholdLeaveProps>,
+    data: web::Data<AppData>,
+) -> Result<impl Responder, AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&data.auth_service, req.api_key.clone()).await?;
+
+    let con: &mut tokio_postgres::Client = &mut *data.pool.get().await.map_err(report_pool_err)?;
+
+    let membership = manage_household::leave(con, user.user_id, req.household_id).await?;
+
+    Ok(web::Json(fill_household_membership(membership)))
+}
+
+// change the role of a member, or remove them (admins only)
+pub async fn household_membership_new(
+    req: web::Json<request::HouseholdMembershipNewProps>,
+    data: web::Data<AppData>,
+) -> Result<impl Responder, AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&data.auth_service, req.api_key.clone()).await?;
+
+    let con: &mut tokio_postgres::Client = &mut *data.pool.get().await.map_err(report_pool_err)?;
+
+    let membership = manage_household::update_membership(
+        con,
+        user.user_id,
+        req.household_id,
+        req.user_id,
+        req.role,
+        req.active,
+    )
+    .await?;
+
+    Ok(web::Json(fill_household_membership(membership)))
+}
+
+// the current members of a household you belong to
+pub async fn household_membership_view(
+    req: web::Json<request::HouseholdMembershipViewProps>,
+    data: web::Data<AppData>,
+) -> Result<impl Responder, AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&data.auth_service, req.api_key.clone()).await?;
+
+    let con: &mut tokio_postgres::Client = &mut *data.pool.get().await.map_err(report_pool_err)?;
+
+    manage_household::require_member(con, req.household_id, user.user_id).await?;
+
+    let memberships =
+        household_membership_service::get_active_by_household_id(con, req.household_id)
+            .await
+            .map_err(report_postgres_err)?;
+
+    Ok(web::Json(
+        memberships
+            .into_iter()
+            .map(fill_household_membership)
+            .collect::<Vec<_>>(),
+    ))
+}
+
+// create a code that somebody else can use to join the household (admins only)
+pub async fn household_invite_new(
+    req: web::Json<request::HouseholdInviteNewProps>,
+    data: web::Data<AppData>,
+) -> Result<impl Responder, AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&data.auth_service, req.api_key.clone()).await?;
+
+    let con: &mut tokio_postgres::Client = &mut *data.pool.get().await.map_err(report_pool_err)?;
+
+    let invite = manage_household::create_invite(
+        con,
+        user.user_id,
+        req.household_id,
+        req.role.unwrap_or(HouseholdRole::Member),
+    )
+    .await?;
+
+    Ok(web::Json(fill_household_invite(invite)))
+}
+
+pub async fn household_invite_accept(
+    req: web::Json<request::HouseholdInviteAcceptProps>,
+    data: web::Data<AppData>,
+) -> Result<impl Responder, AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&data.auth_service, req.api_key.clone()).await?;
+
+    let con: &mut tokio_postgres::Client = &mut *data.pool.get().await.map_err(report_pool_err)?;
+
+    let membership =
+        manage_household::accept_invite(con, user.user_id, req.household_invite_code.clone())
+            .await?;
+
+    Ok(web::Json(fill_household_membership(membership)))
+}
+
+// ask another user to become your contact
+pub async fn contact_request_new(
+    req: web::Json<request::ContactRequestNewProps>,
+    data: web::Data<AppData>,
+) -> Result<impl Responder, AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&data.auth_service, req.api_key.clone()).await?;
+
+    // validate that the other user exists in the first place
+    data.auth_service
+        .get_user_by_id(req.target_user_id)
+        .await
+        .map_err(report_auth_err)?;
+
+    let con: &mut tokio_postgres::Client = &mut *data.pool.get().await.map_err(report_pool_err)?;
+
+    let contact_request =
+        manage_contact::request_contact(con, user.user_id, req.target_user_id).await?;
+
+    Ok(web::Json(fill_contact_request(contact_request)))
+}
+
+// accept or decline a contact request sent to you
+pub async fn contact_request_respond(
+    req: web::Json<request::ContactRequestRespondProps>,
+    data: web::Data<AppData>,
+) -> Result<impl Responder, AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&data.auth_service, req.api_key.clone()).await?;
+
+    let con: &mut tokio_postgres::Client = &mut *data.pool.get().await.map_err(report_pool_err)?;
+
+    let contact_request =
+        manage_contact::respond(con, user.user_id, req.contact_request_id, req.accepted).await?;
+
+    Ok(web::Json(fill_contact_request(contact_request)))
+}
+
+// contact requests you sent or received that haven't been answered yet
+pub async fn contact_request_view(
+    req: web::Json<request::ContactRequestViewProps>,
+    data: web::Data<AppData>,
+) -> Result<impl Responder, AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&data.auth_service, req.api_key.clone()).await?;
+
+    let con: &mut tokio_postgres::Client = &mut *data.pool.get().await.map_err(report_pool_err)?;
+
+    let contact_requests = contact_request_service::get_pending_by_user_id(con, user.user_id)
+        .await
+        .map_err(report_postgres_err)?;
+
+    Ok(web::Json(
+        contact_requests
+            .into_iter()
+            .map(fill_contact_request)
+            .collect::<Vec<_>>(),
+    ))
+}
+
+pub async fn contact_view(
+    req: web::Json<request::ContactViewProps>,
+    data: web::Data<AppData>,
+) -> Result<impl Responder, AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&data.auth_service, req.api_key.clone()).await?;
+
+    let con: &mut tokio_postgres::Client = &mut *data.pool.get().await.map_err(report_pool_err)?;
+
+    let contacts = contact_service::get_by_user_id(con, user.user_id)
+        .await
+        .map_err(report_postgres_err)?;
+
+    Ok(web::Json(
+        contacts.into_iter().map(fill_contact).collect::<Vec<_>>(),
+    ))
+}
+
+// block or unblock another user
+pub async fn contact_block_new(
+    req: web::Json<request::ContactBlockNewProps>,
+    data: web::Data<AppData>,
+) -> Result<impl Responder, AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&data.auth_service, req.api_key.clone()).await?;
+
+    let con: &mut tokio_postgres::Client = &mut *data.pool.get().await.map_err(report_pool_err)?;
+
+    let contact_block =
+        manage_contact::set_block(con, user.user_id, req.target_user_id, req.active).await?;
+
+    if req.active {
+        manage_sleep_status::on_blocked(&data.push_hub, user.user_id, req.target_user_id);
+    }
+
+    Ok(web::Json(fill_contact_block(contact_block)))
+}
+
+// the users you currently block
+pub async fn contact_block_view(
+    req: web::Json<request::ContactBlockViewProps>,
+    data: web::Data<AppData>,
+) -> Result<impl Responder, AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&data.auth_service, req.api_key.clone()).await?;
+
+    let con: &mut tokio_postgres::Client = &mut *data.pool.get().await.map_err(report_pool_err)?;
+
+    let contact_blocks = contact_block_service::get_active_by_creator_user_id(con, user.user_id)
+        .await
+        .map_err(report_postgres_err)?;
+
+    Ok(web::Json(
+        contact_blocks
+            .into_iter()
+            .map(fill_contact_block)
+            .collect::<Vec<_>>(),
+    ))
+}
+
+// start or stop letting another user see when you're asleep
+pub async fn sleep_status_share_new(
+    req: web::Json<request::SleepStatusShareNewProps>,
+    data: web::Data<AppData>,
+) -> Result<impl Responder, AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&data.auth_service, req.api_key.clone()).await?;
+
+    let con: &mut tokio_postgres::Client = &mut *data.pool.get().await.map_err(report_pool_err)?;
+
+    let share = manage_sleep_status::set_share(
+        con,
+        &data.push_hub,
+        user.user_id,
+        req.target_user_id,
+        req.active,
+    )
+    .await?;
+
+    Ok(web::Json(fill_sleep_status_share(share)))
+}
+
+// the users you currently share your sleep status with
+pub async fn sleep_status_share_view(
+    req: web::Json<request::SleepStatusShareViewProps>,
+    data: web::Data<AppData>,
+) -> Result<impl Responder, AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&data.auth_service, req.api_key.clone()).await?;
+
+    let con: &mut tokio_postgres::Client = &mut *data.pool.get().await.map_err(report_pool_err)?;
+
+    let shares = sleep_status_share_service::get_active_by_creator_user_id(con, user.user_id)
+        .await
+        .map_err(report_postgres_err)?;
+
+    Ok(web::Json(
+        shares
+            .into_iter()
+            .map(fill_sleep_status_share)
+            .collect::<Vec<_>>(),
+    ))
+}
+
+// messages you scheduled that haven't been delivered yet
+pub async fn scheduled_user_message_view(
+    req: web::Json<request::ScheduledUserMessageViewProps>,
+    data: web::Data<AppData>,
+) -> Result<impl Responder, AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&data.auth_service, req.api_key.clone()).await?;
+
+    let con: &mut tokio_postgres::Client = &mut *data.pool.get().await.map_err(report_pool_err)?;
+
+    let user_messages = user_message_service::get_scheduled_by_creator(
+        con,
+        user.user_id,
+        utils::current_time_millis(),
+    )
+    .await
+    .map_err(report_postgres_err)?;
+
+    Ok(web::Json(
+        fill_user_messages_with_metadata(con, user_messages).await?,
+    ))
+}
+
+pub async fn scheduled_user_message_reschedule(
+    req: web::Json<request::ScheduledUserMessageRescheduleProps>,
+    data: web::Data<AppData>,
+) -> Result<impl Responder, AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&data.auth_service, req.api_key.clone()).await?;
+
+    let con: &mut tokio_postgres::Client = &mut *data.pool.get().await.map_err(report_pool_err)?;
+
+    let user_message = manage_user_message::reschedule_user_message(
+        con,
+        user.user_id,
+        req.user_message_id,
+        req.deliver_at,
+        req.deliver_on_wake.unwrap_or(false),
+    )
+    .await?;
+
+    Ok(web::Json(
+        fill_user_message_with_metadata(con, user_message).await?,
+    ))
+}
+
+pub async fn scheduled_user_message_cancel(
+    req: web::Json<request::ScheduledUserMessageCancelProps>,
+    data: web::Data<AppData>,
+) -> Result<impl Responder, AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&data.auth_service, req.api_key.clone()).await?;
+
+    let con: &mut tokio_postgres::Client = &mut *data.pool.get().await.map_err(report_pool_err)?;
+
+    let user_message =
+        manage_user_message::cancel_user_message(con, user.user_id, req.user_message_id).await?;
+
+    Ok(web::Json(fill_user_message(
+        user_message,
+        UserMessageMetadata::default(),
+    )))
+}
+
+// everybody you've exchanged messages with, with the latest message and how many you haven't played
+pub async fn conversation_view(
+    req: web::Json<request::ConversationViewProps>,
+    data: web::Data<AppData>,
+) -> Result<impl Responder, AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&data.auth_service, req.api_key.clone()).await?;
+
+    let con: &mut tokio_postgres::Client = &mut *data.pool.get().await.map_err(report_pool_err)?;
+
+    let conversations =
+        conversation_service::get_by_user_id(con, user.user_id, utils::current_time_millis())
+            .await
+            .map_err(report_postgres_err)?;
+
+    Ok(web::Json(
+        fill_conversations_with_metadata(con, conversations).await?,
+    ))
+}
+
+// the messages between you and one other user, a page at a time
+pub async fn conversation_page(
+    req: web::Json<request::ConversationPageProps>,
+    data: web::Data<AppData>,
+) -> Result<impl Responder, AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&data.auth_service, req.api_key.clone()).await?;
+
+    let con: &mut tokio_postgres::Client = &mut *data.pool.get().await.map_err(report_pool_err)?;
+
+    let user_messages = manage_user_message::get_conversation_page(con, user.user_id, &req).await?;
+
+    Ok(web::Json(
+        fill_user_messages_with_metadata(con, user_messages).await?,
+    ))
+}
+
+// the target finished listening to a message, which deletes it if it's ephemeral
+pub async fn user_message_played(
+    req: web::Json<request::UserMessagePlayedProps>,
+    data: web::Data<AppData>,
+) -> Result<impl Responder, AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&data.auth_service, req.api_key.clone()).await?;
+
+    let con: &mut tokio_postgres::Client = &mut *data.pool.get().await.map_err(report_pool_err)?;
+
+    let user_message =
+        manage_user_message::play_user_message(con, user.user_id, req.user_message_id).await?;
+
+    Ok(web::Json(
+        fill_user_message_with_metadata(con, user_message).await?,
+    ))
+}
+
+// take back a message you sent
+pub async fn user_message_unsend(
+    req: web::Json<request::UserMessageUnsendProps>,
+    data: web::Data<AppData>,
+) -> Result<impl Responder, AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&data.auth_service, req.api_key.clone()).await?;
+
+    let con: &mut tokio_postgres::Client = &mut *data.pool.get().await.map_err(report_pool_err)?;
+
+    let user_message = manage_user_message::unsend_user_message(
+        con,
+        &data.push_hub,
+        user.user_id,
+        req.user_message_id,
+    )
+    .await?;
+
+    Ok(web::Json(fill_user_message(
+        user_message,
+        UserMessageMetadata::default(),
+    )))
+}
+
+// remove a message from your inbox
+pub async fn user_message_delete(
+    req: web::Json<request::UserMessageDeleteProps>,
+    data: web::Data<AppData>,
+) -> Result<impl Responder, AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&data.auth_service, req.api_key.clone()).await?;
+
+    let con: &mut tokio_postgres::Client = &mut *data.pool.get().await.map_err(report_pool_err)?;
+
+    let user_message = manage_user_message::delete_user_message_from_inbox(
+        con,
+        &data.push_hub,
+        user.user_id,
+        req.user_message_id,
+    )
+    .await?;
+
+    Ok(web::Json(
+        fill_user_message_with_metadata(con, user_message).await?,
+    ))
+}
+
+// remove any message for good (admins only)
+pub async fn admin_user_message_delete(
+    req: web::Json<request::AdminUserMessageDeleteProps>,
+    data: web::Data<AppData>,
+) -> Result<impl Responder, AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&data.auth_service, req.api_key.clone()).await?;
+
+    if !data.admin_user_ids.contains(&user.user_id) {
+        return Err(AppError::Forbidden);
+    }
+
+    let con: &mut tokio_postgres::Client = &mut *data.pool.get().await.map_err(report_pool_err)?;
+
+    let user_message =
+        manage_user_message::admin_delete_user_message(con, &data.push_hub, req.user_message_id)
+            .await?;
+
+    log::info!(
+        "admin {} deleted user message {}",
+        user.user_id,
+        user_message.user_message_id
+    );
+
+    Ok(web::Json(fill_user_message(
+        user_message,
+        UserMessageMetadata::default(),
+    )))
+}
+
+// react to a message with a bit of text or an emoji
+pub async fn user_message_reaction_new(
+    req: web::Json<request::UserMessageReactionNewProps>,
+    data: web::Data<AppData>,
+) -> Result<impl Responder, AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&data.auth_service, req.api_key.clone()).await?;
+
+    let con: &mut tokio_postgres::Client = &mut *data.pool.get().await.map_err(report_pool_err)?;
+
+    let user_message_reaction = manage_reaction::add_reaction(
+        con,
+        &data.push_hub,
+        user.user_id,
+        req.user_message_id,
+        &req.reaction,
+    )
+    .await?;
+
+    Ok(web::Json(fill_user_message_reaction(user_message_reaction)))
+}
+
+// take back your reaction
+pub async fn user_message_reaction_delete(
+    req: web::Json<request::UserMessageReactionDeleteProps>,
+    data: web::Data<AppData>,
+) -> Result<impl Responder, AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&data.auth_service, req.api_key.clone()).await?;
+
+    let con: &mut tokio_postgres::Client = &mut *data.pool.get().await.map_err(report_pool_err)?;
+
+    let user_message_reaction = manage_reaction::remove_reaction(
+        con,
+        &data.push_hub,
+        user.user_id,
+        req.user_message_reaction_id,
+    )
+    .await?;
+
+    Ok(web::Json(fill_user_message_reaction(user_message_reaction)))
+}
+
+// set how long your messages, or your household's, are kept
+pub async fn retention_policy_new(
+    req: web::Json<request::RetentionPolicyNewProps>,
+    data: web::Data<AppData>,
+) -> Result<impl Responder, AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&data.auth_service, req.api_key.clone()).await?;
+
+    let con: &mut tokio_postgres::Client = &mut *data.pool.get().await.map_err(report_pool_err)?;
+
+    let retention_policy = manage_retention::set_policy(con, user.user_id, &req).await?;
+
+    Ok(web::Json(fill_retention_policy(retention_policy)))
+}
+
+// the current retention policy, or null if messages are kept forever
+pub async fn retention_policy_view(
+    req: web::Json<request::RetentionPolicyViewProps>,
+    data: web::Data<AppData>,
+) -> Result<impl Responder, AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&data.auth_service, req.api_key.clone()).await?;
+
+    let con: &mut tokio_postgres::Client = &mut *data.pool.get().await.map_err(report_pool_err)?;
+
+    let retention_policy =
+        manage_retention::get_policy(con, user.user_id, req.household_id).await?;
+
+    Ok(web::Json(retention_policy.map(fill_retention_policy)))
+}
+
+// save the whole task list as a new checkpoint
+pub async fn checkpoint_new(
+    req: web::Json<request::CheckpointNewProps>,
+    data: web::Data<AppData>,
+) -> Result<impl Responder, AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&data.auth_service, req.api_key.clone()).await?;
+
+    let con: &mut tokio_postgres::Client = &mut *data.pool.get().await.map_err(report_pool_err)?;
+
+    let (checkpoint, live_tasks, finished_tasks) =
+        manage_checkpoint::save_checkpoint(con, user.user_id, &req).await?;
+
+    Ok(web::Json(fill_checkpoint(
+        checkpoint,
+        live_tasks,
+        finished_tasks,
+    )))
+}
+
+// a past checkpoint, or the latest one
+pub async fn checkpoint_view(
+    req: web::Json<request::CheckpointViewProps>,
+    data: web::Data<AppData>,
+) -> Result<impl Responder, AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&data.auth_service, req.api_key.clone()).await?;
+
+    let con: &mut tokio_postgres::Client = &mut *data.pool.get().await.map_err(report_pool_err)?;
+
+    let checkpoint = match req.checkpoint_id {
+        Some(checkpoint_id) => {
+            Some(manage_checkpoint::get_checkpoint(con, user.user_id, checkpoint_id).await?)
+        }
+        None => checkpoint_service::get_recent_by_creator_user_id(con, user.user_id)
+            .await
+            .map_err(report_postgres_err)?,
+    };
+
+    // nothing has been saved yet
+    let checkpoint = match checkpoint {
+        Some(checkpoint) => checkpoint,
+        None => return Ok(web::Json(None)),
+    };
+
+    let (live_tasks, finished_tasks) =
+        manage_checkpoint::get_tasks(con, checkpoint.checkpoint_id).await?;
+
+    Ok(web::Json(Some(fill_checkpoint(
+        checkpoint,
+        live_tasks,
+        finished_tasks,
+    ))))
+}
+
+// past checkpoints, newest first
+pub async fn checkpoint_list(
+    req: web::Json<request::CheckpointListProps>,
+    data: web::Data<AppData>,
+) -> Result<impl Responder, AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&data.auth_service, req.api_key.clone()).await?;
+
+    let con: &mut tokio_postgres::Client = &mut *data.pool.get().await.map_err(report_pool_err)?;
+
+    let checkpoints = manage_checkpoint::list_checkpoints(con, user.user_id, &req).await?;
+
+    let mut resp_checkpoints = vec![];
+    for checkpoint in checkpoints.into_iter() {
+        let (live_tasks, finished_tasks) =
+            manage_checkpoint::get_tasks(con, checkpoint.checkpoint_id).await?;
+        resp_checkpoints.push(fill_checkpoint(checkpoint, live_tasks, finished_tasks));
+    }
+
+    Ok(web::Json(resp_checkpoints))
+}
+
+// what changed between two checkpoints
+pub async fn checkpoint_diff(
+    req: web::Json<request::CheckpointDiffProps>,
+    data: web::Data<AppData>,
+) -> Result<impl Responder, AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&data.auth_service, req.api_key.clone()).await?;
+
+    let con: &mut tokio_postgres::Client = &mut *data.pool.get().await.map_err(report_pool_err)?;
+
+    let diff = manage_checkpoint::diff_checkpoints(
+        con,
+        user.user_id,
+        req.from_checkpoint_id,
+        req.to_checkpoint_id,
+    )
+    .await?;
+
+    Ok(web::Json(fill_checkpoint_diff(diff)))
+}
+
+// one task followed across every checkpoint it appears in
+pub async fn checkpoint_task_history(
+    req: web::Json<request::TaskHistoryProps>,
+    data: web::Data<AppData>,
+) -> Result<impl Responder, AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&data.auth_service, req.api_key.clone()).await?;
+
+    let con: &mut tokio_postgres::Client = &mut *data.pool.get().await.map_err(report_pool_err)?;
+
+    let history =
+        manage_checkpoint::get_task_history(con, user.user_id, req.live_task_id.clone()).await?;
+
+    Ok(web::Json(fill_task_history(history)))
+}
+
+// set an alarm that plays stored messages on a recurring schedule
+pub async fn alarm_new(
+    req: web::Json<request::AlarmNewProps>,
+    data: web::Data<AppData>,
+) -> Result<impl Responder, AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&data.auth_service, req.api_key.clone()).await?;
+
+    let con: &mut tokio_postgres::Client = &mut *data.pool.get().await.map_err(report_pool_err)?;
+
+    let (alarm, user_message_ids) = manage_alarm::create_alarm(con, user.user_id, &req).await?;
+
+    Ok(web::Json(fill_alarm(alarm, user_message_ids)))
+}
+
+pub async fn alarm_update(
+    req: web::Json<request::AlarmUpdateProps>,
+    data: web::Data<AppData>,
+) -> Result<impl Responder, AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&data.auth_service, req.api_key.clone()).await?;
+
+    let con: &mut tokio_postgres::Client = &mut *data.pool.get().await.map_err(report_pool_err)?;
+
+    let (alarm, user_message_ids) = manage_alarm::update_alarm(con, user.user_id, &req).await?;
+
+    Ok(web::Json(fill_alarm(alarm, user_message_ids)))
+}
+
+// alarms you set, or that ring for you
+pub async fn alarm_view(
+    req: web::Json<request::AlarmViewProps>,
+    data: web::Data<AppData>,
+) -> Result<impl Responder, AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&data.auth_service, req.api_key.clone()).await?;
+
+    let con: &mut tokio_postgres::Client = &mut *data.pool.get().await.map_err(report_pool_err)?;
+
+    let alarms = alarm_service::get_by_user_id(con, user.user_id)
+        .await
+        .map_err(report_postgres_err)?;
+
+    let mut resp_alarms = vec![];
+    for alarm in alarms.into_iter() {
+        let user_message_ids = alarm_user_message_service::get_by_alarm_id(con, alarm.alarm_id)
+            .await
+            .map_err(report_postgres_err)?;
+        resp_alarms.push(fill_alarm(alarm, user_message_ids));
+    }
+
+    Ok(web::Json(resp_alarms))
+}
+
+pub async fn alarm_delete(
+    req: web::Json<request::AlarmDeleteProps>,
+    data: web::Data<AppData>,
+) -> Result<impl Responder, AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&data.auth_service, req.api_key.clone()).await?;
+
+    let con: &mut tokio_postgres::Client = &mut *data.pool.get().await.map_err(report_pool_err)?;
+
+    let alarm = manage_alarm::delete_alarm(con, &data.push_hub, user.user_id, req.alarm_id).await?;
+
+    Ok(web::Json(fill_alarm(alarm, vec![])))
+}
+
+pub async fn alarm_snooze(
+    req: web::Json<request::AlarmSnoozeProps>,
+    data: web::Data<AppData>,
+) -> Result<impl Responder, AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&data.auth_service, req.api_key.clone()).await?;
+
+    let con: &mut tokio_postgres::Client = &mut *data.pool.get().await.map_err(report_pool_err)?;
+
+    let alarm = manage_alarm::snooze_alarm(
+        con,
+        &data.push_hub,
+        user.user_id,
+        req.alarm_id,
+        req.snooze_millis
+            .unwrap_or(manage_alarm::DEFAULT_SNOOZE_MILLIS),
+    )
+    .await?;
+
+    let user_message_ids = alarm_user_message_service::get_by_alarm_id(con, alarm.alarm_id)
+        .await
+        .map_err(report_postgres_err)?;
+
+    Ok(web::Json(fill_alarm(alarm, user_message_ids)))
+}
+
+pub async fn alarm_dismiss(
+    req: web::Json<request::AlarmDismissProps>,
+    data: web::Data<AppData>,
+) -> Result<impl Responder, AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&data.auth_service, req.api_key.clone()).await?;
+
+    let con: &mut tokio_postgres::Client = &mut *data.pool.get().await.map_err(report_pool_err)?;
+
+    let alarm =
+        manage_alarm::dismiss_alarm(con, &data.push_hub, user.user_id, req.alarm_id).await?;
+
+    let user_message_ids = alarm_user_message_service::get_by_alarm_id(con, alarm.alarm_id)
+        .await
+        .map_err(report_postgres_err)?;
+
+    Ok(web::Json(fill_alarm(alarm, user_message_ids)))
+}
+
+// add a rule that does things when you go to sleep or wake up
+pub async fn sleep_rule_new(
+    req: web::Json<request::SleepRuleNewProps>,
+    data: web::Data<AppData>,
+) -> Result<impl Responder, AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&data.auth_service, req.api_key.clone()).await?;
+
+    let con: &mut tokio_postgres::Client = &mut *data.pool.get().await.map_err(report_pool_err)?;
+
+    let (rule, actions) = manage_sleep_rule::create_rule(con, user.user_id, &req).await?;
+
+    Ok(web::Json(fill_sleep_rule(rule, actions)))
+}
+
+pub async fn sleep_rule_view(
+    req: web::Json<request::SleepRuleViewProps>,
+    data: web::Data<AppData>,
+) -> Result<impl Responder, AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&data.auth_service, req.api_key.clone()).await?;
+
+    let con: &mut tokio_postgres::Client = &mut *data.pool.get().await.map_err(report_pool_err)?;
+
+    let rules = manage_sleep_rule::get_rules(con, user.user_id).await?;
+
+    Ok(web::Json(
+        rules
+            .into_iter()
+            .map(|(rule, actions)| fill_sleep_rule(rule, actions))
+            .collect::<Vec<_>>(),
+    ))
+}
+
+pub async fn sleep_rule_delete(
+    req: web::Json<request::SleepRuleDeleteProps>,
+    data: web::Data<AppData>,
+) -> Result<impl Responder, AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&data.auth_service, req.api_key.clone()).await?;
+
+    let con: &mut tokio_postgres::Client = &mut *data.pool.get().await.map_err(report_pool_err)?;
+
+    let rule = manage_sleep_rule::delete_rule(con, user.user_id, req.sleep_rule_id).await?;
+
+    Ok(web::Json(fill_sleep_rule(rule, vec![])))
+}
+
+// whether your incoming messages are being held right now
+pub async fn do_not_disturb_view(
+    req: web::Json<request::DoNotDisturbViewProps>,
+    data: web::Data<AppData>,
+) -> Result<impl Responder, AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&data.auth_service, req.api_key.clone()).await?;
+
+    let con: &mut tokio_postgres::Client = &mut *data.pool.get().await.map_err(report_pool_err)?;
+
+    let dnd = manage_sleep_rule::get_active_do_not_disturb(
+        con,
+        user.user_id,
+        utils::current_time_millis(),
+    )
+    .await
+    .map_err(report_postgres_err)?;
+
+    Ok(web::Json(dnd.map(fill_do_not_disturb)))
+}
+
+// devices listen here for things they should do right away, like ringing an alarm
+pub async fn ws_push(
+    data: web::Data<AppData>,
+    req: HttpRequest,
+    stream: web::Payload,
+    query: web::Query<request::PushProps>,
+) -> Result<impl Responder, Error> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&data.auth_service, query.api_key.clone()).await?;
+
+    // subscribe before upgrading so that nothing pushed in between is lost
+    let receiver = data.push_hub.subscribe(user.user_id);
+
+    let (res, session, msg_stream) = actix_ws::handle(&req, stream)?;
+    // spawn websocket handler (and don't await it) so that the response is returned immediately
+    rt::spawn(manage_push::push_ws(
+        session,
+        msg_stream,
+        receiver,
+        vec![],
+        |x| !manage_sleep_status::is_sleep_status_event(x),
+    ));
+    Ok(res)
+}
+
+// partners listen here to see when the people sharing with them go to sleep or wake up
+pub async fn ws_sleep_status(
+    data: web::Data<AppData>,
+    req: HttpRequest,
+    stream: web::Payload,
+    query: web::Query<request::PushProps>,
+) -> Result<impl Responder, Error> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&data.auth_service, query.api_key.clone()).await?;
+
+    // subscribe before taking the snapshot so that no change falls in between
+    let receiver = data.push_hub.subscribe(user.user_id);
+
+    let sleep_statuses = {
+        let con: &mut tokio_postgres::Client =
+            &mut *data.pool.get().await.map_err(report_pool_err)?;
+        manage_sleep_status::get_shared_statuses(con, user.user_id).await?
+    };
+
+    let (res, session, msg_stream) = actix_ws::handle(&req, stream)?;
+    // spawn websocket handler (and don't await it) so that the response is returned immediately
+    rt::spawn(manage_push::push_ws(
+        session,
+        msg_stream,
+        receiver,
+        vec![response::PushEvent::SleepStatusSnapshot { sleep_statuses }],
+        manage_sleep_status::is_sleep_status_event,
+    ));
+    Ok(res)
+}
+
+// replace the bedtime routine
+pub async fn bedtime_routine_new(
+    req: web::Json<request::BedtimeRoutineNewProps>,
+    data: web::Data<AppData>,
+) -> Result<impl Responder, AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&data.auth_service, req.api_key.clone()).await?;
+
+    let con: &mut tokio_postgres::Client = &mut *data.pool.get().await.map_err(report_pool_err)?;
+
+    let (routine, tasks) = manage_bedtime_routine::set_routine(con, user.user_id, &req).await?;
+
+    Ok(web::Json(fill_bedtime_routine(routine, tasks)))
+}
+
+pub async fn bedtime_routine_view(
+    req: web::Json<request::BedtimeRoutineViewProps>,
+    data: web::Data<AppData>,
+) -> Result<impl Responder, AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&data.auth_service, req.api_key.clone()).await?;
+
+    let con: &mut tokio_postgres::Client = &mut *data.pool.get().await.map_err(report_pool_err)?;
+
+    let routine = manage_bedtime_routine::get_routine(con, user.user_id).await?;
+
+    Ok(web::Json(routine.map(|(routine, tasks)| {
+        fill_bedtime_routine(routine, tasks)
+    })))
+}
+
+// routine completion against how long the user slept
+pub async fn bedtime_routine_stats(
+    req: web::Json<request::BedtimeRoutineStatsProps>,
+    data: web::Data<AppData>,
+) -> Result<impl Responder, AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&data.auth_service, req.api_key.clone()).await?;
+
+    let con: &mut tokio_postgres::Client = &mut *data.pool.get().await.map_err(report_pool_err)?;
+
+    let stats = manage_bedtime_routine::get_routine_stats(con, user.user_id, &req).await?;
+
+    Ok(web::Json(fill_routine_stats(stats)))
 }
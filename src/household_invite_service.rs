@@ -0,0 +1,105 @@
+use super::db_types::*;
+use super::utils;
+use tokio_postgres::GenericClient;
+
+impl From<tokio_postgres::row::Row> for HouseholdInvite {
+    // select * from household_invite order only, otherwise it will fail
+    fn from(row: tokio_postgres::Row) -> HouseholdInvite {
+        HouseholdInvite {
+            household_invite_code: row.get("household_invite_code"),
+            creation_time: row.get("creation_time"),
+            creator_user_id: row.get("creator_user_id"),
+            household_id: row.get("household_id"),
+            role: row
+                .get::<_, i64>("role")
+                .try_into()
+                .expect("invalid household role"),
+            expiry_time: row.get("expiry_time"),
+            accepted_user_id: row.get("accepted_user_id"),
+            accepted_time: row.get("accepted_time"),
+        }
+    }
+}
+
+pub async fn add(
+    con: &mut impl GenericClient,
+    creator_user_id: i64,
+    household_id: i64,
+    role: HouseholdRole,
+    expiry_time: i64,
+) -> Result<HouseholdInvite, tokio_postgres::Error> {
+    let household_invite_code = utils::random_string();
+
+    let row = con
+        .query_one(
+            "INSERT INTO
+             household_invite(
+                 household_invite_code,
+                 creator_user_id,
+                 household_id,
+                 role,
+                 expiry_time
+             )
+             VALUES($1, $2, $3, $4, $5)
+             RETURNING creation_time
+            ",
+            &[
+                &household_invite_code,
+                &creator_user_id,
+                &household_id,
+                &i64::from(role),
+                &expiry_time,
+            ],
+        )
+        .await?;
+
+    // return household invite
+    Ok(HouseholdInvite {
+        household_invite_code,
+        creation_time: row.get(0),
+        creator_user_id,
+        household_id,
+        role,
+        expiry_time,
+        accepted_user_id: None,
+        accepted_time: None,
+    })
+}
+
+pub async fn get_by_household_invite_code(
+    con: &mut impl GenericClient,
+    household_invite_code: &str,
+) -> Result<Option<HouseholdInvite>, tokio_postgres::Error> {
+    let result = con
+        .query_opt(
+            "SELECT * FROM household_invite WHERE household_invite_code=$1",
+            &[&household_invite_code],
+        )
+        .await?
+        .map(|x| x.into());
+    Ok(result)
+}
+
+// uses up the invite, only if nobody has used it yet and it hasn't expired
+pub async fn accept(
+    con: &mut impl GenericClient,
+    household_invite_code: &str,
+    accepted_user_id: i64,
+    accepted_time: i64,
+) -> Result<Option<HouseholdInvite>, tokio_postgres::Error> {
+    let result = con
+        .query_opt(
+            "UPDATE household_invite
+             SET accepted_user_id = $2,
+                 accepted_time = $3
+             WHERE household_invite_code = $1
+             AND accepted_user_id IS NULL
+             AND expiry_time > $3
+             RETURNING *
+            ",
+            &[&household_invite_code, &accepted_user_id, &accepted_time],
+        )
+        .await?
+        .map(|x| x.into());
+    Ok(result)
+}
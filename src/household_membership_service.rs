@@ -0,0 +1,119 @@
+use super::db_types::*;
+use tokio_postgres::GenericClient;
+
+impl From<tokio_postgres::row::Row> for HouseholdMembership {
+    // select * from household_membership order only, otherwise it will fail
+    fn from(row: tokio_postgres::Row) -> HouseholdMembership {
+        HouseholdMembership {
+            household_membership_id: row.get("household_membership_id"),
+            creation_time: row.get("creation_time"),
+            creator_user_id: row.get("creator_user_id"),
+            household_id: row.get("household_id"),
+            user_id: row.get("user_id"),
+            role: row
+                .get::<_, i64>("role")
+                .try_into()
+                .expect("invalid household role"),
+            active: row.get("active"),
+        }
+    }
+}
+
+pub async fn add(
+    con: &mut impl GenericClient,
+    creator_user_id: i64,
+    household_id: i64,
+    user_id: i64,
+    role: HouseholdRole,
+    active: bool,
+) -> Result<HouseholdMembership, tokio_postgres::Error> {
+    let row = con
+        .query_one(
+            "INSERT INTO
+             household_membership(
+                 creator_user_id,
+                 household_id,
+                 user_id,
+                 role,
+                 active
+             )
+             VALUES($1, $2, $3, $4, $5)
+             RETURNING household_membership_id, creation_time
+            ",
+            &[
+                &creator_user_id,
+                &household_id,
+                &user_id,
+                &i64::from(role),
+                &active,
+            ],
+        )
+        .await?;
+
+    // return household membership
+    Ok(HouseholdMembership {
+        household_membership_id: row.get(0),
+        creation_time: row.get(1),
+        creator_user_id,
+        household_id,
+        user_id,
+        role,
+        active,
+    })
+}
+
+// the current membership of a user in a household, if they were ever part of it
+pub async fn get_recent(
+    con: &mut impl GenericClient,
+    household_id: i64,
+    user_id: i64,
+) -> Result<Option<HouseholdMembership>, tokio_postgres::Error> {
+    let result = con
+        .query_opt(
+            "SELECT * FROM recent_household_membership WHERE household_id=$1 AND user_id=$2",
+            &[&household_id, &user_id],
+        )
+        .await?
+        .map(|x| x.into());
+    Ok(result)
+}
+
+pub async fn get_active_by_household_id(
+    con: &mut impl GenericClient,
+    household_id: i64,
+) -> Result<Vec<HouseholdMembership>, tokio_postgres::Error> {
+    let result = con
+        .query(
+            "SELECT * FROM recent_household_membership
+             WHERE household_id=$1 AND active
+             ORDER BY household_membership_id
+            ",
+            &[&household_id],
+        )
+        .await?
+        .into_iter()
+        .map(|x| x.into())
+        .collect();
+    Ok(result)
+}
+
+// whether both users are currently members of at least one common household
+pub async fn shares_household(
+    con: &mut impl GenericClient,
+    user_id: i64,
+    other_user_id: i64,
+) -> Result<bool, tokio_postgres::Error> {
+    let row = con
+        .query_one(
+            "SELECT EXISTS(
+                 SELECT 1 FROM recent_household_membership a
+                 INNER JOIN recent_household_membership b ON a.household_id = b.household_id
+                 WHERE a.user_id=$1 AND a.active
+                 AND b.user_id=$2 AND b.active
+             )
+            ",
+            &[&user_id, &other_user_id],
+        )
+        .await?;
+    Ok(row.get(0))
+}
@@ -0,0 +1,93 @@
+use super::db_types::*;
+use tokio_postgres::GenericClient;
+
+impl From<tokio_postgres::row::Row> for Household {
+    // select * from household order only, otherwise it will fail
+    fn from(row: tokio_postgres::Row) -> Household {
+        Household {
+            household_id: row.get("household_id"),
+            creation_time: row.get("creation_time"),
+            creator_user_id: row.get("creator_user_id"),
+            name: row.get("name"),
+        }
+    }
+}
+
+pub async fn add(
+    con: &mut impl GenericClient,
+    creator_user_id: i64,
+    name: String,
+) -> Result<Household, tokio_postgres::Error> {
+    let row = con
+        .query_one(
+            "INSERT INTO
+             household(
+                 creator_user_id,
+                 name
+             )
+             VALUES($1, $2)
+             RETURNING household_id, creation_time
+            ",
+            &[&creator_user_id, &name],
+        )
+        .await?;
+
+    // return household
+    Ok(Household {
+        household_id: row.get(0),
+        creation_time: row.get(1),
+        creator_user_id,
+        name,
+    })
+}
+
+pub async fn get_by_household_id(
+    con: &mut impl GenericClient,
+    household_id: i64,
+) -> Result<Option<Household>, tokio_postgres::Error> {
+    let result = con
+        .query_opt(
+            "SELECT * FROM household WHERE household_id=$1",
+            &[&household_id],
+        )
+        .await?
+        .map(|x| x.into());
+    Ok(result)
+}
+
+// locks the household row until the end of the transaction,
+// so that changes to its membership happen one at a time
+pub async fn lock_by_household_id(
+    con: &mut impl GenericClient,
+    household_id: i64,
+) -> Result<Option<Household>, tokio_postgres::Error> {
+    let result = con
+        .query_opt(
+            "SELECT * FROM household WHERE household_id=$1 FOR UPDATE",
+            &[&household_id],
+        )
+        .await?
+        .map(|x| x.into());
+    Ok(result)
+}
+
+// every household the user is currently a member of
+pub async fn get_by_member_user_id(
+    con: &mut impl GenericClient,
+    user_id: i64,
+) -> Result<Vec<Household>, tokio_postgres::Error> {
+    let result = con
+        .query(
+            "SELECT h.* FROM household h
+             INNER JOIN recent_household_membership hm ON hm.household_id = h.household_id
+             WHERE hm.user_id = $1 AND hm.active
+             ORDER BY h.household_id
+            ",
+            &[&user_id],
+        )
+        .await?
+        .into_iter()
+        .map(|x| x.into())
+        .collect();
+    Ok(result)
+}
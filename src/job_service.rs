@@ -0,0 +1,149 @@
+use super::db_types::*;
+use tokio_postgres::GenericClient;
+
+impl From<tokio_postgres::row::Row> for Job {
+    // select * from job order only, otherwise it will fail
+    fn from(row: tokio_postgres::Row) -> Job {
+        Job {
+            job_id: row.get("job_id"),
+            creation_time: row.get("creation_time"),
+            kind: row.get("kind"),
+            payload: row.get("payload"),
+            unique_key: row.get("unique_key"),
+            run_at: row.get("run_at"),
+            attempts: row.get("attempts"),
+            max_attempts: row.get("max_attempts"),
+            lock_token: row.get("lock_token"),
+            locked_until: row.get("locked_until"),
+            last_error: row.get("last_error"),
+            finish_time: row.get("finish_time"),
+            succeeded: row.get("succeeded"),
+        }
+    }
+}
+
+// queues up a job. if unique_key is given and an unfinished job already has it, nothing is added.
+pub async fn add(
+    con: &mut impl GenericClient,
+    kind: String,
+    payload: String,
+    unique_key: Option<String>,
+    run_at: i64,
+    max_attempts: i64,
+) -> Result<Option<Job>, tokio_postgres::Error> {
+    let result = con
+        .query_opt(
+            "INSERT INTO
+             job(
+                 kind,
+                 payload,
+                 unique_key,
+                 run_at,
+                 max_attempts
+             )
+             VALUES($1, $2, $3, $4, $5)
+             ON CONFLICT (unique_key) WHERE finish_time IS NULL
+             DO NOTHING
+             RETURNING *
+            ",
+            &[&kind, &payload, &unique_key, &run_at, &max_attempts],
+        )
+        .await?
+        .map(|x| x.into());
+    Ok(result)
+}
+
+// takes the next runnable job, holding it until locked_until.
+// jobs whose holder let the lock lapse are picked up again.
+pub async fn claim(
+    con: &mut impl GenericClient,
+    current_time: i64,
+    lock_token: String,
+    locked_until: i64,
+) -> Result<Option<Job>, tokio_postgres::Error> {
+    let result = con
+        .query_opt(
+            "UPDATE job
+             SET lock_token = $2,
+                 locked_until = $3,
+                 attempts = attempts + 1
+             WHERE job_id = (
+                 SELECT job_id FROM job
+                 WHERE finish_time IS NULL
+                 AND run_at <= $1
+                 AND (locked_until IS NULL OR locked_until <= $1)
+                 ORDER BY run_at
+                 LIMIT 1
+                 FOR UPDATE SKIP LOCKED
+             )
+             RETURNING *
+            ",
+            &[&current_time, &lock_token, &locked_until],
+        )
+        .await?
+        .map(|x| x.into());
+    Ok(result)
+}
+
+// marks a job as succeeded. returns false if we no longer held it.
+pub async fn succeed(
+    con: &mut impl GenericClient,
+    job_id: i64,
+    lock_token: String,
+    current_time: i64,
+) -> Result<bool, tokio_postgres::Error> {
+    let n = con
+        .execute(
+            "UPDATE job
+             SET finish_time = $3,
+                 succeeded = TRUE,
+                 lock_token = NULL,
+                 locked_until = NULL
+             WHERE job_id = $1 AND lock_token = $2 AND finish_time IS NULL
+            ",
+            &[&job_id, &lock_token, &current_time],
+        )
+        .await?;
+    Ok(n == 1)
+}
+
+// records a failed attempt. the job is retried at retry_at, or given up on once out of attempts.
+// returns the job if we still held it.
+pub async fn fail(
+    con: &mut impl GenericClient,
+    job_id: i64,
+    lock_token: String,
+    current_time: i64,
+    retry_at: i64,
+    error: String,
+) -> Result<Option<Job>, tokio_postgres::Error> {
+    let result = con
+        .query_opt(
+            "UPDATE job
+             SET last_error = $5,
+                 lock_token = NULL,
+                 locked_until = NULL,
+                 run_at = CASE WHEN attempts < max_attempts THEN $4 ELSE run_at END,
+                 finish_time = CASE WHEN attempts >= max_attempts THEN $3::bigint END,
+                 succeeded = CASE WHEN attempts >= max_attempts THEN FALSE END
+             WHERE job_id = $1 AND lock_token = $2 AND finish_time IS NULL
+             RETURNING *
+            ",
+            &[&job_id, &lock_token, &current_time, &retry_at, &error],
+        )
+        .await?
+        .map(|x| x.into());
+    Ok(result)
+}
+
+// deletes jobs that finished before the given time. returns how many were deleted.
+pub async fn delete_finished_before(
+    con: &mut impl GenericClient,
+    finish_time: i64,
+) -> Result<u64, tokio_postgres::Error> {
+    con.execute(
+        "DELETE FROM job WHERE finish_time IS NOT NULL AND finish_time < $1",
+        &[&finish_time],
+    )
+    .await
+}
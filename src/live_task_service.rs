@@ -0,0 +1,87 @@
+use super::db_types::*;
+use tokio_postgres::GenericClient;
+
+impl From<tokio_postgres::row::Row> for LiveTask {
+    // select * from live_task order only, otherwise it will fail
+    fn from(row: tokio_postgres::Row) -> LiveTask {
+        LiveTask {
+            checkpoint_id: row.get("checkpoint_id"),
+            live_task_id: row.get("live_task_id"),
+            position: row.get("position"),
+            value: row.get("value"),
+        }
+    }
+}
+
+pub async fn add(
+    con: &mut impl GenericClient,
+    checkpoint_id: i64,
+    live_task_id: String,
+    position: i64,
+    value: String,
+) -> Result<LiveTask, tokio_postgres::Error> {
+    con.execute(
+        "INSERT INTO
+         live_task(
+             checkpoint_id,
+             live_task_id,
+             position,
+             value
+         )
+         VALUES($1, $2, $3, $4)
+        ",
+        &[&checkpoint_id, &live_task_id, &position, &value],
+    )
+    .await?;
+
+    // return live task
+    Ok(LiveTask {
+        checkpoint_id,
+        live_task_id,
+        position,
+        value,
+    })
+}
+
+// the checkpoint's live tasks, in order
+pub async fn get_by_checkpoint_id(
+    con: &mut impl GenericClient,
+    checkpoint_id: i64,
+) -> Result<Vec<LiveTask>, tokio_postgres::Error> {
+    let result = con
+        .query(
+            "SELECT * FROM live_task
+             WHERE checkpoint_id=$1
+             ORDER BY position
+            ",
+            &[&checkpoint_id],
+        )
+        .await?
+        .into_iter()
+        .map(|x| x.into())
+        .collect();
+    Ok(result)
+}
+
+// every checkpoint of the creator's that has this task live, oldest first
+pub async fn get_by_creator_user_id_and_live_task_id(
+    con: &mut impl GenericClient,
+    creator_user_id: i64,
+    live_task_id: String,
+) -> Result<Vec<LiveTask>, tokio_postgres::Error> {
+    let result = con
+        .query(
+            "SELECT lt.* FROM live_task lt
+             INNER JOIN checkpoint c ON c.checkpoint_id = lt.checkpoint_id
+             WHERE c.creator_user_id=$1
+             AND lt.live_task_id=$2
+             ORDER BY lt.checkpoint_id
+            ",
+            &[&creator_user_id, &live_task_id],
+        )
+        .await?
+        .into_iter()
+        .map(|x| x.into())
+        .collect();
+    Ok(result)
+}
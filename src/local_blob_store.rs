@@ -0,0 +1,94 @@
+use std::path::PathBuf;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use crate::blob_store::BlobStoreError;
+
+// stores blobs as plain files under a root directory
+#[derive(Clone, Debug)]
+pub struct LocalBlobStore {
+    root: PathBuf,
+}
+
+// a blob being written, it only appears under its key once finished
+pub struct LocalBlobWriter {
+    file: tokio::fs::File,
+    tmp_path: PathBuf,
+    path: PathBuf,
+}
+
+// a blob being read back
+pub struct LocalBlobReader {
+    file: tokio::fs::File,
+}
+
+impl LocalBlobStore {
+    pub fn new(root: impl Into<PathBuf>) -> LocalBlobStore {
+        LocalBlobStore { root: root.into() }
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.root.join(key)
+    }
+
+    pub async fn writer(&self, key: &str) -> Result<LocalBlobWriter, BlobStoreError> {
+        let path = self.path(key);
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        // write to a temporary file first so that readers never see a partial blob
+        let tmp_path = path.with_extension("part");
+        let file = tokio::fs::File::create(&tmp_path).await?;
+        Ok(LocalBlobWriter {
+            file,
+            tmp_path,
+            path,
+        })
+    }
+
+    pub async fn reader(&self, key: &str) -> Result<LocalBlobReader, BlobStoreError> {
+        let file = tokio::fs::File::open(self.path(key)).await?;
+        Ok(LocalBlobReader { file })
+    }
+
+    pub async fn delete(&self, key: &str) -> Result<(), BlobStoreError> {
+        match tokio::fs::remove_file(self.path(key)).await {
+            Ok(()) => Ok(()),
+            // deleting something that's already gone is fine
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+impl LocalBlobWriter {
+    pub async fn write(&mut self, chunk: &[u8]) -> Result<(), BlobStoreError> {
+        self.file.write_all(chunk).await?;
+        Ok(())
+    }
+
+    pub async fn finish(mut self) -> Result<(), BlobStoreError> {
+        self.file.flush().await?;
+        self.file.sync_all().await?;
+        tokio::fs::rename(&self.tmp_path, &self.path).await?;
+        Ok(())
+    }
+
+    pub async fn abort(self) -> Result<(), BlobStoreError> {
+        drop(self.file);
+        tokio::fs::remove_file(&self.tmp_path).await?;
+        Ok(())
+    }
+}
+
+impl LocalBlobReader {
+    pub async fn read(&mut self, max_len: usize) -> Result<Option<Vec<u8>>, BlobStoreError> {
+        let mut buf = vec![0; max_len];
+        let n = self.file.read(&mut buf).await?;
+        if n == 0 {
+            return Ok(None);
+        }
+        buf.truncate(n);
+        Ok(Some(buf))
+    }
+}
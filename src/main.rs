@@ -2,19 +2,73 @@ use std::net::Ipv4Addr;
 use std::str::FromStr;
 
 use actix_web::{middleware, web, App, HttpServer};
-use clap::Parser;
+use clap::{Parser, Subcommand, ValueEnum};
 
 use auth_service_api::client::AuthService;
 
+mod alarm_service;
+mod alarm_user_message_service;
+mod audio_blob_service;
+mod audio_transcript_service;
+mod blob_store;
+mod command_transcriber;
 mod db_types;
 mod handlers;
+mod job_service;
 mod request;
 mod response;
+mod sleep_import;
 mod utils;
 
+mod manage_alarm;
+mod manage_audio_blob;
+mod manage_bedtime_routine;
+mod manage_checkpoint;
+mod manage_contact;
+mod manage_household;
+mod manage_job;
+mod manage_push;
+mod manage_reaction;
+mod manage_retention;
+mod manage_sleep_event;
+mod manage_sleep_export;
+mod manage_sleep_import;
+mod manage_sleep_rule;
+mod manage_sleep_status;
+mod manage_transcript;
 mod manage_user_message;
+mod migrate_blobs;
+mod recurrence;
+mod upload_limits;
 
+mod apple_health_sleep_import;
+mod csv_sleep_import;
+mod fitbit_sleep_import;
+mod local_blob_store;
+mod s3_blob_store;
+mod transcriber;
+
+mod bedtime_routine_service;
+mod bedtime_routine_snapshot_service;
+mod bedtime_routine_task_service;
+mod checkpoint_service;
+mod contact_block_service;
+mod contact_request_service;
+mod contact_service;
+mod conversation_service;
+mod do_not_disturb_service;
+mod finished_task_service;
+mod household_invite_service;
+mod household_membership_service;
+mod household_service;
+mod live_task_service;
+mod retention_policy_service;
+mod sleep_event_edit_service;
 mod sleep_event_service;
+mod sleep_rule_action_service;
+mod sleep_rule_service;
+mod sleep_status_share_service;
+mod user_message_reaction_service;
 mod user_message_service;
 
 static SERVICE: &'static str = "kthg";
@@ -22,17 +76,88 @@ static VERSION_MAJOR: i64 = 0;
 static VERSION_MINOR: i64 = 0;
 static VERSION_REV: i64 = 1;
 
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum BlobStoreKind {
+    Local,
+    S3,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum TranscriberKind {
+    Off,
+    Noop,
+    Command,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+enum Command {
+    /// Move audio out of the legacy user_message.audio_data column into the blob store
+    MigrateBlobs,
+    /// Run background jobs without serving requests
+    Worker,
+    /// Import a user's sleep from another app's export
+    ImportSleep {
+        /// Whose sleep it is
+        #[clap(long)]
+        user_id: i64,
+        #[clap(long, value_enum)]
+        format: sleep_import::SleepImportFormat,
+        /// What times without an offset are in
+        #[clap(long, default_value = "UTC")]
+        time_zone: String,
+        path: std::path::PathBuf,
+    },
+}
+
 #[derive(Parser, Debug, Clone)]
 #[clap(about, version, author)]
+#[clap(subcommand_negates_reqs = true)]
 struct Opts {
-    #[clap(long)]
-    port: u16,
+    #[clap(long, required = true)]
+    port: Option<u16>,
     #[clap(long)]
     database_url: String,
+    #[clap(long, required = true)]
+    auth_service_url: Option<String>,
+    #[clap(long, required = true)]
+    app_pub_origin: Option<String>,
+    #[clap(long, value_enum, default_value = "local")]
+    blob_store: BlobStoreKind,
+    #[clap(long, default_value = "./blobs")]
+    blob_store_path: String,
+    #[clap(long)]
+    s3_endpoint: Option<String>,
+    #[clap(long, default_value = "us-east-1")]
+    s3_region: String,
+    #[clap(long)]
+    s3_bucket: Option<String>,
     #[clap(long)]
-    auth_service_url: String,
+    s3_access_key: Option<String>,
     #[clap(long)]
-    app_pub_origin: String,
+    s3_secret_key: Option<String>,
+    #[clap(long, default_value = "10485760")]
+    max_message_bytes: i64,
+    #[clap(long, default_value = "300")]
+    max_message_duration_secs: i64,
+    #[clap(long, default_value = "536870912")]
+    user_quota_bytes: i64,
+    /// How many background jobs this process runs at once, 0 to leave them to `kthg worker`
+    #[clap(long, default_value = "2")]
+    job_workers: usize,
+    /// How voice messages are transcribed, if at all
+    #[clap(long, value_enum, default_value = "off")]
+    transcriber: TranscriberKind,
+    /// Program run on each recording by the command transcriber
+    #[clap(long)]
+    transcribe_command: Option<String>,
+    /// Argument to the transcribe command, may be repeated. {audio} stands for the recording's path
+    #[clap(long, allow_hyphen_values = true)]
+    transcribe_arg: Vec<String>,
+    /// Users who may hard delete any message, comma separated
+    #[clap(long, value_delimiter = ',')]
+    admin_user_ids: Vec<i64>,
+    #[clap(subcommand)]
+    command: Option<Command>,
 }
 
 #[derive(Clone)]
@@ -40,28 +165,84 @@ pub struct AppData {
     pub auth_service: AuthService,
     pub app_pub_origin: String,
     pub pool: deadpool_postgres::Pool,
+    pub blob_store: blob_store::BlobStore,
+    pub upload_limits: upload_limits::UploadLimits,
+    pub push_hub: manage_push::PushHub,
+    pub admin_user_ids: Vec<i64>,
+    pub transcriber: Option<transcriber::Transcriber>,
+}
+
+fn build_blob_store(
+    opts: &Opts,
+) -> Result<blob_store::BlobStore, Box<dyn std::error::Error + 'static>> {
+    match opts.blob_store {
+        BlobStoreKind::Local => Ok(blob_store::BlobStore::Local(
+            local_blob_store::LocalBlobStore::new(&opts.blob_store_path),
+        )),
+        BlobStoreKind::S3 => {
+            let endpoint = opts
+                .s3_endpoint
+                .as_deref()
+                .ok_or("--s3-endpoint is required for the s3 blob store")?
+                .parse()?;
+            let bucket = opts
+                .s3_bucket
+                .clone()
+                .ok_or("--s3-bucket is required for the s3 blob store")?;
+            let access_key = opts
+                .s3_access_key
+                .clone()
+                .ok_or("--s3-access-key is required for the s3 blob store")?;
+            let secret_key = opts
+                .s3_secret_key
+                .clone()
+                .ok_or("--s3-secret-key is required for the s3 blob store")?;
+            Ok(blob_store::BlobStore::S3(s3_blob_store::S3BlobStore::new(
+                endpoint,
+                opts.s3_region.clone(),
+                bucket,
+                access_key,
+                secret_key,
+            )))
+        }
+    }
+}
+
+fn build_transcriber(
+    opts: &Opts,
+) -> Result<Option<transcriber::Transcriber>, Box<dyn std::error::Error + 'static>> {
+    match opts.transcriber {
+        TranscriberKind::Off => Ok(None),
+        TranscriberKind::Noop => Ok(Some(transcriber::Transcriber::Noop(
+            transcriber::NoopTranscriber,
+        ))),
+        TranscriberKind::Command => {
+            let program = opts
+                .transcribe_command
+                .clone()
+                .ok_or("--transcribe-command is required for the command transcriber")?;
+            Ok(Some(transcriber::Transcriber::Command(
+                command_transcriber::CommandTranscriber::new(program, opts.transcribe_arg.clone()),
+            )))
+        }
+    }
 }
 
 #[tokio::main(flavor = "current_thread")]
 async fn main() -> Result<(), Box<dyn std::error::Error + 'static>> {
     env_logger::init();
 
-    let Opts {
-        auth_service_url,
-        app_pub_origin,
-        port,
-        database_url,
-    } = Opts::parse();
+    let opts = Opts::parse();
 
     // connect to postgres
-    let postgres_config = tokio_postgres::Config::from_str(&database_url).map_err(|e| {
+    let postgres_config = tokio_postgres::Config::from_str(&opts.database_url).map_err(|e| {
         log::error!("couldn't parse database_url: {}", e);
         e
     })?;
     log::info!("parsed database url");
 
     let mgr = deadpool_postgres::Manager::from_config(
-        postgres_config,
+        postgres_config.clone(),
         tokio_postgres::NoTls,
         deadpool_postgres::ManagerConfig {
             recycling_method: deadpool_postgres::RecyclingMethod::Fast,
@@ -78,15 +259,96 @@ async fn main() -> Result<(), Box<dyn std::error::Error + 'static>> {
 
     log::info!("built database connection pool");
 
+    let blob_store = build_blob_store(&opts).map_err(|e| {
+        log::error!("couldn't configure blob store: {}", e);
+        e
+    })?;
+    log::info!("configured {:?} blob store", opts.blob_store);
+
+    let transcriber = build_transcriber(&opts).map_err(|e| {
+        log::error!("couldn't configure transcriber: {}", e);
+        e
+    })?;
+    log::info!("configured {:?} transcriber", opts.transcriber);
+
+    let job_context = manage_job::JobContext {
+        pool: pool.clone(),
+        blob_store: blob_store.clone(),
+        transcriber: transcriber.clone(),
+    };
+
+    // run commands instead of the server
+    match opts.command {
+        Some(Command::MigrateBlobs) => {
+            return migrate_blobs::migrate_blobs(&pool, &blob_store).await;
+        }
+        Some(Command::ImportSleep {
+            user_id,
+            format,
+            time_zone,
+            path,
+        }) => {
+            return manage_sleep_import::import_sleep_file(
+                &pool, user_id, format, &time_zone, &path,
+            )
+            .await;
+        }
+        Some(Command::Worker) => {
+            let workers = (0..opts.job_workers.max(1))
+                .map(|_| tokio::spawn(manage_job::work(job_context.clone())));
+            futures_util::future::join_all(workers).await;
+            return Ok(());
+        }
+        None => {}
+    }
+
+    // run background jobs, such as sweeping unreferenced audio out of the blob store
+    for _ in 0..opts.job_workers {
+        tokio::spawn(manage_job::work(job_context.clone()));
+    }
+
+    // hand events published by any process to the devices connected to this one
+    let push_hub = manage_push::PushHub::new(pool.clone());
+    tokio::spawn(manage_push::listen(
+        postgres_config.clone(),
+        push_hub.clone(),
+    ));
+
+    // ring alarms as they come due. several instances may do this at once, since each alarm is
+    // locked while it's rung, and the push reaches the target's devices on every instance
+    tokio::spawn(manage_alarm::fire_alarms_periodically(
+        pool.clone(),
+        push_hub.clone(),
+    ));
+
+    // these are required by clap whenever no subcommand is given
+    let port = opts.port.unwrap();
+    let auth_service_url = opts.auth_service_url.unwrap();
+    let app_pub_origin = opts.app_pub_origin.unwrap();
+
     // open connection to auth service
     let auth_service = AuthService::new(&auth_service_url);
     log::info!("connected to auth service");
 
+    let upload_limits = upload_limits::UploadLimits {
+        max_message_bytes: opts.max_message_bytes,
+        max_message_duration_millis: opts.max_message_duration_secs * 1000,
+        user_quota_bytes: opts.user_quota_bytes,
+    };
+
+    // json bodies carry the audio base64 encoded, so leave room for that
+    let json_limit = (opts.max_message_bytes as usize / 3 + 1) * 4 + 4096;
+
     // start server
     let data = AppData {
         auth_service,
         app_pub_origin,
         pool,
+        blob_store,
+        upload_limits,
+        push_hub,
+        admin_user_ids: opts.admin_user_ids,
+        transcriber,
     };
 
     HttpServer::new(move || {
@@ -95,6 +357,11 @@ async fn main() -> Result<(), Box<dyn std::error::Error + 'static>> {
             .wrap(middleware::Logger::default())
             // add data
             .app_data(actix_web::web::Data::new(data.clone()))
+            .app_data(
+                web::JsonConfig::default()
+                    .limit(json_limit)
+                    .error_handler(handlers::json_error_handler),
+            )
             // handle info query
             .service(web::resource("/public/info").route(web::route().to(handlers::info)))
             // submit user message
@@ -102,6 +369,11 @@ async fn main() -> Result<(), Box<dyn std::error::Error + 'static>> {
                 web::resource("/public/user_message/new")
                     .route(web::route().to(handlers::user_message_new)),
             )
+            // submit user message to several targets
+            .service(
+                web::resource("/public/user_message/broadcast")
+                    .route(web::route().to(handlers::user_message_broadcast)),
+            )
             // submit sleep event
             .service(
                 web::resource("/public/sleep_event/new")
@@ -112,11 +384,116 @@ async fn main() -> Result<(), Box<dyn std::error::Error + 'static>> {
                 web::resource("/public/user_message/view")
                     .route(web::route().to(handlers::user_message_view)),
             )
+            // search user messages by text and transcript
+            .service(
+                web::resource("/public/user_message/search")
+                    .route(web::route().to(handlers::user_message_search)),
+            )
+            // submit user message as a binary body
+            .service(
+                web::resource("/public/user_message/upload")
+                    .route(web::route().to(handlers::user_message_upload)),
+            )
+            // submit user message to several targets as a binary body
+            .service(
+                web::resource("/public/user_message/broadcast_upload")
+                    .route(web::route().to(handlers::user_message_broadcast_upload)),
+            )
+            // fetch user message audio
+            .service(
+                web::resource("/public/user_message/audio")
+                    .route(web::route().to(handlers::user_message_audio)),
+            )
+            // list conversations
+            .service(
+                web::resource("/public/conversation/view")
+                    .route(web::route().to(handlers::conversation_view)),
+            )
+            // page through a conversation
+            .service(
+                web::resource("/public/conversation/page")
+                    .route(web::route().to(handlers::conversation_page)),
+            )
+            // report that a message was listened to
+            .service(
+                web::resource("/public/user_message/played")
+                    .route(web::route().to(handlers::user_message_played)),
+            )
+            // take back a sent message
+            .service(
+                web::resource("/public/user_message/unsend")
+                    .route(web::route().to(handlers::user_message_unsend)),
+            )
+            // remove message from inbox
+            .service(
+                web::resource("/public/user_message/delete")
+                    .route(web::route().to(handlers::user_message_delete)),
+            )
+            // hard delete any message
+            .service(
+                web::resource("/public/admin/user_message/delete")
+                    .route(web::route().to(handlers::admin_user_message_delete)),
+            )
+            // react to a message
+            .service(
+                web::resource("/public/user_message_reaction/new")
+                    .route(web::route().to(handlers::user_message_reaction_new)),
+            )
+            // remove reaction
+            .service(
+                web::resource("/public/user_message_reaction/delete")
+                    .route(web::route().to(handlers::user_message_reaction_delete)),
+            )
+            // view own scheduled messages
+            .service(
+                web::resource("/public/scheduled_user_message/view")
+                    .route(web::route().to(handlers::scheduled_user_message_view)),
+            )
+            // change when a scheduled message is delivered
+            .service(
+                web::resource("/public/scheduled_user_message/reschedule")
+                    .route(web::route().to(handlers::scheduled_user_message_reschedule)),
+            )
+            // withdraw a scheduled message
+            .service(
+                web::resource("/public/scheduled_user_message/cancel")
+                    .route(web::route().to(handlers::scheduled_user_message_cancel)),
+            )
             // view sleep event
             .service(
                 web::resource("/public/sleep_event/view")
                     .route(web::route().to(handlers::sleep_event_view)),
             )
+            // record a sleep event after the fact
+            .service(
+                web::resource("/public/sleep_event/backfill")
+                    .route(web::route().to(handlers::sleep_event_backfill)),
+            )
+            // correct a sleep event
+            .service(
+                web::resource("/public/sleep_event/update")
+                    .route(web::route().to(handlers::sleep_event_update)),
+            )
+            // remove a sleep event
+            .service(
+                web::resource("/public/sleep_event/delete")
+                    .route(web::route().to(handlers::sleep_event_delete)),
+            )
+            // import sleep from another app
+            .service(
+                web::resource("/public/sleep_event/import")
+                    .route(web::route().to(handlers::sleep_event_import)),
+            )
+            // download sleep as csv, json or icalendar
+            .service(
+                web::resource("/public/sleep_event/export")
+                    .route(web::route().to(handlers::sleep_event_export)),
+            )
+            // view sleep event edits
+            .service(
+                web::resource("/public/sleep_event/history")
+                    .route(web::route().to(handlers::sleep_event_history)),
+            )
             // get recent id
             .service(
                 web::resource("/public/get_recent_user_message_id")
@@ -127,6 +504,177 @@ async fn main() -> Result<(), Box<dyn std::error::Error + 'static>> {
                 web::resource("/public/query_params_sleep_event_new")
                     .route(web::route().to(handlers::query_params_sleep_event_new)),
             )
+            // create household
+            .service(
+                web::resource("/public/household/new")
+                    .route(web::route().to(handlers::household_new)),
+            )
+            // view own households
+            .service(
+                web::resource("/public/household/view")
+                    .route(web::route().to(handlers::household_view)),
+            )
+            // leave household
+            .service(
+                web::resource("/public/household/leave")
+                    .route(web::route().to(handlers::household_leave)),
+            )
+            // change household member role
+            .service(
+                web::resource("/public/household_membership/new")
+                    .route(web::route().to(handlers::household_membership_new)),
+            )
+            // view household members
+            .service(
+                web::resource("/public/household_membership/view")
+                    .route(web::route().to(handlers::household_membership_view)),
+            )
+            // invite to household
+            .service(
+                web::resource("/public/household_invite/new")
+                    .route(web::route().to(handlers::household_invite_new)),
+            )
+            // join household with invite code
+            .service(
+                web::resource("/public/household_invite/accept")
+                    .route(web::route().to(handlers::household_invite_accept)),
+            )
+            // ask to pair with another user
+            .service(
+                web::resource("/public/contact_request/new")
+                    .route(web::route().to(handlers::contact_request_new)),
+            )
+            // accept or decline pairing
+            .service(
+                web::resource("/public/contact_request/respond")
+                    .route(web::route().to(handlers::contact_request_respond)),
+            )
+            // view pending pairing requests
+            .service(
+                web::resource("/public/contact_request/view")
+                    .route(web::route().to(handlers::contact_request_view)),
+            )
+            // share sleep status
+            .service(
+                web::resource("/public/sleep_status_share/new")
+                    .route(web::route().to(handlers::sleep_status_share_new)),
+            )
+            // view who you share sleep status with
+            .service(
+                web::resource("/public/sleep_status_share/view")
+                    .route(web::route().to(handlers::sleep_status_share_view)),
+            )
+            // view contacts
+            .service(
+                web::resource("/public/contact/view")
+                    .route(web::route().to(handlers::contact_view)),
+            )
+            // block or unblock user
+            .service(
+                web::resource("/public/contact_block/new")
+                    .route(web::route().to(handlers::contact_block_new)),
+            )
+            // view blocked users
+            .service(
+                web::resource("/public/contact_block/view")
+                    .route(web::route().to(handlers::contact_block_view)),
+            )
+            // set how long messages are kept
+            .service(
+                web::resource("/public/retention_policy/new")
+                    .route(web::route().to(handlers::retention_policy_new)),
+            )
+            // view retention policy
+            .service(
+                web::resource("/public/retention_policy/view")
+                    .route(web::route().to(handlers::retention_policy_view)),
+            )
+            // save task list
+            .service(
+                web::resource("/public/checkpoint/new")
+                    .route(web::route().to(handlers::checkpoint_new)),
+            )
+            // view latest or past task list
+            .service(
+                web::resource("/public/checkpoint/view")
+                    .route(web::route().to(handlers::checkpoint_view)),
+            )
+            // list past task lists
+            .service(
+                web::resource("/public/checkpoint/list")
+                    .route(web::route().to(handlers::checkpoint_list)),
+            )
+            // compare two task lists
+            .service(
+                web::resource("/public/checkpoint/diff")
+                    .route(web::route().to(handlers::checkpoint_diff)),
+            )
+            // follow one task across task lists
+            .service(
+                web::resource("/public/checkpoint/task_history")
+                    .route(web::route().to(handlers::checkpoint_task_history)),
+            )
+            // set bedtime routine
+            .service(
+                web::resource("/public/bedtime_routine/new")
+                    .route(web::route().to(handlers::bedtime_routine_new)),
+            )
+            // view bedtime routine
+            .service(
+                web::resource("/public/bedtime_routine/view")
+                    .route(web::route().to(handlers::bedtime_routine_view)),
+            )
+            // routine completion against sleep
+            .service(
+                web::resource("/public/bedtime_routine/stats")
+                    .route(web::route().to(handlers::bedtime_routine_stats)),
+            )
+            // set alarm
+            .service(web::resource("/public/alarm/new").route(web::route().to(handlers::alarm_new)))
+            // change alarm
+            .service(
+                web::resource("/public/alarm/update")
+                    .route(web::route().to(handlers::alarm_update)),
+            )
+            // view alarms
+            .service(
+                web::resource("/public/alarm/view").route(web::route().to(handlers::alarm_view)),
+            )
+            // delete alarm
+            .service(
+                web::resource("/public/alarm/delete")
+                    .route(web::route().to(handlers::alarm_delete)),
+            )
+            // snooze ringing alarm
+            .service(
+                web::resource("/public/alarm/snooze")
+                    .route(web::route().to(handlers::alarm_snooze)),
+            )
+            // dismiss ringing alarm
+            .service(
+                web::resource("/public/alarm/dismiss")
+                    .route(web::route().to(handlers::alarm_dismiss)),
+            )
+            // add a rule run on sleep events
+            .service(
+                web::resource("/public/sleep_rule/new")
+                    .route(web::route().to(handlers::sleep_rule_new)),
+            )
+            // view sleep rules
+            .service(
+                web::resource("/public/sleep_rule/view")
+                    .route(web::route().to(handlers::sleep_rule_view)),
+            )
+            // delete sleep rule
+            .service(
+                web::resource("/public/sleep_rule/delete")
+                    .route(web::route().to(handlers::sleep_rule_delete)),
+            )
+            // view current do not disturb
+            .service(
+                web::resource("/public/do_not_disturb/view")
+                    .route(web::route().to(handlers::do_not_disturb_view)),
+            )
             // websocket submit recording
             .service(
                 web::resource("/public/ws/submit_user_message")
@@ -137,6 +685,13 @@ async fn main() -> Result<(), Box<dyn std::error::Error + 'static>> {
                 web::resource("/public/ws/receive_user_message")
                     .route(web::route().to(handlers::ws_receive_user_message)),
             )
+            // websocket device push events
+            .service(web::resource("/public/ws/push").route(web::route().to(handlers::ws_push)))
+            // websocket sleep status of users sharing with you
+            .service(
+                web::resource("/public/ws/sleep_status")
+                    .route(web::route().to(handlers::ws_sleep_status)),
+            )
     })
     .bind((Ipv4Addr::new(0, 0, 0, 0), port))?
     .run()
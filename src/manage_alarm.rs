@@ -0,0 +1,369 @@
+use std::time::Duration;
+
+use chrono::{DateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use tokio_postgres::GenericClient;
+
+use crate::{
+    alarm_service, alarm_user_message_service,
+    db_types::Alarm,
+    handlers::{self, AppError},
+    manage_contact,
+    manage_push::PushHub,
+    manage_user_message,
+    recurrence::Recurrence,
+    request,
+    response::PushEvent,
+    user_message_service, utils,
+};
+
+/// How often the scheduler looks for alarms that need to ring.
+const ALARM_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+// occurrences missed by more than this (say, while the server was down) are skipped instead of rung late
+const MAX_FIRE_DELAY_MILLIS: i64 = 10 * 60 * 1000;
+
+// how many alarms to ring per round trip
+const ALARM_BATCH_SIZE: i64 = 64;
+
+pub const DEFAULT_SNOOZE_MILLIS: i64 = 9 * 60 * 1000;
+const MAX_SNOOZE_MILLIS: i64 = 60 * 60 * 1000;
+
+// when an alarm rings
+pub struct Schedule {
+    recurrence: Recurrence,
+    start: DateTime<Tz>,
+}
+
+fn to_utc(millis: i64) -> Result<DateTime<Utc>, AppError> {
+    Utc.timestamp_millis_opt(millis)
+        .single()
+        .ok_or(AppError::BadRequest)
+}
+
+impl Schedule {
+    pub fn parse(recurrence: &str, time_zone: &str, start_time: i64) -> Result<Schedule, AppError> {
+        let recurrence = recurrence.parse::<Recurrence>().map_err(|e| {
+            log::info!("{}", e);
+            AppError::BadRequest
+        })?;
+        let tz = time_zone.parse::<Tz>().map_err(|e| {
+            log::info!("{}", e);
+            AppError::BadRequest
+        })?;
+        Ok(Schedule {
+            recurrence,
+            start: to_utc(start_time)?.with_timezone(&tz),
+        })
+    }
+
+    // the first time the alarm rings after the given time, if it ever does
+    pub fn next_after(&self, time: i64) -> Option<i64> {
+        let after = to_utc(time).ok()?;
+        self.recurrence
+            .next_after(self.start, after)
+            .map(|t| t.timestamp_millis())
+    }
+}
+
+// an alarm may only play messages that both the person who set it and the target can listen to
+async fn check_user_message_ids(
+    con: &mut impl GenericClient,
+    creator_user_id: i64,
+    target_user_id: i64,
+    user_message_ids: &[i64],
+) -> Result<(), AppError> {
+    if user_message_ids.is_empty() {
+        return Err(AppError::BadRequest);
+    }
+
+    let now = utils::current_time_millis();
+
+    for user_message_id in user_message_ids {
+        let um = user_message_service::get_by_user_message_id(con, *user_message_id)
+            .await
+            .map_err(handlers::report_postgres_err)?
+            .ok_or(AppError::NotFound)?;
+
+        let can_listen = |user_id: i64| {
+            um.creator_user_id == user_id
+                || (um.target_user_id == user_id
+                    && manage_user_message::is_visible_to_target(&um, now))
+        };
+
+        if !can_listen(creator_user_id) || !can_listen(target_user_id) {
+            return Err(AppError::Forbidden);
+        }
+    }
+    Ok(())
+}
+
+// the alarm, as long as the user is the one who set it or the one it rings for
+async fn get_alarm_for_user(
+    con: &mut impl GenericClient,
+    user_id: i64,
+    alarm_id: i64,
+) -> Result<Alarm, AppError> {
+    let alarm = alarm_service::get_by_alarm_id(con, alarm_id)
+        .await
+        .map_err(handlers::report_postgres_err)?
+        .ok_or(AppError::NotFound)?;
+
+    if alarm.creator_user_id != user_id && alarm.target_user_id != user_id {
+        return Err(AppError::Forbidden);
+    }
+    Ok(alarm)
+}
+
+pub async fn create_alarm(
+    con: &mut tokio_postgres::Client,
+    creator_user_id: i64,
+    props: &request::AlarmNewProps,
+) -> Result<(Alarm, Vec<i64>), AppError> {
+    // setting an alarm for someone else is like sending them a message
+    manage_contact::check_can_message(con, creator_user_id, props.target_user_id).await?;
+
+    let now = utils::current_time_millis();
+    let start_time = props.start_time.unwrap_or(now);
+    let schedule = Schedule::parse(&props.recurrence, &props.time_zone, start_time)?;
+
+    check_user_message_ids(
+        con,
+        creator_user_id,
+        props.target_user_id,
+        &props.user_message_ids,
+    )
+    .await?;
+
+    let mut tx = con
+        .transaction()
+        .await
+        .map_err(handlers::report_postgres_err)?;
+
+    let alarm = alarm_service::add(
+        &mut tx,
+        creator_user_id,
+        props.target_user_id,
+        props.recurrence.clone(),
+        props.time_zone.clone(),
+        start_time,
+        schedule.next_after(now),
+    )
+    .await
+    .map_err(handlers::report_postgres_err)?;
+
+    alarm_user_message_service::set_by_alarm_id(&mut tx, alarm.alarm_id, &props.user_message_ids)
+        .await
+        .map_err(handlers::report_postgres_err)?;
+
+    tx.commit().await.map_err(handlers::report_postgres_err)?;
+    Ok((alarm, props.user_message_ids.clone()))
+}
+
+// fields that aren't given are left as they are
+pub async fn update_alarm(
+    con: &mut tokio_postgres::Client,
+    user_id: i64,
+    props: &request::AlarmUpdateProps,
+) -> Result<(Alarm, Vec<i64>), AppError> {
+    let mut tx = con
+        .transaction()
+        .await
+        .map_err(handlers::report_postgres_err)?;
+
+    let mut alarm = get_alarm_for_user(&mut tx, user_id, props.alarm_id).await?;
+
+    if let Some(recurrence) = &props.recurrence {
+        alarm.recurrence = recurrence.clone();
+    }
+    if let Some(time_zone) = &props.time_zone {
+        alarm.time_zone = time_zone.clone();
+    }
+    if let Some(start_time) = props.start_time {
+        alarm.start_time = start_time;
+    }
+    if let Some(active) = props.active {
+        alarm.active = active;
+    }
+
+    let schedule = Schedule::parse(&alarm.recurrence, &alarm.time_zone, alarm.start_time)?;
+    alarm.next_fire_time = schedule.next_after(utils::current_time_millis());
+    if !alarm.active {
+        alarm.snooze_until = None;
+    }
+
+    alarm_service::update(&mut tx, &alarm)
+        .await
+        .map_err(handlers::report_postgres_err)?;
+
+    let user_message_ids = match &props.user_message_ids {
+        Some(user_message_ids) => {
+            check_user_message_ids(&mut tx, user_id, alarm.target_user_id, user_message_ids)
+                .await?;
+            alarm_user_message_service::set_by_alarm_id(&mut tx, alarm.alarm_id, user_message_ids)
+                .await
+                .map_err(handlers::report_postgres_err)?;
+            user_message_ids.clone()
+        }
+        None => alarm_user_message_service::get_by_alarm_id(&mut tx, alarm.alarm_id)
+            .await
+            .map_err(handlers::report_postgres_err)?,
+    };
+
+    tx.commit().await.map_err(handlers::report_postgres_err)?;
+    Ok((alarm, user_message_ids))
+}
+
+pub async fn delete_alarm(
+    con: &mut tokio_postgres::Client,
+    push_hub: &PushHub,
+    user_id: i64,
+    alarm_id: i64,
+) -> Result<Alarm, AppError> {
+    let alarm = get_alarm_for_user(con, user_id, alarm_id).await?;
+
+    alarm_service::delete(con, alarm_id)
+        .await
+        .map_err(handlers::report_postgres_err)?;
+
+    push_hub.push(alarm.target_user_id, PushEvent::StopAlarm { alarm_id });
+    Ok(alarm)
+}
+
+// stops the alarm for now, it rings again once the snooze is over
+pub async fn snooze_alarm(
+    con: &mut tokio_postgres::Client,
+    push_hub: &PushHub,
+    user_id: i64,
+    alarm_id: i64,
+    snooze_millis: i64,
+) -> Result<Alarm, AppError> {
+    if snooze_millis <= 0 || snooze_millis > MAX_SNOOZE_MILLIS {
+        return Err(AppError::BadRequest);
+    }
+
+    let mut alarm = get_alarm_for_user(con, user_id, alarm_id).await?;
+    if !alarm.active {
+        return Err(AppError::BadRequest);
+    }
+
+    alarm.snooze_until = Some(utils::current_time_millis() + snooze_millis);
+    alarm_service::update(con, &alarm)
+        .await
+        .map_err(handlers::report_postgres_err)?;
+
+    push_hub.push(alarm.target_user_id, PushEvent::StopAlarm { alarm_id });
+    Ok(alarm)
+}
+
+// stops the alarm until its next regular occurrence
+pub async fn dismiss_alarm(
+    con: &mut tokio_postgres::Client,
+    push_hub: &PushHub,
+    user_id: i64,
+    alarm_id: i64,
+) -> Result<Alarm, AppError> {
+    let mut alarm = get_alarm_for_user(con, user_id, alarm_id).await?;
+
+    alarm.snooze_until = None;
+    alarm_service::update(con, &alarm)
+        .await
+        .map_err(handlers::report_postgres_err)?;
+
+    push_hub.push(alarm.target_user_id, PushEvent::StopAlarm { alarm_id });
+    Ok(alarm)
+}
+
+// rings every alarm that is due, and works out when each rings next
+pub async fn fire_due_alarms(
+    pool: &deadpool_postgres::Pool,
+    push_hub: &PushHub,
+) -> Result<u64, AppError> {
+    let con: &mut tokio_postgres::Client =
+        &mut *pool.get().await.map_err(handlers::report_pool_err)?;
+
+    let mut fired: u64 = 0;
+
+    loop {
+        let now = utils::current_time_millis();
+
+        let mut tx = con
+            .transaction()
+            .await
+            .map_err(handlers::report_postgres_err)?;
+
+        let alarms = alarm_service::lock_due(&mut tx, now, ALARM_BATCH_SIZE)
+            .await
+            .map_err(handlers::report_postgres_err)?;
+
+        if alarms.is_empty() {
+            break;
+        }
+
+        let mut events = vec![];
+
+        for mut alarm in alarms {
+            let mut fire_time = None;
+
+            if let Some(next_fire_time) = alarm.next_fire_time.filter(|t| *t <= now) {
+                fire_time = Some(next_fire_time);
+                alarm.next_fire_time =
+                    match Schedule::parse(&alarm.recurrence, &alarm.time_zone, alarm.start_time) {
+                        Ok(schedule) => schedule.next_after(now),
+                        Err(_) => {
+                            log::error!("alarm {} has an invalid schedule", alarm.alarm_id);
+                            None
+                        }
+                    };
+            }
+
+            if let Some(snooze_until) = alarm.snooze_until.filter(|t| *t <= now) {
+                fire_time = Some(snooze_until);
+                alarm.snooze_until = None;
+            }
+
+            if let Some(fire_time) = fire_time.filter(|t| now - t <= MAX_FIRE_DELAY_MILLIS) {
+                alarm.last_fire_time = Some(now);
+                let user_message_ids =
+                    alarm_user_message_service::get_by_alarm_id(&mut tx, alarm.alarm_id)
+                        .await
+                        .map_err(handlers::report_postgres_err)?;
+                events.push((
+                    alarm.target_user_id,
+                    PushEvent::PlayAlarm {
+                        alarm_id: alarm.alarm_id,
+                        user_message_ids,
+                        fire_time,
+                    },
+                ));
+            }
+
+            alarm_service::update(&mut tx, &alarm)
+                .await
+                .map_err(handlers::report_postgres_err)?;
+        }
+
+        tx.commit().await.map_err(handlers::report_postgres_err)?;
+
+        // only push once we know the alarms won't ring a second time
+        for (target_user_id, event) in events {
+            push_hub.push(target_user_id, event);
+            fired += 1;
+        }
+    }
+
+    Ok(fired)
+}
+
+pub async fn fire_alarms_periodically(pool: deadpool_postgres::Pool, push_hub: PushHub) {
+    let mut interval = tokio::time::interval(ALARM_CHECK_INTERVAL);
+    loop {
+        interval.tick().await;
+        match fire_due_alarms(&pool, &push_hub).await {
+            Ok(0) => {}
+            Ok(n) => log::info!("rang {} alarms", n),
+            // errors have already been logged
+            Err(_) => {}
+        }
+    }
+}
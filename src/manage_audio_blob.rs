@@ -0,0 +1,45 @@
+use std::time::Duration;
+
+use crate::audio_blob_service;
+use crate::blob_store::BlobStore;
+use crate::handlers::{self, AppError};
+
+/// How often unreferenced blobs are swept out of the blob store.
+pub const GARBAGE_COLLECTION_INTERVAL: Duration = Duration::from_secs(60);
+
+// how many blobs to delete per round trip
+const GARBAGE_BATCH_SIZE: i64 = 64;
+
+// deletes blobs that no user message references anymore from the blob store
+pub async fn collect_garbage(
+    pool: &deadpool_postgres::Pool,
+    blob_store: &BlobStore,
+) -> Result<u64, AppError> {
+    let con: &mut tokio_postgres::Client =
+        &mut *pool.get().await.map_err(handlers::report_pool_err)?;
+
+    let mut collected: u64 = 0;
+
+    loop {
+        let keys = audio_blob_service::get_garbage(con, GARBAGE_BATCH_SIZE)
+            .await
+            .map_err(handlers::report_postgres_err)?;
+
+        if keys.is_empty() {
+            break;
+        }
+
+        for audio_blob_key in keys {
+            blob_store
+                .delete(&audio_blob_key)
+                .await
+                .map_err(handlers::report_blob_store_err)?;
+            audio_blob_service::remove_garbage(con, audio_blob_key)
+                .await
+                .map_err(handlers::report_postgres_err)?;
+            collected += 1;
+        }
+    }
+
+    Ok(collected)
+}
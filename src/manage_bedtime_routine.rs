@@ -0,0 +1,263 @@
+use std::collections::HashSet;
+
+use tokio_postgres::GenericClient;
+
+use crate::{
+    bedtime_routine_service, bedtime_routine_snapshot_service, bedtime_routine_task_service,
+    checkpoint_service,
+    db_types::{
+        BedtimeRoutine, BedtimeRoutineSnapshot, BedtimeRoutineTask, RoutineNight, SleepEvent,
+        SleepEventKind,
+    },
+    finished_task_service,
+    handlers::{self, AppError},
+    manage_checkpoint::{MAX_CHECKPOINT_TASKS, MAX_TASK_ID_CHARS, MAX_TASK_VALUE_CHARS},
+    request, sleep_event_service,
+};
+
+// how far back tonight's routine can have been started, if the user hasn't woken up since
+const MAX_ROUTINE_WINDOW_MILLIS: i64 = 24 * 60 * 60 * 1000;
+
+// nights sharing how much of the routine was finished
+#[derive(Clone, Debug)]
+pub struct RoutineBucket {
+    pub night_count: i64,
+    // only nights the user woke up from count towards the average
+    pub average_sleep_millis: Option<i64>,
+}
+
+#[derive(Clone, Debug)]
+pub struct RoutineStats {
+    pub nights: Vec<RoutineNight>,
+    // the whole routine was finished
+    pub complete: RoutineBucket,
+    pub partial: RoutineBucket,
+    // none of it was finished
+    pub skipped: RoutineBucket,
+}
+
+// every task needs an id, and a task can only be in the routine once.
+// routine tasks are checkpoint tasks, so they're capped the same way.
+fn check_tasks(props: &request::BedtimeRoutineNewProps) -> Result<(), AppError> {
+    if props.tasks.len() > MAX_CHECKPOINT_TASKS {
+        return Err(AppError::BadRequest);
+    }
+
+    let mut seen = HashSet::new();
+    for task in props.tasks.iter() {
+        let id = &task.bedtime_routine_task_id;
+        if id.is_empty()
+            || id.chars().count() > MAX_TASK_ID_CHARS
+            || task.value.chars().count() > MAX_TASK_VALUE_CHARS
+            || !seen.insert(id)
+        {
+            return Err(AppError::BadRequest);
+        }
+    }
+    Ok(())
+}
+
+// replaces the user's routine. an empty one turns the routine off.
+pub async fn set_routine(
+    con: &mut tokio_postgres::Client,
+    creator_user_id: i64,
+    props: &request::BedtimeRoutineNewProps,
+) -> Result<(BedtimeRoutine, Vec<BedtimeRoutineTask>), AppError> {
+    check_tasks(props)?;
+
+    let mut tx = con
+        .transaction()
+        .await
+        .map_err(handlers::report_postgres_err)?;
+
+    let routine = bedtime_routine_service::add(&mut tx, creator_user_id)
+        .await
+        .map_err(handlers::report_postgres_err)?;
+
+    let mut tasks = vec![];
+    for (position, task) in props.tasks.iter().enumerate() {
+        let task = bedtime_routine_task_service::add(
+            &mut tx,
+            routine.bedtime_routine_id,
+            task.bedtime_routine_task_id.clone(),
+            position as i64,
+            task.value.clone(),
+        )
+        .await
+        .map_err(handlers::report_postgres_err)?;
+        tasks.push(task);
+    }
+
+    tx.commit().await.map_err(handlers::report_postgres_err)?;
+    Ok((routine, tasks))
+}
+
+// the user's current routine, if they have one
+pub async fn get_routine(
+    con: &mut impl GenericClient,
+    user_id: i64,
+) -> Result<Option<(BedtimeRoutine, Vec<BedtimeRoutineTask>)>, AppError> {
+    let routine = match bedtime_routine_service::get_recent_by_creator_user_id(con, user_id)
+        .await
+        .map_err(handlers::report_postgres_err)?
+    {
+        Some(routine) => routine,
+        None => return Ok(None),
+    };
+
+    let tasks =
+        bedtime_routine_task_service::get_by_bedtime_routine_id(con, routine.bedtime_routine_id)
+            .await
+            .map_err(handlers::report_postgres_err)?;
+
+    Ok(Some((routine, tasks)))
+}
+
+// the ids of the tasks that were finished in the checkpoint
+async fn get_finished_task_ids(
+    con: &mut impl GenericClient,
+    checkpoint_id: i64,
+) -> Result<HashSet<String>, tokio_postgres::Error> {
+    Ok(
+        finished_task_service::get_by_checkpoint_id(con, checkpoint_id)
+            .await?
+            .into_iter()
+            .map(|x| x.finished_task_id)
+            .collect(),
+    )
+}
+
+// records how much of tonight's routine the user finished before going to sleep.
+// a routine task counts if it is finished on the last task list saved tonight,
+// unless it has stayed finished since before tonight started.
+// tonight starts when the user last woke up, but no more than a day ago.
+pub async fn snapshot_routine(
+    con: &mut impl GenericClient,
+    sleep_event: &SleepEvent,
+) -> Result<Option<BedtimeRoutineSnapshot>, tokio_postgres::Error> {
+    let user_id = sleep_event.creator_user_id;
+    let sleep_time = sleep_event.creation_time;
+
+    let routine = match bedtime_routine_service::get_recent_by_creator_user_id(con, user_id).await?
+    {
+        Some(routine) => routine,
+        None => return Ok(None),
+    };
+    let tasks =
+        bedtime_routine_task_service::get_by_bedtime_routine_id(con, routine.bedtime_routine_id)
+            .await?;
+    if tasks.is_empty() {
+        return Ok(None);
+    }
+
+    let last_wake = sleep_event_service::get_recent_by_user_id_and_kind_before(
+        con,
+        user_id,
+        SleepEventKind::Wake,
+        sleep_time,
+    )
+    .await?;
+    let night_start = last_wake
+        .map(|x| x.creation_time)
+        .unwrap_or(i64::MIN)
+        .max(sleep_time - MAX_ROUTINE_WINDOW_MILLIS);
+
+    let last = checkpoint_service::get_by_creator_user_id(
+        con,
+        user_id,
+        Some(night_start),
+        Some(sleep_time),
+        1,
+    )
+    .await?
+    .pop();
+
+    let finished_count = match &last {
+        Some(last) => {
+            let finished_last = get_finished_task_ids(con, last.checkpoint_id).await?;
+
+            // whatever was finished before tonight and on every list since was done on an earlier night
+            let carried_over: HashSet<String> = finished_task_service::get_ids_finished_throughout(
+                con,
+                user_id,
+                night_start,
+                sleep_time,
+            )
+            .await?
+            .into_iter()
+            .collect();
+
+            tasks
+                .iter()
+                .filter(|x| {
+                    finished_last.contains(&x.bedtime_routine_task_id)
+                        && !carried_over.contains(&x.bedtime_routine_task_id)
+                })
+                .count() as i64
+        }
+        None => 0,
+    };
+
+    let snapshot = bedtime_routine_snapshot_service::add(
+        con,
+        sleep_event.sleep_event_id,
+        routine.bedtime_routine_id,
+        last.map(|x| x.checkpoint_id),
+        tasks.len() as i64,
+        finished_count,
+    )
+    .await?;
+
+    Ok(Some(snapshot))
+}
+
+fn bucket<'a>(nights: impl Iterator<Item = &'a RoutineNight>) -> RoutineBucket {
+    let mut night_count = 0;
+    let mut slept = vec![];
+    for night in nights {
+        night_count += 1;
+        if let Some(wake_time) = night.wake_time {
+            slept.push(wake_time - night.sleep_time);
+        }
+    }
+    RoutineBucket {
+        night_count,
+        average_sleep_millis: match slept.len() {
+            0 => None,
+            n => Some(slept.iter().sum::<i64>() / n as i64),
+        },
+    }
+}
+
+// how routine completion lines up with how long the user slept, night by night
+pub async fn get_routine_stats(
+    con: &mut impl GenericClient,
+    user_id: i64,
+    props: &request::BedtimeRoutineStatsProps,
+) -> Result<RoutineStats, AppError> {
+    let nights = bedtime_routine_snapshot_service::get_nights_by_creator_user_id(
+        con,
+        user_id,
+        props.min_creation_time,
+        props.max_creation_time,
+    )
+    .await
+    .map_err(handlers::report_postgres_err)?;
+
+    let complete = bucket(
+        nights
+            .iter()
+            .filter(|x| x.snapshot.finished_count == x.snapshot.task_count),
+    );
+    let partial = bucket(nights.iter().filter(|x| {
+        x.snapshot.finished_count > 0 && x.snapshot.finished_count < x.snapshot.task_count
+    }));
+    let skipped = bucket(nights.iter().filter(|x| x.snapshot.finished_count == 0));
+
+    Ok(RoutineStats {
+        nights,
+        complete,
+        partial,
+        skipped,
+    })
+}
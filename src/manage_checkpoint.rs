@@ -0,0 +1,610 @@
+use std::collections::{HashMap, HashSet};
+
+use tokio_postgres::GenericClient;
+
+use crate::{
+    checkpoint_service,
+    db_types::{Checkpoint, FinishedTask, LiveTask},
+    finished_task_service,
+    handlers::{self, AppError},
+    live_task_service, request,
+};
+
+// how many checkpoints are listed at once, unless the client asks for fewer
+const CHECKPOINT_PAGE_SIZE: i64 = 20;
+const MAX_CHECKPOINT_PAGE_SIZE: i64 = 100;
+
+// a checkpoint is saved, and diffed, all at once, so it has to stay reasonably small
+pub(crate) const MAX_CHECKPOINT_TASKS: usize = 2000;
+pub(crate) const MAX_TASK_ID_CHARS: usize = 128;
+pub(crate) const MAX_TASK_VALUE_CHARS: usize = 2000;
+
+// a task as it stood in one checkpoint, whether it was live or finished
+#[derive(Clone, Debug)]
+pub struct TaskState {
+    pub task_id: String,
+    pub position: i64,
+    pub value: String,
+    // none while the task is live
+    pub status: Option<i64>,
+}
+
+impl From<LiveTask> for TaskState {
+    fn from(x: LiveTask) -> TaskState {
+        TaskState {
+            task_id: x.live_task_id,
+            position: x.position,
+            value: x.value,
+            status: None,
+        }
+    }
+}
+
+impl From<FinishedTask> for TaskState {
+    fn from(x: FinishedTask) -> TaskState {
+        TaskState {
+            task_id: x.finished_task_id,
+            position: x.position,
+            value: x.value,
+            status: Some(x.status),
+        }
+    }
+}
+
+// a live task that moved relative to the others
+#[derive(Clone, Debug)]
+pub struct TaskMove {
+    pub task_id: String,
+    pub from_position: i64,
+    pub to_position: i64,
+}
+
+// a task whose value, or status once finished, changed
+#[derive(Clone, Debug)]
+pub struct TaskEdit {
+    pub from: TaskState,
+    pub to: TaskState,
+}
+
+// what changed from one checkpoint to another
+#[derive(Clone, Debug)]
+pub struct CheckpointDiff {
+    pub from_checkpoint_id: i64,
+    pub to_checkpoint_id: i64,
+    pub added: Vec<TaskState>,
+    pub removed: Vec<TaskState>,
+    // went from live to finished, as they are afterwards
+    pub completed: Vec<TaskState>,
+    // went from finished back to live, as they are afterwards
+    pub reopened: Vec<TaskState>,
+    pub reordered: Vec<TaskMove>,
+    pub edited: Vec<TaskEdit>,
+}
+
+// one checkpoint a task appeared in
+#[derive(Clone, Debug)]
+pub struct TaskHistoryEntry {
+    pub checkpoint_id: i64,
+    pub creation_time: i64,
+    pub state: TaskState,
+}
+
+// a task followed across all of a user's checkpoints
+#[derive(Clone, Debug)]
+pub struct TaskHistory {
+    pub task_id: String,
+    pub entries: Vec<TaskHistoryEntry>,
+    // when the task last became finished, if it still is in the latest checkpoint it appears in
+    pub finished_time: Option<i64>,
+    // how long it sat from first showing up until then
+    pub time_to_finish_millis: Option<i64>,
+}
+
+// every task needs an id, and a task can only be in one place on the list.
+// there's a cap on how many tasks there are and how long each one is.
+fn check_tasks(props: &request::CheckpointNewProps) -> Result<(), AppError> {
+    if props.live_tasks.len() + props.finished_tasks.len() > MAX_CHECKPOINT_TASKS {
+        return Err(AppError::BadRequest);
+    }
+
+    let mut seen = HashSet::new();
+    let tasks = props
+        .live_tasks
+        .iter()
+        .map(|x| (&x.live_task_id, &x.value))
+        .chain(
+            props
+                .finished_tasks
+                .iter()
+                .map(|x| (&x.finished_task_id, &x.value)),
+        );
+    for (id, value) in tasks {
+        if id.is_empty()
+            || id.chars().count() > MAX_TASK_ID_CHARS
+            || value.chars().count() > MAX_TASK_VALUE_CHARS
+            || !seen.insert(id)
+        {
+            return Err(AppError::BadRequest);
+        }
+    }
+    Ok(())
+}
+
+// saves the whole task list as a new checkpoint. tasks keep the order they were given in.
+pub async fn save_checkpoint(
+    con: &mut tokio_postgres::Client,
+    creator_user_id: i64,
+    props: &request::CheckpointNewProps,
+) -> Result<(Checkpoint, Vec<LiveTask>, Vec<FinishedTask>), AppError> {
+    check_tasks(props)?;
+
+    let mut tx = con
+        .transaction()
+        .await
+        .map_err(handlers::report_postgres_err)?;
+
+    let checkpoint = checkpoint_service::add(&mut tx, creator_user_id)
+        .await
+        .map_err(handlers::report_postgres_err)?;
+
+    let mut live_tasks = vec![];
+    for (position, task) in props.live_tasks.iter().enumerate() {
+        let live_task = live_task_service::add(
+            &mut tx,
+            checkpoint.checkpoint_id,
+            task.live_task_id.clone(),
+            position as i64,
+            task.value.clone(),
+        )
+        .await
+        .map_err(handlers::report_postgres_err)?;
+        live_tasks.push(live_task);
+    }
+
+    let mut finished_tasks = vec![];
+    for (position, task) in props.finished_tasks.iter().enumerate() {
+        let finished_task = finished_task_service::add(
+            &mut tx,
+            checkpoint.checkpoint_id,
+            task.finished_task_id.clone(),
+            position as i64,
+            task.value.clone(),
+            task.status,
+        )
+        .await
+        .map_err(handlers::report_postgres_err)?;
+        finished_tasks.push(finished_task);
+    }
+
+    tx.commit().await.map_err(handlers::report_postgres_err)?;
+    Ok((checkpoint, live_tasks, finished_tasks))
+}
+
+// a checkpoint only its creator may see
+pub async fn get_checkpoint(
+    con: &mut impl GenericClient,
+    user_id: i64,
+    checkpoint_id: i64,
+) -> Result<Checkpoint, AppError> {
+    let checkpoint = checkpoint_service::get_by_checkpoint_id(con, checkpoint_id)
+        .await
+        .map_err(handlers::report_postgres_err)?
+        .ok_or(AppError::NotFound)?;
+
+    if checkpoint.creator_user_id != user_id {
+        return Err(AppError::NotFound);
+    }
+    Ok(checkpoint)
+}
+
+// the checkpoint's tasks, each list in order
+pub async fn get_tasks(
+    con: &mut impl GenericClient,
+    checkpoint_id: i64,
+) -> Result<(Vec<LiveTask>, Vec<FinishedTask>), AppError> {
+    let live_tasks = live_task_service::get_by_checkpoint_id(con, checkpoint_id)
+        .await
+        .map_err(handlers::report_postgres_err)?;
+    let finished_tasks = finished_task_service::get_by_checkpoint_id(con, checkpoint_id)
+        .await
+        .map_err(handlers::report_postgres_err)?;
+    Ok((live_tasks, finished_tasks))
+}
+
+// the user's checkpoints, newest first
+pub async fn list_checkpoints(
+    con: &mut impl GenericClient,
+    user_id: i64,
+    props: &request::CheckpointListProps,
+) -> Result<Vec<Checkpoint>, AppError> {
+    let limit = props
+        .limit
+        .unwrap_or(CHECKPOINT_PAGE_SIZE)
+        .clamp(1, MAX_CHECKPOINT_PAGE_SIZE);
+
+    checkpoint_service::get_by_creator_user_id(
+        con,
+        user_id,
+        props.min_creation_time,
+        props.max_creation_time,
+        limit,
+    )
+    .await
+    .map_err(handlers::report_postgres_err)
+}
+
+// the ids that keep their relative order from one order to the other, as many as possible.
+// whatever is left over is what moved. both orders hold the same ids, each only once,
+// so this is the longest increasing run of positions in b, taken in the order of a.
+fn keep_order<'a>(a: &[&'a str], b: &[&'a str]) -> HashSet<&'a str> {
+    let b_positions: HashMap<&str, usize> = b.iter().enumerate().map(|(i, x)| (*x, i)).collect();
+    let positions: Vec<usize> = a
+        .iter()
+        .filter_map(|x| b_positions.get(x).copied())
+        .collect();
+
+    // tails[k] is where the increasing run of length k + 1 with the smallest last position ends
+    let mut tails: Vec<usize> = vec![];
+    // the element before each one in the longest run ending at it
+    let mut previous: Vec<Option<usize>> = vec![None; positions.len()];
+    for (i, position) in positions.iter().enumerate() {
+        let k = tails.partition_point(|t| positions[*t] < *position);
+        previous[i] = k.checked_sub(1).map(|k| tails[k]);
+        if k == tails.len() {
+            tails.push(i);
+        } else {
+            tails[k] = i;
+        }
+    }
+
+    let mut kept = HashSet::new();
+    let mut next = tails.last().copied();
+    while let Some(i) = next {
+        kept.insert(b[positions[i]]);
+        next = previous[i];
+    }
+    kept
+}
+
+fn diff_tasks(
+    from_checkpoint_id: i64,
+    to_checkpoint_id: i64,
+    from: (Vec<LiveTask>, Vec<FinishedTask>),
+    to: (Vec<LiveTask>, Vec<FinishedTask>),
+) -> CheckpointDiff {
+    let from: Vec<TaskState> = from
+        .0
+        .into_iter()
+        .map(TaskState::from)
+        .chain(from.1.into_iter().map(TaskState::from))
+        .collect();
+    let to: Vec<TaskState> =
+        to.0.into_iter()
+            .map(TaskState::from)
+            .chain(to.1.into_iter().map(TaskState::from))
+            .collect();
+
+    let from_by_id: HashMap<&str, &TaskState> =
+        from.iter().map(|x| (x.task_id.as_str(), x)).collect();
+    let to_by_id: HashMap<&str, &TaskState> = to.iter().map(|x| (x.task_id.as_str(), x)).collect();
+
+    let removed = from
+        .iter()
+        .filter(|x| !to_by_id.contains_key(x.task_id.as_str()))
+        .cloned()
+        .collect();
+
+    let mut added = vec![];
+    let mut completed = vec![];
+    let mut reopened = vec![];
+    let mut edited = vec![];
+    for t in to.iter() {
+        let f = match from_by_id.get(t.task_id.as_str()) {
+            Some(f) => f,
+            None => {
+                added.push(t.clone());
+                continue;
+            }
+        };
+        match (f.status, t.status) {
+            (None, Some(_)) => completed.push(t.clone()),
+            (Some(_), None) => reopened.push(t.clone()),
+            _ => {}
+        }
+        let status_changed = f.status.is_some() && t.status.is_some() && f.status != t.status;
+        if f.value != t.value || status_changed {
+            edited.push(TaskEdit {
+                from: (*f).clone(),
+                to: t.clone(),
+            });
+        }
+    }
+
+    // only live tasks that are live on both sides can be reordered
+    let is_live_in = |by_id: &HashMap<&str, &TaskState>, id: &str| {
+        by_id.get(id).is_some_and(|x| x.status.is_none())
+    };
+    let from_order: Vec<&str> = from
+        .iter()
+        .filter(|x| x.status.is_none() && is_live_in(&to_by_id, &x.task_id))
+        .map(|x| x.task_id.as_str())
+        .collect();
+    let to_order: Vec<&str> = to
+        .iter()
+        .filter(|x| x.status.is_none() && is_live_in(&from_by_id, &x.task_id))
+        .map(|x| x.task_id.as_str())
+        .collect();
+    let kept = keep_order(&from_order, &to_order);
+    let reordered = to_order
+        .iter()
+        .filter(|x| !kept.contains(*x))
+        .map(|x| TaskMove {
+            task_id: x.to_string(),
+            from_position: from_by_id[x].position,
+            to_position: to_by_id[x].position,
+        })
+        .collect();
+
+    CheckpointDiff {
+        from_checkpoint_id,
+        to_checkpoint_id,
+        added,
+        removed,
+        completed,
+        reopened,
+        reordered,
+        edited,
+    }
+}
+
+// what changed between two of the user's checkpoints
+pub async fn diff_checkpoints(
+    con: &mut impl GenericClient,
+    user_id: i64,
+    from_checkpoint_id: i64,
+    to_checkpoint_id: i64,
+) -> Result<CheckpointDiff, AppError> {
+    let from = get_checkpoint(con, user_id, from_checkpoint_id).await?;
+    let to = get_checkpoint(con, user_id, to_checkpoint_id).await?;
+
+    let from_tasks = get_tasks(con, from.checkpoint_id).await?;
+    let to_tasks = get_tasks(con, to.checkpoint_id).await?;
+
+    Ok(diff_tasks(
+        from.checkpoint_id,
+        to.checkpoint_id,
+        from_tasks,
+        to_tasks,
+    ))
+}
+
+// follows a task through every one of the user's checkpoints, oldest first
+pub async fn get_task_history(
+    con: &mut impl GenericClient,
+    user_id: i64,
+    task_id: String,
+) -> Result<TaskHistory, AppError> {
+    let live_tasks =
+        live_task_service::get_by_creator_user_id_and_live_task_id(con, user_id, task_id.clone())
+            .await
+            .map_err(handlers::report_postgres_err)?;
+    let finished_tasks = finished_task_service::get_by_creator_user_id_and_finished_task_id(
+        con,
+        user_id,
+        task_id.clone(),
+    )
+    .await
+    .map_err(handlers::report_postgres_err)?;
+
+    let mut states: Vec<(i64, TaskState)> = live_tasks
+        .into_iter()
+        .map(|x| (x.checkpoint_id, x.into()))
+        .chain(
+            finished_tasks
+                .into_iter()
+                .map(|x| (x.checkpoint_id, x.into())),
+        )
+        .collect();
+    states.sort_by_key(|(checkpoint_id, _)| *checkpoint_id);
+
+    let checkpoint_ids: Vec<i64> = states.iter().map(|(id, _)| *id).collect();
+    let creation_times: HashMap<i64, i64> =
+        checkpoint_service::get_by_checkpoint_ids(con, &checkpoint_ids)
+            .await
+            .map_err(handlers::report_postgres_err)?
+            .into_iter()
+            .map(|x| (x.checkpoint_id, x.creation_time))
+            .collect();
+
+    // a checkpoint may have been purged since its tasks were read
+    let entries: Vec<TaskHistoryEntry> = states
+        .into_iter()
+        .map(|(checkpoint_id, state)| {
+            Ok(TaskHistoryEntry {
+                checkpoint_id,
+                creation_time: *creation_times
+                    .get(&checkpoint_id)
+                    .ok_or(AppError::NotFound)?,
+                state,
+            })
+        })
+        .collect::<Result<_, AppError>>()?;
+
+    // the run of finished entries at the end, if there is one, starts when it was finished
+    let finished_time = entries
+        .iter()
+        .rev()
+        .take_while(|x| x.state.status.is_some())
+        .last()
+        .map(|x| x.creation_time);
+    let time_to_finish_millis = match (entries.first(), finished_time) {
+        (Some(first), Some(finished_time)) => Some(finished_time - first.creation_time),
+        _ => None,
+    };
+
+    Ok(TaskHistory {
+        task_id,
+        entries,
+        finished_time,
+        time_to_finish_millis,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn live(id: &str, position: i64, value: &str) -> LiveTask {
+        LiveTask {
+            checkpoint_id: 0,
+            live_task_id: id.to_string(),
+            position,
+            value: value.to_string(),
+        }
+    }
+
+    fn finished(id: &str, position: i64, value: &str, status: i64) -> FinishedTask {
+        FinishedTask {
+            checkpoint_id: 0,
+            finished_task_id: id.to_string(),
+            position,
+            value: value.to_string(),
+            status,
+        }
+    }
+
+    // the live tasks, in order
+    fn lives(ids: &[&str]) -> Vec<LiveTask> {
+        ids.iter()
+            .enumerate()
+            .map(|(i, id)| live(id, i as i64, id))
+            .collect()
+    }
+
+    fn ids(tasks: &[TaskState]) -> Vec<&str> {
+        tasks.iter().map(|x| x.task_id.as_str()).collect()
+    }
+
+    #[test]
+    fn nothing_changed() {
+        let diff = diff_tasks(
+            1,
+            2,
+            (lives(&["a", "b"]), vec![]),
+            (lives(&["a", "b"]), vec![]),
+        );
+        assert!(diff.added.is_empty());
+        assert!(diff.removed.is_empty());
+        assert!(diff.completed.is_empty());
+        assert!(diff.reopened.is_empty());
+        assert!(diff.reordered.is_empty());
+        assert!(diff.edited.is_empty());
+    }
+
+    #[test]
+    fn finds_added_and_removed() {
+        let diff = diff_tasks(
+            1,
+            2,
+            (lives(&["a", "b", "c"]), vec![]),
+            (lives(&["a", "c", "d"]), vec![]),
+        );
+        assert_eq!(ids(&diff.added), vec!["d"]);
+        assert_eq!(ids(&diff.removed), vec!["b"]);
+        // c only shifted up because b went away
+        assert!(diff.reordered.is_empty());
+    }
+
+    #[test]
+    fn finds_the_fewest_moves() {
+        let diff = diff_tasks(
+            1,
+            2,
+            (lives(&["a", "b", "c", "d", "e"]), vec![]),
+            (lives(&["e", "a", "b", "c", "d"]), vec![]),
+        );
+        assert_eq!(diff.reordered.len(), 1);
+        let moved = &diff.reordered[0];
+        assert_eq!(moved.task_id, "e");
+        assert_eq!(moved.from_position, 4);
+        assert_eq!(moved.to_position, 0);
+    }
+
+    #[test]
+    fn finds_swaps() {
+        let diff = diff_tasks(
+            1,
+            2,
+            (lives(&["a", "b", "c", "d"]), vec![]),
+            (lives(&["b", "a", "d", "c"]), vec![]),
+        );
+        // one of each swapped pair has to have moved
+        assert_eq!(diff.reordered.len(), 2);
+        let moved: Vec<&str> = diff.reordered.iter().map(|x| x.task_id.as_str()).collect();
+        assert!(moved.contains(&"a") != moved.contains(&"b"));
+        assert!(moved.contains(&"c") != moved.contains(&"d"));
+    }
+
+    #[test]
+    fn finds_completed_and_reopened() {
+        let diff = diff_tasks(
+            1,
+            2,
+            (lives(&["a", "b"]), vec![finished("c", 0, "c", 1)]),
+            (
+                vec![live("b", 0, "b"), live("c", 1, "c")],
+                vec![finished("a", 0, "a", 1)],
+            ),
+        );
+        assert_eq!(ids(&diff.completed), vec!["a"]);
+        assert_eq!(diff.completed[0].status, Some(1));
+        assert_eq!(ids(&diff.reopened), vec!["c"]);
+        assert!(diff.edited.is_empty());
+        // tasks that finished or reopened don't count as moved
+        assert!(diff.reordered.is_empty());
+    }
+
+    #[test]
+    fn finds_edits() {
+        let diff = diff_tasks(
+            1,
+            2,
+            (
+                vec![live("a", 0, "buy milk"), live("b", 1, "walk dog")],
+                vec![
+                    finished("c", 0, "call mom", 1),
+                    finished("d", 1, "pay rent", 1),
+                ],
+            ),
+            (
+                vec![live("a", 0, "buy oat milk"), live("b", 1, "walk dog")],
+                vec![
+                    finished("c", 0, "call mom", 2),
+                    finished("d", 1, "pay rent", 1),
+                ],
+            ),
+        );
+        let edited: Vec<(&str, &str, Option<i64>)> = diff
+            .edited
+            .iter()
+            .map(|x| (x.to.task_id.as_str(), x.from.value.as_str(), x.from.status))
+            .collect();
+        assert_eq!(
+            edited,
+            vec![("a", "buy milk", None), ("c", "call mom", Some(1))]
+        );
+        assert_eq!(diff.edited[0].to.value, "buy oat milk");
+        assert_eq!(diff.edited[1].to.status, Some(2));
+    }
+
+    #[test]
+    fn diffs_large_checkpoints() {
+        // reversing the whole list keeps only one task in place
+        let from: Vec<String> = (0..MAX_CHECKPOINT_TASKS).map(|i| i.to_string()).collect();
+        let to: Vec<String> = from.iter().rev().cloned().collect();
+        let from: Vec<&str> = from.iter().map(|x| x.as_str()).collect();
+        let to: Vec<&str> = to.iter().map(|x| x.as_str()).collect();
+        let diff = diff_tasks(1, 2, (lives(&from), vec![]), (lives(&to), vec![]));
+        assert_eq!(diff.reordered.len(), MAX_CHECKPOINT_TASKS - 1);
+    }
+}
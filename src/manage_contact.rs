@@ -0,0 +1,147 @@
+use tokio_postgres::GenericClient;
+
+use crate::{
+    contact_block_service, contact_request_service, contact_service,
+    db_types::{ContactBlock, ContactRequest},
+    handlers::{self, AppError},
+    household_membership_service, utils,
+};
+
+// users may message their contacts and the people they share a household with,
+// unless either of them has blocked the other
+pub async fn check_can_message(
+    con: &mut impl GenericClient,
+    creator_user_id: i64,
+    target_user_id: i64,
+) -> Result<(), AppError> {
+    if contact_block_service::is_blocked_between(con, creator_user_id, target_user_id)
+        .await
+        .map_err(handlers::report_postgres_err)?
+    {
+        return Err(AppError::Forbidden);
+    }
+
+    // notes to self are always fine
+    if creator_user_id == target_user_id {
+        return Ok(());
+    }
+
+    if contact_service::get_by_user_id_and_contact_user_id(con, creator_user_id, target_user_id)
+        .await
+        .map_err(handlers::report_postgres_err)?
+        .is_some()
+    {
+        return Ok(());
+    }
+
+    if household_membership_service::shares_household(con, creator_user_id, target_user_id)
+        .await
+        .map_err(handlers::report_postgres_err)?
+    {
+        return Ok(());
+    }
+
+    Err(AppError::Forbidden)
+}
+
+// asks the target to pair.
+// if the target already asked us, that request is accepted instead.
+pub async fn request_contact(
+    con: &mut impl GenericClient,
+    creator_user_id: i64,
+    target_user_id: i64,
+) -> Result<ContactRequest, AppError> {
+    if creator_user_id == target_user_id {
+        return Err(AppError::BadRequest);
+    }
+
+    if contact_block_service::is_blocked_between(con, creator_user_id, target_user_id)
+        .await
+        .map_err(handlers::report_postgres_err)?
+    {
+        return Err(AppError::Forbidden);
+    }
+
+    // already paired
+    if contact_service::get_by_user_id_and_contact_user_id(con, creator_user_id, target_user_id)
+        .await
+        .map_err(handlers::report_postgres_err)?
+        .is_some()
+    {
+        return Err(AppError::BadRequest);
+    }
+
+    if let Some(reverse) =
+        contact_request_service::get_pending(con, target_user_id, creator_user_id)
+            .await
+            .map_err(handlers::report_postgres_err)?
+    {
+        return respond(con, creator_user_id, reverse.contact_request_id, true).await;
+    }
+
+    // asking twice doesn't pile up requests
+    if let Some(pending) =
+        contact_request_service::get_pending(con, creator_user_id, target_user_id)
+            .await
+            .map_err(handlers::report_postgres_err)?
+    {
+        return Ok(pending);
+    }
+
+    contact_request_service::add(con, creator_user_id, target_user_id)
+        .await
+        .map_err(handlers::report_postgres_err)
+}
+
+// only the user who was asked may answer, and only once
+pub async fn respond(
+    con: &mut impl GenericClient,
+    user_id: i64,
+    contact_request_id: i64,
+    accepted: bool,
+) -> Result<ContactRequest, AppError> {
+    let request = contact_request_service::get_by_contact_request_id(con, contact_request_id)
+        .await
+        .map_err(handlers::report_postgres_err)?
+        .ok_or(AppError::NotFound)?;
+
+    if request.target_user_id != user_id {
+        return Err(AppError::Forbidden);
+    }
+
+    contact_request_service::respond(
+        con,
+        contact_request_id,
+        accepted,
+        utils::current_time_millis(),
+    )
+    .await
+    .map_err(handlers::report_postgres_err)?
+    .ok_or(AppError::BadRequest)
+}
+
+// blocking also turns down anything the blocked user is still asking for
+pub async fn set_block(
+    con: &mut impl GenericClient,
+    creator_user_id: i64,
+    target_user_id: i64,
+    active: bool,
+) -> Result<ContactBlock, AppError> {
+    if creator_user_id == target_user_id {
+        return Err(AppError::BadRequest);
+    }
+
+    if active {
+        if let Some(pending) =
+            contact_request_service::get_pending(con, target_user_id, creator_user_id)
+                .await
+                .map_err(handlers::report_postgres_err)?
+        {
+            respond(con, creator_user_id, pending.contact_request_id, false).await?;
+        }
+    }
+
+    contact_block_service::add(con, creator_user_id, target_user_id, active)
+        .await
+        .map_err(handlers::report_postgres_err)
+}
@@ -0,0 +1,290 @@
+use tokio_postgres::GenericClient;
+
+use crate::{
+    db_types::{Household, HouseholdInvite, HouseholdMembership, HouseholdRole},
+    handlers::{self, AppError},
+    household_invite_service, household_membership_service, household_service, utils,
+};
+
+// how long an invite code can be used for
+const INVITE_DURATION_MILLIS: i64 = 7 * 24 * 60 * 60 * 1000;
+
+// the user's membership, if they currently belong to the household
+pub async fn require_member(
+    con: &mut impl GenericClient,
+    household_id: i64,
+    user_id: i64,
+) -> Result<HouseholdMembership, AppError> {
+    household_service::get_by_household_id(con, household_id)
+        .await
+        .map_err(handlers::report_postgres_err)?
+        .ok_or(AppError::NotFound)?;
+
+    match household_membership_service::get_recent(con, household_id, user_id)
+        .await
+        .map_err(handlers::report_postgres_err)?
+    {
+        Some(m) if m.active => Ok(m),
+        _ => Err(AppError::Forbidden),
+    }
+}
+
+pub async fn require_admin(
+    con: &mut impl GenericClient,
+    household_id: i64,
+    user_id: i64,
+) -> Result<HouseholdMembership, AppError> {
+    let membership = require_member(con, household_id, user_id).await?;
+    if membership.role != HouseholdRole::Admin {
+        return Err(AppError::Forbidden);
+    }
+    Ok(membership)
+}
+
+// a household that still has members must always have an admin to manage it.
+// changed is the new state of one of the members.
+async fn check_admin_remains(
+    con: &mut impl GenericClient,
+    changed: &HouseholdMembership,
+) -> Result<(), AppError> {
+    let members =
+        household_membership_service::get_active_by_household_id(con, changed.household_id)
+            .await
+            .map_err(handlers::report_postgres_err)?;
+
+    let remaining: Vec<&HouseholdMembership> = members
+        .iter()
+        .filter(|m| m.user_id != changed.user_id)
+        .chain(Some(changed).filter(|c| c.active))
+        .collect();
+
+    if !remaining.is_empty() && !remaining.iter().any(|m| m.role == HouseholdRole::Admin) {
+        return Err(AppError::BadRequest);
+    }
+    Ok(())
+}
+
+// the creator of a household becomes its first admin
+pub async fn create_household(
+    con: &mut tokio_postgres::Client,
+    creator_user_id: i64,
+    name: String,
+) -> Result<Household, AppError> {
+    if name.trim().is_empty() {
+        return Err(AppError::BadRequest);
+    }
+
+    let mut tx = con
+        .transaction()
+        .await
+        .map_err(handlers::report_postgres_err)?;
+
+    let household = household_service::add(&mut tx, creator_user_id, name)
+        .await
+        .map_err(handlers::report_postgres_err)?;
+
+    household_membership_service::add(
+        &mut tx,
+        creator_user_id,
+        household.household_id,
+        creator_user_id,
+        HouseholdRole::Admin,
+        true,
+    )
+    .await
+    .map_err(handlers::report_postgres_err)?;
+
+    tx.commit().await.map_err(handlers::report_postgres_err)?;
+    Ok(household)
+}
+
+// only admins may hand out invites
+pub async fn create_invite(
+    con: &mut tokio_postgres::Client,
+    creator_user_id: i64,
+    household_id: i64,
+    role: HouseholdRole,
+) -> Result<HouseholdInvite, AppError> {
+    require_admin(con, household_id, creator_user_id).await?;
+
+    household_invite_service::add(
+        con,
+        creator_user_id,
+        household_id,
+        role,
+        utils::current_time_millis() + INVITE_DURATION_MILLIS,
+    )
+    .await
+    .map_err(handlers::report_postgres_err)
+}
+
+pub async fn accept_invite(
+    con: &mut tokio_postgres::Client,
+    user_id: i64,
+    household_invite_code: String,
+) -> Result<HouseholdMembership, AppError> {
+    let mut tx = con
+        .transaction()
+        .await
+        .map_err(handlers::report_postgres_err)?;
+
+    let invite =
+        household_invite_service::get_by_household_invite_code(&mut tx, &household_invite_code)
+            .await
+            .map_err(handlers::report_postgres_err)?
+            .ok_or(AppError::NotFound)?;
+
+    household_service::lock_by_household_id(&mut tx, invite.household_id)
+        .await
+        .map_err(handlers::report_postgres_err)?
+        .ok_or(AppError::NotFound)?;
+
+    // don't burn the code on somebody who is already in
+    if let Some(m) = household_membership_service::get_recent(&mut tx, invite.household_id, user_id)
+        .await
+        .map_err(handlers::report_postgres_err)?
+    {
+        if m.active {
+            return Err(AppError::BadRequest);
+        }
+    }
+
+    // fails if the code was already used or has expired
+    let invite = household_invite_service::accept(
+        &mut tx,
+        &household_invite_code,
+        user_id,
+        utils::current_time_millis(),
+    )
+    .await
+    .map_err(handlers::report_postgres_err)?
+    .ok_or(AppError::BadRequest)?;
+
+    let membership = household_membership_service::add(
+        &mut tx,
+        invite.creator_user_id,
+        invite.household_id,
+        user_id,
+        invite.role,
+        true,
+    )
+    .await
+    .map_err(handlers::report_postgres_err)?;
+
+    tx.commit().await.map_err(handlers::report_postgres_err)?;
+    Ok(membership)
+}
+
+// lets an admin change the role of a member, or remove them from the household
+pub async fn update_membership(
+    con: &mut tokio_postgres::Client,
+    creator_user_id: i64,
+    household_id: i64,
+    user_id: i64,
+    role: HouseholdRole,
+    active: bool,
+) -> Result<HouseholdMembership, AppError> {
+    let mut tx = con
+        .transaction()
+        .await
+        .map_err(handlers::report_postgres_err)?;
+
+    household_service::lock_by_household_id(&mut tx, household_id)
+        .await
+        .map_err(handlers::report_postgres_err)?
+        .ok_or(AppError::NotFound)?;
+
+    require_admin(&mut tx, household_id, creator_user_id).await?;
+
+    // people only join by accepting an invite
+    match household_membership_service::get_recent(&mut tx, household_id, user_id)
+        .await
+        .map_err(handlers::report_postgres_err)?
+    {
+        Some(m) if m.active => {}
+        _ => return Err(AppError::NotFound),
+    }
+
+    let changed = HouseholdMembership {
+        household_membership_id: 0,
+        creation_time: 0,
+        creator_user_id,
+        household_id,
+        user_id,
+        role,
+        active,
+    };
+    check_admin_remains(&mut tx, &changed).await?;
+
+    let membership = household_membership_service::add(
+        &mut tx,
+        creator_user_id,
+        household_id,
+        user_id,
+        role,
+        active,
+    )
+    .await
+    .map_err(handlers::report_postgres_err)?;
+
+    tx.commit().await.map_err(handlers::report_postgres_err)?;
+    Ok(membership)
+}
+
+// the last admin has to hand over to someone else before leaving
+pub async fn leave(
+    con: &mut tokio_postgres::Client,
+    user_id: i64,
+    household_id: i64,
+) -> Result<HouseholdMembership, AppError> {
+    let mut tx = con
+        .transaction()
+        .await
+        .map_err(handlers::report_postgres_err)?;
+
+    household_service::lock_by_household_id(&mut tx, household_id)
+        .await
+        .map_err(handlers::report_postgres_err)?
+        .ok_or(AppError::NotFound)?;
+
+    let current = require_member(&mut tx, household_id, user_id).await?;
+
+    let changed = HouseholdMembership {
+        active: false,
+        ..current
+    };
+    check_admin_remains(&mut tx, &changed).await?;
+
+    let membership = household_membership_service::add(
+        &mut tx,
+        user_id,
+        household_id,
+        user_id,
+        changed.role,
+        false,
+    )
+    .await
+    .map_err(handlers::report_postgres_err)?;
+
+    tx.commit().await.map_err(handlers::report_postgres_err)?;
+    Ok(membership)
+}
+
+// everyone a member of the household can reach by messaging the household as a whole
+pub async fn get_other_member_ids(
+    con: &mut impl GenericClient,
+    household_id: i64,
+    user_id: i64,
+) -> Result<Vec<i64>, AppError> {
+    require_member(con, household_id, user_id).await?;
+
+    let members = household_membership_service::get_active_by_household_id(con, household_id)
+        .await
+        .map_err(handlers::report_postgres_err)?;
+
+    Ok(members
+        .into_iter()
+        .map(|m| m.user_id)
+        .filter(|id| *id != user_id)
+        .collect())
+}
@@ -0,0 +1,308 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tokio_postgres::GenericClient;
+
+use crate::{
+    blob_store::BlobStore,
+    db_types::Job,
+    handlers::{self, AppError},
+    job_service, manage_audio_blob, manage_retention, manage_transcript,
+    transcriber::Transcriber,
+    utils,
+};
+
+/// How long an idle worker waits before looking for new jobs.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+// a job that runs longer than this is abandoned and counts as a failed attempt
+const JOB_TIMEOUT: Duration = Duration::from_secs(4 * 60);
+
+// how long a claimed job is hidden from other workers.
+// this outlasts JOB_TIMEOUT, so a job only runs again if its worker died.
+const VISIBILITY_TIMEOUT_MILLIS: i64 = 5 * 60 * 1000;
+
+// failed jobs are retried after RETRY_BASE_MILLIS, doubling with every attempt up to RETRY_MAX_MILLIS
+const RETRY_BASE_MILLIS: i64 = 10 * 1000;
+const RETRY_MAX_MILLIS: i64 = 60 * 60 * 1000;
+
+const DEFAULT_MAX_ATTEMPTS: i64 = 5;
+
+// finished jobs are kept this long so failures can still be looked into
+const FINISHED_JOB_RETENTION_MILLIS: i64 = 7 * 24 * 60 * 60 * 1000;
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+// what the workers need to do their jobs
+#[derive(Clone)]
+pub struct JobContext {
+    pub pool: deadpool_postgres::Pool,
+    pub blob_store: BlobStore,
+    // none if transcription is turned off
+    pub transcriber: Option<Transcriber>,
+}
+
+// the work to be done, stored as json in job.payload
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum JobPayload {
+    CollectGarbage,
+    PurgeExpiredUserMessages,
+    PruneFinishedJobs,
+    #[serde(rename_all = "camelCase")]
+    TranscribeAudio {
+        audio_sha256: String,
+    },
+}
+
+impl JobPayload {
+    fn kind(&self) -> &'static str {
+        match self {
+            JobPayload::CollectGarbage => "COLLECT_GARBAGE",
+            JobPayload::PurgeExpiredUserMessages => "PURGE_EXPIRED_USER_MESSAGES",
+            JobPayload::PruneFinishedJobs => "PRUNE_FINISHED_JOBS",
+            JobPayload::TranscribeAudio { .. } => "TRANSCRIBE_AUDIO",
+        }
+    }
+
+    // only one job with a given key is queued at a time
+    fn unique_key(&self) -> Option<String> {
+        match self {
+            JobPayload::CollectGarbage
+            | JobPayload::PurgeExpiredUserMessages
+            | JobPayload::PruneFinishedJobs => Some(self.kind().to_string()),
+            JobPayload::TranscribeAudio { audio_sha256 } => {
+                Some(format!("{}:{}", self.kind(), audio_sha256))
+            }
+        }
+    }
+
+    // periodic jobs queue up their next run once they finish
+    fn repeat_interval(&self) -> Option<Duration> {
+        match self {
+            JobPayload::CollectGarbage => Some(manage_audio_blob::GARBAGE_COLLECTION_INTERVAL),
+            JobPayload::PurgeExpiredUserMessages => Some(manage_retention::PURGE_INTERVAL),
+            JobPayload::PruneFinishedJobs => Some(PRUNE_INTERVAL),
+            JobPayload::TranscribeAudio { .. } => None,
+        }
+    }
+
+    fn periodic() -> Vec<JobPayload> {
+        vec![
+            JobPayload::CollectGarbage,
+            JobPayload::PurgeExpiredUserMessages,
+            JobPayload::PruneFinishedJobs,
+        ]
+    }
+}
+
+// queues up a job to be run by the next free worker at or after run_at.
+// call this inside the transaction that makes the job necessary, so it is never lost.
+// returns none if the same job is already queued.
+pub async fn enqueue(
+    con: &mut impl GenericClient,
+    payload: &JobPayload,
+    run_at: i64,
+) -> Result<Option<Job>, AppError> {
+    let serialized =
+        serde_json::to_string(payload).map_err(handlers::report_internal_serde_error)?;
+    job_service::add(
+        con,
+        payload.kind().to_string(),
+        serialized,
+        payload.unique_key(),
+        run_at,
+        DEFAULT_MAX_ATTEMPTS,
+    )
+    .await
+    .map_err(handlers::report_postgres_err)
+}
+
+fn retry_delay_millis(attempts: i64) -> i64 {
+    let exponent = (attempts - 1).clamp(0, 20) as u32;
+    (RETRY_BASE_MILLIS * 2i64.pow(exponent)).min(RETRY_MAX_MILLIS)
+}
+
+async fn run(ctx: &JobContext, payload: &JobPayload) -> Result<(), AppError> {
+    match payload {
+        JobPayload::CollectGarbage => {
+            let n = manage_audio_blob::collect_garbage(&ctx.pool, &ctx.blob_store).await?;
+            if n > 0 {
+                log::info!("deleted {} unreferenced audio blobs", n);
+            }
+            Ok(())
+        }
+        JobPayload::PurgeExpiredUserMessages => {
+            let (hidden, purged) = manage_retention::purge_expired(&ctx.pool).await?;
+            if hidden > 0 {
+                log::info!("removed {} expired user messages from inboxes", hidden);
+            }
+            if purged > 0 {
+                log::info!("purged {} expired user messages", purged);
+            }
+            Ok(())
+        }
+        JobPayload::PruneFinishedJobs => {
+            let n = prune_finished(&ctx.pool).await?;
+            if n > 0 {
+                log::info!("deleted {} finished jobs", n);
+            }
+            Ok(())
+        }
+        JobPayload::TranscribeAudio { audio_sha256 } => match &ctx.transcriber {
+            Some(transcriber) => {
+                manage_transcript::transcribe_audio(
+                    &ctx.pool,
+                    &ctx.blob_store,
+                    transcriber,
+                    audio_sha256,
+                )
+                .await
+            }
+            None => {
+                log::error!("can't transcribe audio, no transcriber is configured");
+                Err(AppError::InternalServerError)
+            }
+        },
+    }
+}
+
+// deletes jobs that finished (or were given up on) longer than FINISHED_JOB_RETENTION_MILLIS ago
+async fn prune_finished(pool: &deadpool_postgres::Pool) -> Result<u64, AppError> {
+    let con: &mut tokio_postgres::Client =
+        &mut *pool.get().await.map_err(handlers::report_pool_err)?;
+    let before = utils::current_time_millis() - FINISHED_JOB_RETENTION_MILLIS;
+    job_service::delete_finished_before(con, before)
+        .await
+        .map_err(handlers::report_postgres_err)
+}
+
+// records how the job went, and queues the next run of periodic jobs once this one is done
+async fn finish(
+    con: &mut tokio_postgres::Client,
+    job: &Job,
+    lock_token: String,
+    payload: Option<&JobPayload>,
+    result: Result<(), String>,
+) -> Result<(), AppError> {
+    let now = utils::current_time_millis();
+
+    let mut tx = con
+        .transaction()
+        .await
+        .map_err(handlers::report_postgres_err)?;
+
+    let finished = match result {
+        Ok(()) => job_service::succeed(&mut tx, job.job_id, lock_token, now)
+            .await
+            .map_err(handlers::report_postgres_err)?,
+        Err(error) => {
+            let retry_at = now + retry_delay_millis(job.attempts);
+            let failed = job_service::fail(&mut tx, job.job_id, lock_token, now, retry_at, error)
+                .await
+                .map_err(handlers::report_postgres_err)?;
+            match failed {
+                Some(failed) if failed.finish_time.is_some() => {
+                    log::error!(
+                        "giving up on job {} ({}) after {} attempts: {}",
+                        job.job_id,
+                        job.kind,
+                        failed.attempts,
+                        failed.last_error.unwrap_or_default()
+                    );
+                    true
+                }
+                Some(failed) => {
+                    log::warn!(
+                        "job {} ({}) failed, retrying: {}",
+                        job.job_id,
+                        job.kind,
+                        failed.last_error.unwrap_or_default()
+                    );
+                    false
+                }
+                None => false,
+            }
+        }
+    };
+
+    if let Some(payload) = payload.filter(|_| finished) {
+        if let Some(interval) = payload.repeat_interval() {
+            enqueue(&mut tx, payload, now + interval.as_millis() as i64).await?;
+        }
+    }
+
+    tx.commit().await.map_err(handlers::report_postgres_err)?;
+    Ok(())
+}
+
+// claims and runs a single job. returns false if there was nothing to do.
+async fn work_one(ctx: &JobContext) -> Result<bool, AppError> {
+    let con: &mut tokio_postgres::Client =
+        &mut *ctx.pool.get().await.map_err(handlers::report_pool_err)?;
+
+    let now = utils::current_time_millis();
+    let lock_token = utils::random_string();
+
+    let job = match job_service::claim(
+        con,
+        now,
+        lock_token.clone(),
+        now + VISIBILITY_TIMEOUT_MILLIS,
+    )
+    .await
+    .map_err(handlers::report_postgres_err)?
+    {
+        Some(job) => job,
+        None => return Ok(false),
+    };
+
+    // a worker died while holding this job on its last attempt
+    if job.attempts > job.max_attempts {
+        let payload = serde_json::from_str::<JobPayload>(&job.payload).ok();
+        let result = Err(String::from("ran out of attempts"));
+        finish(con, &job, lock_token, payload.as_ref(), result).await?;
+        return Ok(true);
+    }
+
+    let (payload, result) = match serde_json::from_str::<JobPayload>(&job.payload) {
+        Ok(payload) => {
+            let result = match tokio::time::timeout(JOB_TIMEOUT, run(ctx, &payload)).await {
+                Ok(Ok(())) => Ok(()),
+                Ok(Err(e)) => Err(e.to_string()),
+                Err(_) => Err(String::from("timed out")),
+            };
+            (Some(payload), result)
+        }
+        Err(e) => (None, Err(e.to_string())),
+    };
+
+    finish(con, &job, lock_token, payload.as_ref(), result).await?;
+    Ok(true)
+}
+
+// makes sure every periodic job has a run queued, in case it was never queued or got lost
+async fn schedule_periodic(pool: &deadpool_postgres::Pool) -> Result<(), AppError> {
+    let con: &mut tokio_postgres::Client =
+        &mut *pool.get().await.map_err(handlers::report_pool_err)?;
+    let now = utils::current_time_millis();
+    for payload in JobPayload::periodic() {
+        enqueue(con, &payload, now).await?;
+    }
+    Ok(())
+}
+
+// runs jobs one after another for as long as the process lives
+pub async fn work(ctx: JobContext) {
+    let mut scheduled = false;
+    loop {
+        if !scheduled {
+            scheduled = schedule_periodic(&ctx.pool).await.is_ok();
+        }
+        match work_one(&ctx).await {
+            Ok(true) => {}
+            Ok(false) => tokio::time::sleep(POLL_INTERVAL).await,
+            // errors have already been logged, back off so we don't spin
+            Err(_) => tokio::time::sleep(POLL_INTERVAL).await,
+        }
+    }
+}
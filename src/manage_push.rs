@@ -0,0 +1,272 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use actix_ws::{CloseCode, CloseReason, Message, ProtocolError};
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{broadcast, mpsc};
+use tokio_postgres::AsyncMessage;
+use tokio_stream::wrappers::{BroadcastStream, IntervalStream};
+
+use crate::response::PushEvent;
+
+/// How often heartbeat pings are sent.
+///
+/// Should be half (or less) of the acceptable client timeout.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How long before lack of client response causes a timeout.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
+
+// how many events may queue up for a slow device before it starts missing them
+const PUSH_BUFFER_SIZE: usize = 256;
+
+// the postgres notification channel that carries push events between processes
+static PUSH_CHANNEL: &str = "kthg_push";
+
+// postgres refuses notification payloads of 8000 bytes or more
+const MAX_PUSH_PAYLOAD_BYTES: usize = 7999;
+
+// how long to wait before listening again after losing the connection
+const LISTEN_RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+// what goes over the push channel
+#[derive(Serialize, Deserialize)]
+struct PushNotification {
+    user_id: i64,
+    event: PushEvent,
+}
+
+// hands events to the devices of users who are connected right now.
+// events are published through postgres, so whichever process raises them (a server instance
+// or `kthg worker`), every server instance hears about them and passes them to its own devices.
+#[derive(Clone)]
+pub struct PushHub {
+    // events waiting to be published
+    outbox: mpsc::UnboundedSender<(i64, PushEvent)>,
+    // a channel for each user with a device connected to this process
+    channels: Arc<Mutex<HashMap<i64, broadcast::Sender<PushEvent>>>>,
+}
+
+impl PushHub {
+    // must be called from within the runtime, events are published by a background task
+    pub fn new(pool: deadpool_postgres::Pool) -> PushHub {
+        let (outbox, pending) = mpsc::unbounded_channel();
+        let push_hub = PushHub {
+            outbox,
+            channels: Arc::new(Mutex::new(HashMap::new())),
+        };
+        tokio::spawn(publish(pool, push_hub.clone(), pending));
+        push_hub
+    }
+
+    // it's fine if none of the user's devices are listening
+    pub fn push(&self, user_id: i64, event: PushEvent) {
+        let _ = self.outbox.send((user_id, event));
+    }
+
+    // events for this user, as they reach this process
+    pub fn subscribe(&self, user_id: i64) -> broadcast::Receiver<PushEvent> {
+        let mut channels = self.channels.lock().unwrap();
+        channels
+            .entry(user_id)
+            .or_insert_with(|| broadcast::channel(PUSH_BUFFER_SIZE).0)
+            .subscribe()
+    }
+
+    // hands an event to the user's devices connected to this process
+    fn deliver(&self, user_id: i64, event: PushEvent) {
+        let mut channels = self.channels.lock().unwrap();
+        if let Some(sender) = channels.get(&user_id) {
+            // every device hung up, so stop keeping a channel around for them
+            if sender.send(event).is_err() {
+                channels.remove(&user_id);
+            }
+        }
+    }
+}
+
+// publishes pushed events on the push channel.
+// events that can't be published are at least delivered to the devices of this process.
+async fn publish(
+    pool: deadpool_postgres::Pool,
+    push_hub: PushHub,
+    mut pending: mpsc::UnboundedReceiver<(i64, PushEvent)>,
+) {
+    while let Some((user_id, event)) = pending.recv().await {
+        let notification = PushNotification { user_id, event };
+        let payload = match serde_json::to_string(&notification) {
+            Ok(payload) if payload.len() <= MAX_PUSH_PAYLOAD_BYTES => payload,
+            _ => {
+                log::error!("push event for user {} is too large to publish", user_id);
+                push_hub.deliver(notification.user_id, notification.event);
+                continue;
+            }
+        };
+
+        let result = match pool.get().await {
+            Ok(con) => con
+                .execute("SELECT pg_notify($1, $2)", &[&PUSH_CHANNEL, &payload])
+                .await
+                .map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+
+        if let Err(e) = result {
+            log::error!("couldn't publish push event: {}", e);
+            push_hub.deliver(notification.user_id, notification.event);
+        }
+    }
+}
+
+// passes everything published on the push channel to the devices connected to this process.
+// runs forever, listening again whenever the connection is lost.
+pub async fn listen(config: tokio_postgres::Config, push_hub: PushHub) {
+    loop {
+        if let Err(e) = listen_once(&config, &push_hub).await {
+            log::error!("lost push channel: {}", e);
+        }
+        tokio::time::sleep(LISTEN_RETRY_INTERVAL).await;
+    }
+}
+
+async fn listen_once(
+    config: &tokio_postgres::Config,
+    push_hub: &PushHub,
+) -> Result<(), tokio_postgres::Error> {
+    let (client, mut connection) = config.connect(tokio_postgres::NoTls).await?;
+
+    // notifications arrive on the connection, which has to be polled to make any progress
+    let (notifications, mut received) = mpsc::unbounded_channel();
+    let connection = tokio::spawn(async move {
+        let mut messages = futures_util::stream::poll_fn(|cx| connection.poll_message(cx));
+        while let Some(message) = messages.next().await {
+            match message {
+                Ok(AsyncMessage::Notification(n)) => {
+                    let _ = notifications.send(n.payload().to_string());
+                }
+                Ok(_) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    });
+
+    client
+        .batch_execute(&format!("LISTEN {}", PUSH_CHANNEL))
+        .await?;
+    log::info!("listening for push events");
+
+    while let Some(payload) = received.recv().await {
+        match serde_json::from_str::<PushNotification>(&payload) {
+            Ok(n) => push_hub.deliver(n.user_id, n.event),
+            Err(e) => log::error!("malformed push event: {}", e),
+        }
+    }
+
+    match connection.await {
+        Ok(result) => result,
+        // the connection task panicked
+        Err(_) => Ok(()),
+    }
+}
+
+// forwards the events of a user to one of their devices, as json text frames.
+// initial events go out first, then whichever pushed events are wanted.
+pub async fn push_ws(
+    mut session: actix_ws::Session,
+    msg_stream: actix_ws::MessageStream,
+    receiver: broadcast::Receiver<PushEvent>,
+    initial: Vec<PushEvent>,
+    wanted: fn(&PushEvent) -> bool,
+) {
+    let mut last_heartbeat = Instant::now();
+
+    for event in initial {
+        if let Ok(text) = serde_json::to_string(&event) {
+            let _ = session.text(text).await;
+        }
+    }
+
+    enum TaskUpdateKind {
+        // we need to send a heartbeat
+        NeedToSendHeartbeat,
+        // we received a message from the client
+        ClientMessage(Result<Message, ProtocolError>),
+        // something happened that the user's devices should hear about
+        Push(Result<PushEvent, String>),
+    }
+
+    let heartbeat_stream = IntervalStream::new(tokio::time::interval(HEARTBEAT_INTERVAL))
+        .map(|_| TaskUpdateKind::NeedToSendHeartbeat);
+    let client_message_stream = msg_stream.map(TaskUpdateKind::ClientMessage);
+    let push_stream =
+        BroadcastStream::new(receiver).map(|x| TaskUpdateKind::Push(x.map_err(|e| e.to_string())));
+
+    let mut joint_stream =
+        futures_util::stream_select!(heartbeat_stream, client_message_stream, push_stream,);
+
+    let reason = loop {
+        match joint_stream.next().await.unwrap() {
+            // received message from WebSocket client
+            TaskUpdateKind::ClientMessage(Ok(msg)) => {
+                log::debug!("msg: {msg:?}");
+                match msg {
+                    Message::Continuation(_) | Message::Binary(_) | Message::Text(_) => {
+                        break Some(CloseReason {
+                            code: CloseCode::Unsupported,
+                            description: None,
+                        });
+                    }
+                    Message::Close(_) => break None,
+                    Message::Ping(bytes) => {
+                        last_heartbeat = Instant::now();
+                        let _ = session.pong(&bytes).await;
+                    }
+                    Message::Pong(_) => {
+                        last_heartbeat = Instant::now();
+                    }
+                    // no-op; ignore
+                    Message::Nop => {}
+                };
+            }
+            // client WebSocket stream error
+            TaskUpdateKind::ClientMessage(Err(err)) => {
+                log::error!("{}", err);
+                break None;
+            }
+            TaskUpdateKind::Push(Ok(event)) => {
+                if wanted(&event) {
+                    if let Ok(text) = serde_json::to_string(&event) {
+                        let _ = session.text(text).await;
+                    }
+                }
+            }
+            // the device fell too far behind, it has to reconnect and catch up
+            TaskUpdateKind::Push(Err(e)) => {
+                log::info!("push subscriber lagged: {}", e);
+                break Some(CloseReason {
+                    code: CloseCode::Again,
+                    description: Some(e),
+                });
+            }
+            // heartbeat interval ticked
+            TaskUpdateKind::NeedToSendHeartbeat => {
+                // if no heartbeat ping/pong received recently, close the connection
+                if Instant::now().duration_since(last_heartbeat) > CLIENT_TIMEOUT {
+                    log::info!("client has not sent heartbeat in over {CLIENT_TIMEOUT:?}");
+                    break Some(CloseReason {
+                        code: CloseCode::Protocol,
+                        description: Some(String::from("server: timed out")),
+                    });
+                }
+                // send heartbeat ping
+                let _ = session.ping(b"").await;
+            }
+        }
+    };
+
+    // attempt to close connection gracefully
+    let _ = session.close(reason).await;
+}
@@ -0,0 +1,105 @@
+use tokio_postgres::GenericClient;
+
+use crate::{
+    db_types::UserMessageReaction,
+    handlers::{self, AppError},
+    manage_push::PushHub,
+    manage_user_message,
+    response::PushEvent,
+    user_message_reaction_service,
+};
+
+// long enough for a few words or an emoji sequence
+const MAX_REACTION_CHARS: usize = 32;
+
+fn check_reaction(reaction: &str) -> Result<(), AppError> {
+    let len = reaction.chars().count();
+    if len == 0 || len > MAX_REACTION_CHARS || reaction.chars().any(char::is_control) {
+        Err(AppError::BadRequest)
+    } else {
+        Ok(())
+    }
+}
+
+// anybody who can see the message may react to it.
+// both users in the conversation hear about it, so the sender's devices can show it right away.
+pub async fn add_reaction(
+    con: &mut impl GenericClient,
+    push_hub: &PushHub,
+    creator_user_id: i64,
+    user_message_id: i64,
+    reaction: &str,
+) -> Result<UserMessageReaction, AppError> {
+    let reaction = reaction.trim();
+    check_reaction(reaction)?;
+
+    let um = manage_user_message::get_visible_user_message(con, creator_user_id, user_message_id)
+        .await?;
+
+    let user_message_reaction = user_message_reaction_service::add(
+        con,
+        creator_user_id,
+        user_message_id,
+        reaction.to_string(),
+    )
+    .await
+    .map_err(handlers::report_postgres_err)?;
+
+    for user_id in [um.creator_user_id, um.target_user_id] {
+        push_hub.push(
+            user_id,
+            PushEvent::UserMessageReactionAdded {
+                user_message_reaction: handlers::fill_user_message_reaction(
+                    user_message_reaction.clone(),
+                ),
+            },
+        );
+    }
+
+    Ok(user_message_reaction)
+}
+
+// only the one who reacted may take it back
+pub async fn remove_reaction(
+    con: &mut impl GenericClient,
+    push_hub: &PushHub,
+    user_id: i64,
+    user_message_reaction_id: i64,
+) -> Result<UserMessageReaction, AppError> {
+    let user_message_reaction = user_message_reaction_service::get_by_user_message_reaction_id(
+        con,
+        user_message_reaction_id,
+    )
+    .await
+    .map_err(handlers::report_postgres_err)?
+    .ok_or(AppError::NotFound)?;
+
+    if user_message_reaction.creator_user_id != user_id {
+        return Err(AppError::Forbidden);
+    }
+
+    let um = manage_user_message::get_visible_user_message(
+        con,
+        user_id,
+        user_message_reaction.user_message_id,
+    )
+    .await?;
+
+    let user_message_reaction =
+        user_message_reaction_service::delete(con, user_message_reaction_id)
+            .await
+            .map_err(handlers::report_postgres_err)?
+            .ok_or(AppError::NotFound)?;
+
+    for user_id in [um.creator_user_id, um.target_user_id] {
+        push_hub.push(
+            user_id,
+            PushEvent::UserMessageReactionRemoved {
+                user_message_reaction_id,
+                user_message_id: um.user_message_id,
+            },
+        );
+    }
+
+    Ok(user_message_reaction)
+}
@@ -0,0 +1,119 @@
+use std::time::Duration;
+
+use tokio_postgres::GenericClient;
+
+use crate::{
+    db_types::RetentionPolicy,
+    handlers::{self, AppError},
+    manage_household, manage_user_message, request, retention_policy_service, user_message_service,
+    utils,
+};
+
+/// How often messages that retention policies no longer keep are purged.
+pub const PURGE_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+// how many messages to delete per round trip
+const PURGE_BATCH_SIZE: i64 = 64;
+
+// a user sets their own policy, a household's is set by its admins
+pub async fn set_policy(
+    con: &mut impl GenericClient,
+    creator_user_id: i64,
+    props: &request::RetentionPolicyNewProps,
+) -> Result<RetentionPolicy, AppError> {
+    if props.keep_days.is_some_and(|x| x <= 0) || props.keep_last.is_some_and(|x| x <= 0) {
+        return Err(AppError::BadRequest);
+    }
+
+    let user_id = match props.household_id {
+        Some(household_id) => {
+            manage_household::require_admin(con, household_id, creator_user_id).await?;
+            None
+        }
+        None => Some(creator_user_id),
+    };
+
+    retention_policy_service::add(
+        con,
+        creator_user_id,
+        user_id,
+        props.household_id,
+        props.keep_days,
+        props.keep_last,
+        props.delete_after_played.unwrap_or(false),
+    )
+    .await
+    .map_err(handlers::report_postgres_err)
+}
+
+// any member may see their household's policy
+pub async fn get_policy(
+    con: &mut impl GenericClient,
+    user_id: i64,
+    household_id: Option<i64>,
+) -> Result<Option<RetentionPolicy>, AppError> {
+    match household_id {
+        Some(household_id) => {
+            manage_household::require_member(con, household_id, user_id).await?;
+            retention_policy_service::get_recent_by_household_id(con, household_id).await
+        }
+        None => retention_policy_service::get_recent_by_user_id(con, user_id).await,
+    }
+    .map_err(handlers::report_postgres_err)
+}
+
+// hides expired messages from their targets, then deletes every message neither side keeps,
+// releasing its audio so the blob can be collected. returns how many were hidden and deleted.
+pub async fn purge_expired(pool: &deadpool_postgres::Pool) -> Result<(u64, u64), AppError> {
+    let con: &mut tokio_postgres::Client =
+        &mut *pool.get().await.map_err(handlers::report_pool_err)?;
+
+    let mut hidden: u64 = 0;
+
+    loop {
+        let n = user_message_service::hide_expired_from_targets(
+            con,
+            utils::current_time_millis(),
+            PURGE_BATCH_SIZE,
+        )
+        .await
+        .map_err(handlers::report_postgres_err)?;
+
+        if n == 0 {
+            break;
+        }
+        hidden += n;
+    }
+
+    let mut purged: u64 = 0;
+
+    loop {
+        let mut tx = con
+            .transaction()
+            .await
+            .map_err(handlers::report_postgres_err)?;
+
+        let ums = user_message_service::delete_expired(
+            &mut tx,
+            utils::current_time_millis(),
+            PURGE_BATCH_SIZE,
+        )
+        .await
+        .map_err(handlers::report_postgres_err)?;
+
+        if ums.is_empty() {
+            break;
+        }
+
+        for um in ums.iter() {
+            manage_user_message::release_audio(&mut tx, um)
+                .await
+                .map_err(handlers::report_postgres_err)?;
+        }
+
+        tx.commit().await.map_err(handlers::report_postgres_err)?;
+        purged += ums.len() as u64;
+    }
+
+    Ok((hidden, purged))
+}
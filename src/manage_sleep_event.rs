@@ -0,0 +1,277 @@
+use tokio_postgres::GenericClient;
+
+use crate::{
+    bedtime_routine_snapshot_service,
+    db_types::{
+        SleepEvent, SleepEventEdit, SleepEventEditAction, SleepEventKind, SleepEventSource,
+    },
+    handlers::{self, AppError},
+    manage_bedtime_routine,
+    manage_push::PushHub,
+    manage_sleep_rule, manage_sleep_status, request, sleep_event_edit_service, sleep_event_service,
+    user_message_service, utils,
+};
+
+// how far back an event can be backfilled or moved
+const MAX_BACKFILL_AGE_MILLIS: i64 = 7 * 24 * 60 * 60 * 1000;
+
+// times given by the client can't be in the future, or too far in the past
+fn check_event_time(creation_time: i64) -> Result<(), AppError> {
+    let now = utils::current_time_millis();
+    if creation_time > now || creation_time < now - MAX_BACKFILL_AGE_MILLIS {
+        return Err(AppError::BadRequest);
+    }
+    Ok(())
+}
+
+// going to sleep snapshots how much of the user's bedtime routine they finished,
+// waking up delivers the messages that were scheduled for the user's next wake event.
+// waking up in the past only does so if nothing has happened since.
+// whoever the user shares their status with hears about it once the event is saved.
+async fn on_sleep_event_added(
+    con: &mut impl GenericClient,
+    sleep_event: &SleepEvent,
+) -> Result<(), tokio_postgres::Error> {
+    match sleep_event.kind {
+        SleepEventKind::Sleep => {
+            manage_bedtime_routine::snapshot_routine(con, sleep_event).await?;
+        }
+        SleepEventKind::Wake => {
+            let latest =
+                sleep_event_service::get_recent_by_user_id(con, sleep_event.creator_user_id)
+                    .await?;
+            if latest.is_some_and(|x| x.sleep_event_id == sleep_event.sleep_event_id) {
+                user_message_service::deliver_waiting_for_wake(
+                    con,
+                    sleep_event.creator_user_id,
+                    sleep_event.creation_time,
+                )
+                .await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+// records a sleep event as it happens, and carries out the user's rules for it
+pub async fn add_sleep_event(
+    con: &mut tokio_postgres::Client,
+    push_hub: &PushHub,
+    creator_user_id: i64,
+    kind: SleepEventKind,
+    source: SleepEventSource,
+) -> Result<SleepEvent, AppError> {
+    let mut tx = con
+        .transaction()
+        .await
+        .map_err(handlers::report_postgres_err)?;
+
+    let sleep_event = sleep_event_service::add(&mut tx, creator_user_id, kind, source)
+        .await
+        .map_err(handlers::report_postgres_err)?;
+
+    on_sleep_event_added(&mut tx, &sleep_event)
+        .await
+        .map_err(handlers::report_postgres_err)?;
+
+    let events = manage_sleep_rule::run_rules(&mut tx, &sleep_event).await?;
+
+    tx.commit().await.map_err(handlers::report_postgres_err)?;
+
+    for (user_id, event) in events {
+        push_hub.push(user_id, event);
+    }
+    manage_sleep_status::publish_status(con, push_hub, sleep_event.creator_user_id).await;
+    Ok(sleep_event)
+}
+
+// records a sleep event from a device that can't prove it's acting for the user.
+// it's kept like any other, but doesn't run the user's rules, and nobody is told about it.
+pub async fn add_unverified_sleep_event(
+    con: &mut tokio_postgres::Client,
+    creator_user_id: i64,
+    kind: SleepEventKind,
+    source: SleepEventSource,
+) -> Result<SleepEvent, AppError> {
+    let mut tx = con
+        .transaction()
+        .await
+        .map_err(handlers::report_postgres_err)?;
+
+    let sleep_event = sleep_event_service::add(&mut tx, creator_user_id, kind, source)
+        .await
+        .map_err(handlers::report_postgres_err)?;
+
+    on_sleep_event_added(&mut tx, &sleep_event)
+        .await
+        .map_err(handlers::report_postgres_err)?;
+
+    tx.commit().await.map_err(handlers::report_postgres_err)?;
+    Ok(sleep_event)
+}
+
+// records a sleep event the user forgot to at the time
+pub async fn backfill_sleep_event(
+    con: &mut tokio_postgres::Client,
+    push_hub: &PushHub,
+    creator_user_id: i64,
+    kind: SleepEventKind,
+    creation_time: i64,
+) -> Result<SleepEvent, AppError> {
+    check_event_time(creation_time)?;
+
+    let mut tx = con
+        .transaction()
+        .await
+        .map_err(handlers::report_postgres_err)?;
+
+    let sleep_event = sleep_event_service::add_backfilled(
+        &mut tx,
+        creator_user_id,
+        kind,
+        SleepEventSource::Backfill,
+        creation_time,
+    )
+    .await
+    .map_err(handlers::report_postgres_err)?;
+
+    sleep_event_edit_service::add(
+        &mut tx,
+        creator_user_id,
+        sleep_event.sleep_event_id,
+        SleepEventEditAction::Backfill,
+        None,
+        Some(&sleep_event),
+    )
+    .await
+    .map_err(handlers::report_postgres_err)?;
+
+    on_sleep_event_added(&mut tx, &sleep_event)
+        .await
+        .map_err(handlers::report_postgres_err)?;
+
+    tx.commit().await.map_err(handlers::report_postgres_err)?;
+
+    manage_sleep_status::publish_status(con, push_hub, sleep_event.creator_user_id).await;
+    Ok(sleep_event)
+}
+
+// a sleep event only its creator may change
+async fn get_sleep_event_for_user(
+    con: &mut impl GenericClient,
+    user_id: i64,
+    sleep_event_id: i64,
+) -> Result<SleepEvent, AppError> {
+    let sleep_event = sleep_event_service::get_by_sleep_event_id(con, sleep_event_id)
+        .await
+        .map_err(handlers::report_postgres_err)?
+        .ok_or(AppError::NotFound)?;
+
+    if sleep_event.creator_user_id != user_id {
+        return Err(AppError::NotFound);
+    }
+    Ok(sleep_event)
+}
+
+// corrects a sleep event's kind or time.
+// the bedtime routine snapshot is redone, but messages that were delivered stay delivered.
+pub async fn update_sleep_event(
+    con: &mut tokio_postgres::Client,
+    push_hub: &PushHub,
+    user_id: i64,
+    props: &request::SleepEventUpdateProps,
+) -> Result<SleepEvent, AppError> {
+    if let Some(creation_time) = props.creation_time {
+        check_event_time(creation_time)?;
+    }
+
+    let mut tx = con
+        .transaction()
+        .await
+        .map_err(handlers::report_postgres_err)?;
+
+    let old = get_sleep_event_for_user(&mut tx, user_id, props.sleep_event_id).await?;
+
+    let mut sleep_event = old.clone();
+    if let Some(kind) = props.kind {
+        sleep_event.kind = kind;
+    }
+    if let Some(creation_time) = props.creation_time {
+        sleep_event.creation_time = creation_time;
+    }
+
+    sleep_event_service::update(&mut tx, &sleep_event)
+        .await
+        .map_err(handlers::report_postgres_err)?;
+
+    sleep_event_edit_service::add(
+        &mut tx,
+        user_id,
+        sleep_event.sleep_event_id,
+        SleepEventEditAction::Update,
+        Some(&old),
+        Some(&sleep_event),
+    )
+    .await
+    .map_err(handlers::report_postgres_err)?;
+
+    bedtime_routine_snapshot_service::delete_by_sleep_event_id(&mut tx, sleep_event.sleep_event_id)
+        .await
+        .map_err(handlers::report_postgres_err)?;
+    if sleep_event.kind == SleepEventKind::Sleep {
+        manage_bedtime_routine::snapshot_routine(&mut tx, &sleep_event)
+            .await
+            .map_err(handlers::report_postgres_err)?;
+    }
+
+    tx.commit().await.map_err(handlers::report_postgres_err)?;
+
+    manage_sleep_status::publish_status(con, push_hub, sleep_event.creator_user_id).await;
+    Ok(sleep_event)
+}
+
+// deletes a sleep event, along with its bedtime routine snapshot
+pub async fn delete_sleep_event(
+    con: &mut tokio_postgres::Client,
+    push_hub: &PushHub,
+    user_id: i64,
+    sleep_event_id: i64,
+) -> Result<SleepEvent, AppError> {
+    let mut tx = con
+        .transaction()
+        .await
+        .map_err(handlers::report_postgres_err)?;
+
+    let sleep_event = get_sleep_event_for_user(&mut tx, user_id, sleep_event_id).await?;
+
+    sleep_event_service::delete(&mut tx, sleep_event_id)
+        .await
+        .map_err(handlers::report_postgres_err)?;
+
+    sleep_event_edit_service::add(
+        &mut tx,
+        user_id,
+        sleep_event_id,
+        SleepEventEditAction::Delete,
+        Some(&sleep_event),
+        None,
+    )
+    .await
+    .map_err(handlers::report_postgres_err)?;
+
+    tx.commit().await.map_err(handlers::report_postgres_err)?;
+
+    manage_sleep_status::publish_status(con, push_hub, sleep_event.creator_user_id).await;
+    Ok(sleep_event)
+}
+
+// every backfill, correction and deletion the user made, oldest first
+pub async fn get_sleep_event_history(
+    con: &mut impl GenericClient,
+    user_id: i64,
+    sleep_event_id: Option<i64>,
+) -> Result<Vec<SleepEventEdit>, AppError> {
+    sleep_event_edit_service::get_by_creator_user_id(con, user_id, sleep_event_id)
+        .await
+        .map_err(handlers::report_postgres_err)
+}
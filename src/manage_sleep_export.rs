@@ -0,0 +1,604 @@
+use actix_web::web::Bytes;
+use chrono::{DateTime, NaiveDate, SecondsFormat, TimeZone, Utc};
+use chrono_tz::Tz;
+use futures_util::Stream;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    db_types::{SleepEvent, SleepEventKind},
+    handlers::{self, AppError},
+    request, response, sleep_event_service, utils,
+};
+
+// how many events are read from the database at a time
+const EXPORT_PAGE_SIZE: i64 = 500;
+
+// how far around the range to look for the other half of the nights on its edges
+const MAX_SESSION_MILLIS: i64 = 24 * 60 * 60 * 1000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum SleepExportFormat {
+    // one row per session, for spreadsheets
+    Csv,
+    // an array of sessions
+    Json,
+    // an icalendar file with a VEVENT per session, for calendars
+    Ics,
+}
+
+impl SleepExportFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            SleepExportFormat::Csv => "text/csv; charset=utf-8",
+            SleepExportFormat::Json => "application/json",
+            SleepExportFormat::Ics => "text/calendar; charset=utf-8",
+        }
+    }
+
+    pub fn file_name(self) -> &'static str {
+        match self {
+            SleepExportFormat::Csv => "sleep.csv",
+            SleepExportFormat::Json => "sleep.json",
+            SleepExportFormat::Ics => "sleep.ics",
+        }
+    }
+}
+
+// a night's sleep, or an event that couldn't be paired up with one
+#[derive(Clone, Debug)]
+struct SleepEntry {
+    sleep: Option<SleepEvent>,
+    wake: Option<SleepEvent>,
+}
+
+impl SleepEntry {
+    fn time(&self) -> i64 {
+        self.sleep
+            .as_ref()
+            .or(self.wake.as_ref())
+            .map(|x| x.creation_time)
+            .unwrap_or_default()
+    }
+}
+
+// what to export, already checked
+#[derive(Clone, Debug)]
+pub struct SleepExport {
+    pub user_id: i64,
+    pub format: SleepExportFormat,
+    pub time_zone: Tz,
+    // nights starting from min_time up to but not including max_time
+    pub min_time: Option<i64>,
+    pub max_time: Option<i64>,
+}
+
+fn parse_date(date: &str) -> Result<NaiveDate, AppError> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|e| {
+        log::info!("invalid date {}: {}", date, e);
+        AppError::BadRequest
+    })
+}
+
+// midnight at the start of the day in time_zone
+fn start_of_day(date: NaiveDate, time_zone: Tz) -> Result<DateTime<Tz>, AppError> {
+    let midnight = date.and_hms_opt(0, 0, 0).ok_or(AppError::BadRequest)?;
+    // a few time zones skip midnight when the clocks go forward
+    Ok(time_zone
+        .from_local_datetime(&midnight)
+        .earliest()
+        .unwrap_or_else(|| time_zone.from_utc_datetime(&midnight)))
+}
+
+impl SleepExport {
+    // the range covers whole days in the time zone, both dates included
+    pub fn new(
+        user_id: i64,
+        props: &request::SleepEventExportProps,
+    ) -> Result<SleepExport, AppError> {
+        let time_zone = props
+            .time_zone
+            .as_deref()
+            .unwrap_or("UTC")
+            .parse::<Tz>()
+            .map_err(|e| {
+                log::info!("{}", e);
+                AppError::BadRequest
+            })?;
+
+        let min_time = match &props.start_date {
+            Some(date) => Some(start_of_day(parse_date(date)?, time_zone)?.timestamp_millis()),
+            None => None,
+        };
+        // days aren't always 24 hours long, so the range ends at the start of the next one
+        let max_time = match &props.end_date {
+            Some(date) => {
+                let next_day = parse_date(date)?.succ_opt().ok_or(AppError::BadRequest)?;
+                Some(start_of_day(next_day, time_zone)?.timestamp_millis())
+            }
+            None => None,
+        };
+        if let (Some(min_time), Some(max_time)) = (min_time, max_time) {
+            if max_time <= min_time {
+                return Err(AppError::BadRequest);
+            }
+        }
+
+        Ok(SleepExport {
+            user_id,
+            format: props.format,
+            time_zone,
+            min_time,
+            max_time,
+        })
+    }
+
+    fn local_time(&self, millis: i64) -> String {
+        self.time_zone
+            .timestamp_millis_opt(millis)
+            .unwrap()
+            .to_rfc3339_opts(SecondsFormat::Secs, false)
+    }
+
+    fn header(&self) -> String {
+        match self.format {
+            SleepExportFormat::Csv => {
+                "start,end,duration_minutes,sleep_event_id,wake_event_id\n".into()
+            }
+            SleepExportFormat::Json => "[".into(),
+            SleepExportFormat::Ics => [
+                "BEGIN:VCALENDAR",
+                "VERSION:2.0",
+                "PRODID:-//kthg//sleep export//EN",
+                "CALSCALE:GREGORIAN",
+                "X-WR-CALNAME:Sleep",
+                &format!("X-WR-TIMEZONE:{}", self.time_zone),
+                "",
+            ]
+            .join("\r\n"),
+        }
+    }
+
+    // first is whether this is the first entry written
+    fn entry(&self, x: &SleepEntry, first: bool) -> String {
+        let sleep_time = x.sleep.as_ref().map(|x| x.creation_time);
+        let wake_time = x.wake.as_ref().map(|x| x.creation_time);
+        let duration_millis = match (sleep_time, wake_time) {
+            (Some(sleep_time), Some(wake_time)) => Some(wake_time - sleep_time),
+            _ => None,
+        };
+
+        match self.format {
+            SleepExportFormat::Csv => {
+                let column = |x: Option<String>| x.unwrap_or_default();
+                format!(
+                    "{},{},{},{},{}\n",
+                    column(sleep_time.map(|x| self.local_time(x))),
+                    column(wake_time.map(|x| self.local_time(x))),
+                    column(duration_millis.map(|x| (x / 60000).to_string())),
+                    column(x.sleep.as_ref().map(|x| x.sleep_event_id.to_string())),
+                    column(x.wake.as_ref().map(|x| x.sleep_event_id.to_string())),
+                )
+            }
+            SleepExportFormat::Json => {
+                let entry = response::SleepExportEntry {
+                    sleep_event_id: x.sleep.as_ref().map(|x| x.sleep_event_id),
+                    wake_event_id: x.wake.as_ref().map(|x| x.sleep_event_id),
+                    sleep_time,
+                    wake_time,
+                    sleep_local_time: sleep_time.map(|x| self.local_time(x)),
+                    wake_local_time: wake_time.map(|x| self.local_time(x)),
+                    duration_millis,
+                };
+                let separator = if first { "" } else { "," };
+                // serializing plain numbers and strings can't fail
+                format!("{}{}", separator, serde_json::to_string(&entry).unwrap())
+            }
+            SleepExportFormat::Ics => {
+                // calendars only get whole nights
+                let (Some(sleep), Some(wake), Some(duration_millis)) =
+                    (&x.sleep, &x.wake, duration_millis)
+                else {
+                    return String::new();
+                };
+                let utc = |millis: i64| {
+                    Utc.timestamp_millis_opt(millis)
+                        .unwrap()
+                        .format("%Y%m%dT%H%M%SZ")
+                        .to_string()
+                };
+                let minutes = duration_millis / 60000;
+                [
+                    "BEGIN:VEVENT",
+                    &format!(
+                        "UID:sleep-{}-{}@kthg",
+                        sleep.sleep_event_id, wake.sleep_event_id
+                    ),
+                    &format!("DTSTAMP:{}", utc(utils::current_time_millis())),
+                    &format!("DTSTART:{}", utc(sleep.creation_time)),
+                    &format!("DTEND:{}", utc(wake.creation_time)),
+                    &format!("SUMMARY:Slept {}h {}m", minutes / 60, minutes % 60),
+                    "TRANSP:TRANSPARENT",
+                    "END:VEVENT",
+                    "",
+                ]
+                .join("\r\n")
+            }
+        }
+    }
+
+    fn footer(&self) -> String {
+        match self.format {
+            SleepExportFormat::Csv => String::new(),
+            SleepExportFormat::Json => "]".into(),
+            SleepExportFormat::Ics => "END:VCALENDAR\r\n".into(),
+        }
+    }
+}
+
+// pairs up events as they come in and writes out the nights in the range
+struct ExportWriter {
+    export: SleepExport,
+    entry_written: bool,
+    // a sleep event still waiting for the wake event after it
+    pending_sleep: Option<SleepEvent>,
+}
+
+impl ExportWriter {
+    fn new(export: SleepExport) -> ExportWriter {
+        ExportWriter {
+            export,
+            entry_written: false,
+            pending_sleep: None,
+        }
+    }
+
+    // pairs each sleep event with the wake event following it
+    fn push(&mut self, sleep_event: SleepEvent) -> Option<SleepEntry> {
+        match sleep_event.kind {
+            SleepEventKind::Sleep => {
+                self.pending_sleep
+                    .replace(sleep_event)
+                    .map(|sleep| SleepEntry {
+                        sleep: Some(sleep),
+                        wake: None,
+                    })
+            }
+            SleepEventKind::Wake => Some(SleepEntry {
+                sleep: self.pending_sleep.take(),
+                wake: Some(sleep_event),
+            }),
+        }
+    }
+
+    fn write(&mut self, out: &mut String, entry: SleepEntry) {
+        // nights are in the range if they started in it
+        let time = entry.time();
+        if self.export.min_time.is_some_and(|x| time < x)
+            || self.export.max_time.is_some_and(|x| time >= x)
+        {
+            return;
+        }
+        let s = self.export.entry(&entry, !self.entry_written);
+        if !s.is_empty() {
+            out.push_str(&s);
+            self.entry_written = true;
+        }
+    }
+
+    fn write_event(&mut self, out: &mut String, sleep_event: SleepEvent) {
+        if let Some(entry) = self.push(sleep_event) {
+            self.write(out, entry);
+        }
+    }
+
+    // writes out a sleep event that never got a wake event, and the footer
+    fn finish(&mut self, out: &mut String) {
+        if let Some(sleep) = self.pending_sleep.take() {
+            let entry = SleepEntry {
+                sleep: Some(sleep),
+                wake: None,
+            };
+            self.write(out, entry);
+        }
+        out.push_str(&self.export.footer());
+    }
+}
+
+struct ExportState {
+    // a connection is only held while a page is read, not for the whole download
+    pool: deadpool_postgres::Pool,
+    writer: ExportWriter,
+    header_written: bool,
+    // the footer has been written
+    done: bool,
+    // where the last page left off
+    after: Option<(i64, i64)>,
+}
+
+impl ExportState {
+    // the next chunk of the file, or none once it has all been written
+    async fn next_chunk(&mut self) -> Result<Option<String>, AppError> {
+        if self.done {
+            return Ok(None);
+        }
+        let export = &self.writer.export;
+        if !self.header_written {
+            self.header_written = true;
+            return Ok(Some(export.header()));
+        }
+
+        let page = {
+            let con: &mut tokio_postgres::Client =
+                &mut *self.pool.get().await.map_err(handlers::report_pool_err)?;
+            sleep_event_service::get_page_by_creator_user_id(
+                con,
+                export.user_id,
+                export.min_time.map(|x| x - MAX_SESSION_MILLIS),
+                export.max_time.map(|x| x + MAX_SESSION_MILLIS),
+                self.after,
+                EXPORT_PAGE_SIZE,
+            )
+            .await
+            .map_err(handlers::report_postgres_err)?
+        };
+
+        let mut out = String::new();
+
+        if page.is_empty() {
+            self.writer.finish(&mut out);
+            self.done = true;
+            return Ok(Some(out));
+        }
+
+        for sleep_event in page {
+            self.after = Some((sleep_event.creation_time, sleep_event.sleep_event_id));
+            self.writer.write_event(&mut out, sleep_event);
+        }
+        Ok(Some(out))
+    }
+}
+
+// streams the user's nights out a page of events at a time, oldest first.
+// a sleep event followed by a wake event is a session, any other event goes out on its own.
+pub fn export_sleep(
+    pool: deadpool_postgres::Pool,
+    export: SleepExport,
+) -> impl Stream<Item = Result<Bytes, actix_web::Error>> {
+    let state = ExportState {
+        pool,
+        writer: ExportWriter::new(export),
+        header_written: false,
+        done: false,
+        after: None,
+    };
+
+    futures_util::stream::unfold(Some(state), |state| async move {
+        let mut state = state?;
+        match state.next_chunk().await {
+            Ok(Some(chunk)) => Some((Ok(Bytes::from(chunk)), Some(state))),
+            Ok(None) => None,
+            // already logged
+            Err(e) => Some((Err(e.into()), None)),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db_types::SleepEventSource;
+
+    fn event(sleep_event_id: i64, kind: SleepEventKind, creation_time: i64) -> SleepEvent {
+        SleepEvent {
+            sleep_event_id,
+            creation_time,
+            creator_user_id: 1,
+            kind,
+            source: SleepEventSource::App,
+        }
+    }
+
+    fn export(
+        format: SleepExportFormat,
+        min_time: Option<i64>,
+        max_time: Option<i64>,
+    ) -> SleepExport {
+        SleepExport {
+            user_id: 1,
+            format,
+            time_zone: Tz::UTC,
+            min_time,
+            max_time,
+        }
+    }
+
+    fn ids(entry: &SleepEntry) -> (Option<i64>, Option<i64>) {
+        (
+            entry.sleep.as_ref().map(|x| x.sleep_event_id),
+            entry.wake.as_ref().map(|x| x.sleep_event_id),
+        )
+    }
+
+    // the whole file, as the stream would send it
+    fn render(export: SleepExport, events: Vec<SleepEvent>) -> String {
+        let mut out = export.header();
+        let mut writer = ExportWriter::new(export);
+        for sleep_event in events {
+            writer.write_event(&mut out, sleep_event);
+        }
+        writer.finish(&mut out);
+        out
+    }
+
+    // 2024-01-01T00:00:00Z
+    const DAY: i64 = 1704067200000;
+    const HOUR: i64 = 60 * 60 * 1000;
+
+    #[test]
+    fn pairs_sleep_with_the_following_wake() {
+        let mut writer = ExportWriter::new(export(SleepExportFormat::Csv, None, None));
+        assert!(writer.push(event(1, SleepEventKind::Sleep, DAY)).is_none());
+        let entry = writer
+            .push(event(2, SleepEventKind::Wake, DAY + HOUR))
+            .unwrap();
+        assert_eq!(ids(&entry), (Some(1), Some(2)));
+        assert!(writer.pending_sleep.is_none());
+    }
+
+    #[test]
+    fn a_second_sleep_leaves_the_first_on_its_own() {
+        let mut writer = ExportWriter::new(export(SleepExportFormat::Csv, None, None));
+        assert!(writer.push(event(1, SleepEventKind::Sleep, DAY)).is_none());
+        let entry = writer
+            .push(event(2, SleepEventKind::Sleep, DAY + HOUR))
+            .unwrap();
+        assert_eq!(ids(&entry), (Some(1), None));
+        let entry = writer
+            .push(event(3, SleepEventKind::Wake, DAY + 2 * HOUR))
+            .unwrap();
+        assert_eq!(ids(&entry), (Some(2), Some(3)));
+    }
+
+    #[test]
+    fn a_lone_wake_goes_out_on_its_own() {
+        let mut writer = ExportWriter::new(export(SleepExportFormat::Csv, None, None));
+        let entry = writer.push(event(1, SleepEventKind::Wake, DAY)).unwrap();
+        assert_eq!(ids(&entry), (None, Some(1)));
+    }
+
+    #[test]
+    fn a_pending_sleep_is_written_at_the_end() {
+        let out = render(
+            export(SleepExportFormat::Csv, None, None),
+            vec![event(1, SleepEventKind::Sleep, DAY)],
+        );
+        assert_eq!(
+            out,
+            "start,end,duration_minutes,sleep_event_id,wake_event_id\n\
+             2024-01-01T00:00:00+00:00,,,1,\n"
+        );
+    }
+
+    #[test]
+    fn only_nights_starting_in_the_range_are_written() {
+        let out = render(
+            export(SleepExportFormat::Csv, Some(DAY), Some(DAY + 10 * HOUR)),
+            vec![
+                // starts before the range, ends in it
+                event(1, SleepEventKind::Sleep, DAY - 1),
+                event(2, SleepEventKind::Wake, DAY + HOUR),
+                // starts right at the start
+                event(3, SleepEventKind::Sleep, DAY + 2 * HOUR),
+                event(4, SleepEventKind::Wake, DAY + 3 * HOUR),
+                // starts in the range, ends after it
+                event(5, SleepEventKind::Sleep, DAY + 10 * HOUR - 1),
+                event(6, SleepEventKind::Wake, DAY + 11 * HOUR),
+                // starts right at the end
+                event(7, SleepEventKind::Sleep, DAY + 10 * HOUR),
+            ],
+        );
+        let rows: Vec<&str> = out.lines().skip(1).collect();
+        assert_eq!(rows.len(), 2);
+        assert!(rows[0].ends_with(",3,4"));
+        assert!(rows[1].ends_with(",5,6"));
+    }
+
+    #[test]
+    fn the_range_follows_daylight_saving() {
+        let props = request::SleepEventExportProps {
+            format: SleepExportFormat::Csv,
+            start_date: Some("2024-03-31".into()),
+            end_date: Some("2024-03-31".into()),
+            time_zone: Some("Europe/Berlin".into()),
+            api_key: String::new(),
+        };
+        let export = SleepExport::new(1, &props).unwrap();
+        // the clocks went forward, so the day was 23 hours long
+        assert_eq!(export.min_time, Some(1711839600000));
+        assert_eq!(export.max_time, Some(1711839600000 + 23 * HOUR));
+    }
+
+    #[test]
+    fn writes_csv() {
+        let out = render(
+            export(SleepExportFormat::Csv, None, None),
+            vec![
+                event(1, SleepEventKind::Sleep, DAY),
+                event(2, SleepEventKind::Wake, DAY + 7 * HOUR + 30 * 60 * 1000),
+                event(3, SleepEventKind::Wake, DAY + 8 * HOUR),
+            ],
+        );
+        assert_eq!(
+            out,
+            "start,end,duration_minutes,sleep_event_id,wake_event_id\n\
+             2024-01-01T00:00:00+00:00,2024-01-01T07:30:00+00:00,450,1,2\n\
+             ,2024-01-01T08:00:00+00:00,,,3\n"
+        );
+    }
+
+    #[test]
+    fn writes_json() {
+        let out = render(
+            export(SleepExportFormat::Json, None, None),
+            vec![
+                event(1, SleepEventKind::Sleep, DAY),
+                event(2, SleepEventKind::Wake, DAY + 8 * HOUR),
+                event(3, SleepEventKind::Sleep, DAY + 20 * HOUR),
+            ],
+        );
+        let entries: Vec<response::SleepExportEntry> = serde_json::from_str(&out).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].sleep_event_id, Some(1));
+        assert_eq!(entries[0].wake_event_id, Some(2));
+        assert_eq!(entries[0].duration_millis, Some(8 * HOUR));
+        assert_eq!(
+            entries[0].wake_local_time.as_deref(),
+            Some("2024-01-01T08:00:00+00:00")
+        );
+        assert_eq!(entries[1].sleep_event_id, Some(3));
+        assert_eq!(entries[1].wake_event_id, None);
+        assert_eq!(entries[1].duration_millis, None);
+    }
+
+    #[test]
+    fn writes_json_without_entries() {
+        let out = render(export(SleepExportFormat::Json, None, None), vec![]);
+        assert_eq!(out, "[]");
+    }
+
+    #[test]
+    fn writes_ics() {
+        let out = render(
+            export(SleepExportFormat::Ics, None, None),
+            vec![
+                event(1, SleepEventKind::Sleep, DAY),
+                event(2, SleepEventKind::Wake, DAY + 7 * HOUR + 15 * 60 * 1000),
+                // calendars only get whole nights
+                event(3, SleepEventKind::Wake, DAY + 8 * HOUR),
+                event(4, SleepEventKind::Sleep, DAY + 20 * HOUR),
+            ],
+        );
+
+        // every line ends in CRLF
+        assert!(out.ends_with("\r\n"));
+        assert!(!out.replace("\r\n", "").contains('\n'));
+
+        let lines: Vec<&str> = out.split_terminator("\r\n").collect();
+        assert_eq!(lines.first(), Some(&"BEGIN:VCALENDAR"));
+        assert_eq!(lines.last(), Some(&"END:VCALENDAR"));
+        assert!(lines.contains(&"X-WR-TIMEZONE:UTC"));
+
+        let begin = lines.iter().position(|x| *x == "BEGIN:VEVENT").unwrap();
+        let end = lines.iter().position(|x| *x == "END:VEVENT").unwrap();
+        assert_eq!(lines.iter().filter(|x| **x == "BEGIN:VEVENT").count(), 1);
+
+        let event = &lines[begin + 1..end];
+        assert!(event.contains(&"UID:sleep-1-2@kthg"));
+        assert!(event.contains(&"DTSTART:20240101T000000Z"));
+        assert!(event.contains(&"DTEND:20240101T071500Z"));
+        assert!(event.contains(&"SUMMARY:Slept 7h 15m"));
+        assert!(event.contains(&"TRANSP:TRANSPARENT"));
+        assert!(event.iter().any(|x| x.starts_with("DTSTAMP:")));
+    }
+}
@@ -0,0 +1,289 @@
+use std::io::Read;
+use std::path::Path;
+
+use actix_web::web::{self, Bytes};
+use chrono_tz::Tz;
+use futures_util::StreamExt;
+use tokio::sync::mpsc;
+
+use crate::{
+    db_types::{SleepEvent, SleepEventKind, SleepEventSource},
+    handlers::{self, AppError},
+    sleep_event_service,
+    sleep_import::{self, SkipReason, SleepImportFormat, SleepSession},
+    utils,
+};
+
+/// The largest export accepted over http. Bigger ones can be imported with `kthg import-sleep`
+const MAX_IMPORT_BYTES: usize = 16 * 1024 * 1024;
+
+// how many chunks of an upload may wait for the parser, which bounds the memory an import uses
+const IMPORT_CHUNK_BACKLOG: usize = 4;
+
+// events this close to a session count as recording it already
+const DUPLICATE_TOLERANCE_MILLIS: i64 = 5 * 60 * 1000;
+
+// anything longer is a tracker that was left running
+const MAX_SESSION_MILLIS: i64 = 24 * 60 * 60 * 1000;
+
+#[derive(Clone, Debug)]
+pub struct ImportedSession {
+    pub session: SleepSession,
+    pub sleep_event: SleepEvent,
+    pub wake_event: SleepEvent,
+}
+
+#[derive(Clone, Debug)]
+pub struct SkippedSession {
+    pub session: SleepSession,
+    pub reason: SkipReason,
+}
+
+// what became of each session in an export, oldest first
+#[derive(Clone, Debug)]
+pub struct SleepImportReport {
+    pub imported: Vec<ImportedSession>,
+    pub skipped: Vec<SkippedSession>,
+}
+
+fn check_session(session: &SleepSession, now: i64) -> bool {
+    session.end_time > session.start_time
+        && session.end_time - session.start_time <= MAX_SESSION_MILLIS
+        && session.end_time <= now
+}
+
+// records each session as going to sleep at its start and waking up at its end.
+// sessions that overlap one another or the user's existing events are skipped, so importing
+// the same export twice, or one that covers nights the user recorded here, adds nothing.
+// imports are history, so they neither snapshot bedtime routines nor deliver messages.
+pub async fn import_sessions(
+    con: &mut tokio_postgres::Client,
+    user_id: i64,
+    mut sessions: Vec<SleepSession>,
+) -> Result<SleepImportReport, tokio_postgres::Error> {
+    let now = utils::current_time_millis();
+    sessions.sort_by_key(|x| (x.start_time, x.end_time));
+
+    let mut report = SleepImportReport {
+        imported: vec![],
+        skipped: vec![],
+    };
+
+    let mut tx = con.transaction().await?;
+
+    // every event the user has anywhere near the export, oldest first
+    let existing: Vec<i64> = match (
+        sessions.iter().map(|x| x.start_time).min(),
+        sessions.iter().map(|x| x.end_time).max(),
+    ) {
+        (Some(min), Some(max)) => sleep_event_service::get_by_creator_user_id_between(
+            &mut tx,
+            user_id,
+            min.saturating_sub(DUPLICATE_TOLERANCE_MILLIS),
+            max.saturating_add(DUPLICATE_TOLERANCE_MILLIS),
+        )
+        .await?
+        .into_iter()
+        .map(|x| x.creation_time)
+        .collect(),
+        _ => vec![],
+    };
+
+    let mut last_end_time = i64::MIN;
+    for session in sessions {
+        let reason = if !check_session(&session, now) {
+            Some(SkipReason::Invalid)
+        } else if session.start_time < last_end_time {
+            Some(SkipReason::Overlapping)
+        } else {
+            last_end_time = session.end_time;
+            // the first event at or after the start of the tolerance window
+            let i =
+                existing.partition_point(|x| *x < session.start_time - DUPLICATE_TOLERANCE_MILLIS);
+            if existing
+                .get(i)
+                .is_some_and(|x| *x <= session.end_time + DUPLICATE_TOLERANCE_MILLIS)
+            {
+                Some(SkipReason::Duplicate)
+            } else {
+                None
+            }
+        };
+
+        if let Some(reason) = reason {
+            report.skipped.push(SkippedSession { session, reason });
+            continue;
+        }
+
+        let sleep_event = sleep_event_service::add_backfilled(
+            &mut tx,
+            user_id,
+            SleepEventKind::Sleep,
+            SleepEventSource::Import,
+            session.start_time,
+        )
+        .await?;
+        let wake_event = sleep_event_service::add_backfilled(
+            &mut tx,
+            user_id,
+            SleepEventKind::Wake,
+            SleepEventSource::Import,
+            session.end_time,
+        )
+        .await?;
+        report.imported.push(ImportedSession {
+            session,
+            sleep_event,
+            wake_event,
+        });
+    }
+
+    tx.commit().await?;
+    Ok(report)
+}
+
+fn parse_time_zone(time_zone: &str) -> Result<Tz, String> {
+    time_zone
+        .parse::<Tz>()
+        .map_err(|e| format!("invalid time zone {}: {}", time_zone, e))
+}
+
+// the body of an upload, read by the parser on a blocking thread as its chunks arrive
+struct UploadReader {
+    chunks: mpsc::Receiver<Bytes>,
+    current: Bytes,
+}
+
+impl Read for UploadReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.current.is_empty() {
+            match self.chunks.blocking_recv() {
+                Some(chunk) => self.current = chunk,
+                // the whole body has been read
+                None => return Ok(0),
+            }
+        }
+        let n = buf.len().min(self.current.len());
+        buf[..n].copy_from_slice(&self.current.split_to(n));
+        Ok(n)
+    }
+}
+
+// parses an export sent by the user while it is still arriving, never holding all of it
+pub async fn parse_upload(
+    format: SleepImportFormat,
+    time_zone: &str,
+    mut payload: web::Payload,
+) -> Result<Vec<SleepSession>, AppError> {
+    let time_zone = parse_time_zone(time_zone).map_err(|e| {
+        log::info!("{}", e);
+        AppError::BadRequest
+    })?;
+
+    // exports can be large, so keep parsing off the thread serving requests
+    let (sender, chunks) = mpsc::channel(IMPORT_CHUNK_BACKLOG);
+    let parsing = tokio::task::spawn_blocking(move || {
+        let reader = UploadReader {
+            chunks,
+            current: Bytes::new(),
+        };
+        sleep_import::parse(format, reader, time_zone)
+    });
+
+    let mut size = 0;
+    let mut received = Ok(());
+    while let Some(chunk) = payload.next().await {
+        let chunk = match chunk {
+            Ok(chunk) => chunk,
+            Err(e) => {
+                log::info!("{}", e);
+                received = Err(AppError::BadRequest);
+                break;
+            }
+        };
+        size += chunk.len();
+        if size > MAX_IMPORT_BYTES {
+            received = Err(AppError::MessageTooLarge);
+            break;
+        }
+        // the parser stops reading early if the export is malformed
+        if sender.send(chunk).await.is_err() {
+            break;
+        }
+    }
+    // lets the parser see the end of the body
+    drop(sender);
+
+    let parsed = parsing.await.map_err(|e| {
+        log::error!("sleep import: {}", e);
+        AppError::InternalServerError
+    })?;
+
+    // a body cut short says nothing about the export
+    received?;
+    parsed.map_err(handlers::report_sleep_import_err)
+}
+
+// imports an export from disk on the user's behalf
+pub async fn import_sleep_file(
+    pool: &deadpool_postgres::Pool,
+    user_id: i64,
+    format: SleepImportFormat,
+    time_zone: &str,
+    path: &Path,
+) -> Result<(), Box<dyn std::error::Error + 'static>> {
+    let time_zone = parse_time_zone(time_zone)?;
+    let file = std::fs::File::open(path)?;
+    let sessions = sleep_import::parse(format, file, time_zone)?;
+
+    let con: &mut tokio_postgres::Client = &mut *pool.get().await?;
+    let report = import_sessions(con, user_id, sessions).await?;
+
+    for skipped in report.skipped.iter() {
+        log::info!(
+            "skipped {:?} session from {} to {}",
+            skipped.reason,
+            skipped.session.start_time,
+            skipped.session.end_time
+        );
+    }
+    log::info!(
+        "imported {} sleep sessions for user {}, skipped {}",
+        report.imported.len(),
+        user_id,
+        report.skipped.len()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_an_upload_split_across_chunks() {
+        let data = include_bytes!("../fixtures/sleep_import/sleep.csv");
+        let (sender, chunks) = mpsc::channel(data.len());
+        // chunk boundaries fall in the middle of lines
+        for chunk in data.chunks(7) {
+            sender.try_send(Bytes::copy_from_slice(chunk)).unwrap();
+        }
+        drop(sender);
+
+        let reader = UploadReader {
+            chunks,
+            current: Bytes::new(),
+        };
+        let sessions =
+            sleep_import::parse(SleepImportFormat::Csv, reader, chrono_tz::America::New_York);
+        assert_eq!(
+            sessions.unwrap(),
+            sleep_import::parse(
+                SleepImportFormat::Csv,
+                &data[..],
+                chrono_tz::America::New_York
+            )
+            .unwrap()
+        );
+    }
+}
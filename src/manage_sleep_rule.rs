@@ -0,0 +1,366 @@
+use chrono::{TimeZone, Timelike};
+use chrono_tz::Tz;
+use tokio_postgres::GenericClient;
+
+use crate::{
+    db_types::{
+        DoNotDisturb, SleepEvent, SleepEventKind, SleepRule, SleepRuleAction, SleepRuleActionKind,
+    },
+    do_not_disturb_service,
+    handlers::{self, AppError},
+    manage_contact, manage_user_message, request,
+    response::PushEvent,
+    sleep_event_service, sleep_rule_action_service, sleep_rule_service, user_message_service,
+    utils,
+};
+
+// a rule is meant to do a handful of things, not run a script
+const MAX_RULE_ACTIONS: usize = 16;
+
+// long enough for a goodnight note
+const MAX_PUSH_TEXT_CHARS: usize = 200;
+
+// do not disturb can't be turned on for longer than this at a time
+const MAX_DO_NOT_DISTURB_MILLIS: i64 = 24 * 60 * 60 * 1000;
+
+const MINUTES_PER_DAY: i64 = 24 * 60;
+
+// a window needs both ends and a time zone, and an empty one would never match
+fn check_window(props: &request::SleepRuleNewProps) -> Result<(), AppError> {
+    match (
+        &props.time_zone,
+        props.window_start_minute,
+        props.window_end_minute,
+    ) {
+        (None, None, None) => Ok(()),
+        (Some(time_zone), Some(start), Some(end)) => {
+            time_zone.parse::<Tz>().map_err(|e| {
+                log::info!("{}", e);
+                AppError::BadRequest
+            })?;
+            let in_day = |x: i64| (0..MINUTES_PER_DAY).contains(&x);
+            if !in_day(start) || !in_day(end) || start == end {
+                return Err(AppError::BadRequest);
+            }
+            Ok(())
+        }
+        _ => Err(AppError::BadRequest),
+    }
+}
+
+// whether the event happened inside the rule's window, in the rule's time zone.
+// a window ending before it starts wraps past midnight.
+fn in_window(rule: &SleepRule, time: i64) -> bool {
+    let (Some(time_zone), Some(start), Some(end)) = (
+        rule.time_zone.as_deref().and_then(|x| x.parse::<Tz>().ok()),
+        rule.window_start_minute,
+        rule.window_end_minute,
+    ) else {
+        return true;
+    };
+    let Some(local) = time_zone.timestamp_millis_opt(time).single() else {
+        return false;
+    };
+    let minute = (local.hour() * 60 + local.minute()) as i64;
+    if start < end {
+        start <= minute && minute < end
+    } else {
+        minute >= start || minute < end
+    }
+}
+
+// every action needs the fields its kind uses, and only those
+async fn check_action(
+    con: &mut impl GenericClient,
+    creator_user_id: i64,
+    kind: SleepEventKind,
+    props: &request::SleepRuleActionProps,
+) -> Result<(), AppError> {
+    match props.action {
+        SleepRuleActionKind::Push => {
+            let target_user_id = props.target_user_id.ok_or(AppError::BadRequest)?;
+            if props.user_message_id.is_some() || props.duration_millis.is_some() {
+                return Err(AppError::BadRequest);
+            }
+            if let Some(text_body) = &props.text_body {
+                let len = text_body.trim().chars().count();
+                if len == 0 || len > MAX_PUSH_TEXT_CHARS {
+                    return Err(AppError::BadRequest);
+                }
+            }
+            manage_contact::check_can_message(con, creator_user_id, target_user_id).await?;
+        }
+        SleepRuleActionKind::DeliverUserMessage => {
+            let user_message_id = props.user_message_id.ok_or(AppError::BadRequest)?;
+            if props.target_user_id.is_some()
+                || props.text_body.is_some()
+                || props.duration_millis.is_some()
+            {
+                return Err(AppError::BadRequest);
+            }
+            // it plays on the user's own devices, so it has to be a message to them that is in
+            // their inbox, or will be on their next wake event. the sender decides when it arrives:
+            // messages scheduled for later stay hidden until they're due
+            let um = user_message_service::get_by_user_message_id(con, user_message_id)
+                .await
+                .map_err(handlers::report_postgres_err)?
+                .ok_or(AppError::NotFound)?;
+            let reachable = um.target_deleted_time.is_none()
+                && (um.deliver_at.is_none()
+                    || manage_user_message::is_visible_to_target(
+                        &um,
+                        utils::current_time_millis(),
+                    ));
+            if um.target_user_id != creator_user_id || !reachable {
+                return Err(AppError::NotFound);
+            }
+        }
+        SleepRuleActionKind::DoNotDisturb => {
+            if props.target_user_id.is_some()
+                || props.text_body.is_some()
+                || props.user_message_id.is_some()
+            {
+                return Err(AppError::BadRequest);
+            }
+            match props.duration_millis {
+                Some(x) if x <= 0 || x > MAX_DO_NOT_DISTURB_MILLIS => {
+                    return Err(AppError::BadRequest)
+                }
+                // waking up would end it as soon as it started
+                None if kind == SleepEventKind::Wake => return Err(AppError::BadRequest),
+                _ => {}
+            }
+        }
+    }
+    Ok(())
+}
+
+pub async fn create_rule(
+    con: &mut tokio_postgres::Client,
+    creator_user_id: i64,
+    props: &request::SleepRuleNewProps,
+) -> Result<(SleepRule, Vec<SleepRuleAction>), AppError> {
+    check_window(props)?;
+    if props.actions.is_empty() || props.actions.len() > MAX_RULE_ACTIONS {
+        return Err(AppError::BadRequest);
+    }
+
+    let mut tx = con
+        .transaction()
+        .await
+        .map_err(handlers::report_postgres_err)?;
+
+    for action in props.actions.iter() {
+        check_action(&mut tx, creator_user_id, props.kind, action).await?;
+    }
+
+    let rule = sleep_rule_service::add(
+        &mut tx,
+        creator_user_id,
+        props.kind,
+        props.source,
+        props.time_zone.clone(),
+        props.window_start_minute,
+        props.window_end_minute,
+    )
+    .await
+    .map_err(handlers::report_postgres_err)?;
+
+    let mut actions = vec![];
+    for (position, action) in props.actions.iter().enumerate() {
+        let action = SleepRuleAction {
+            sleep_rule_id: rule.sleep_rule_id,
+            position: position as i64,
+            action: action.action,
+            target_user_id: action.target_user_id,
+            text_body: action.text_body.as_deref().map(|x| x.trim().to_string()),
+            user_message_id: action.user_message_id,
+            duration_millis: action.duration_millis,
+        };
+        sleep_rule_action_service::add(&mut tx, &action)
+            .await
+            .map_err(handlers::report_postgres_err)?;
+        actions.push(action);
+    }
+
+    tx.commit().await.map_err(handlers::report_postgres_err)?;
+    Ok((rule, actions))
+}
+
+// the user's rules, each with what it does
+pub async fn get_rules(
+    con: &mut impl GenericClient,
+    user_id: i64,
+) -> Result<Vec<(SleepRule, Vec<SleepRuleAction>)>, AppError> {
+    let rules = sleep_rule_service::get_by_creator_user_id(con, user_id)
+        .await
+        .map_err(handlers::report_postgres_err)?;
+
+    let mut result = vec![];
+    for rule in rules {
+        let actions = sleep_rule_action_service::get_by_sleep_rule_id(con, rule.sleep_rule_id)
+            .await
+            .map_err(handlers::report_postgres_err)?;
+        result.push((rule, actions));
+    }
+    Ok(result)
+}
+
+// only the user who made a rule may delete it
+pub async fn delete_rule(
+    con: &mut impl GenericClient,
+    user_id: i64,
+    sleep_rule_id: i64,
+) -> Result<SleepRule, AppError> {
+    let rule = sleep_rule_service::get_by_sleep_rule_id(con, sleep_rule_id)
+        .await
+        .map_err(handlers::report_postgres_err)?
+        .ok_or(AppError::NotFound)?;
+
+    if rule.creator_user_id != user_id {
+        return Err(AppError::NotFound);
+    }
+
+    sleep_rule_service::delete(con, sleep_rule_id)
+        .await
+        .map_err(handlers::report_postgres_err)?;
+    Ok(rule)
+}
+
+// carries out the user's rules that this event sets off, in the order they were made.
+// returns what to push once the event is committed.
+// pushes to users the user can no longer message, and messages that haven't reached the user
+// or that they've thrown out, are skipped.
+pub async fn run_rules(
+    con: &mut impl GenericClient,
+    sleep_event: &SleepEvent,
+) -> Result<Vec<(i64, PushEvent)>, AppError> {
+    let user_id = sleep_event.creator_user_id;
+
+    let rules =
+        sleep_rule_service::get_active_by_creator_user_id_and_kind(con, user_id, sleep_event.kind)
+            .await
+            .map_err(handlers::report_postgres_err)?;
+
+    let mut events = vec![];
+    for rule in rules {
+        if rule.source.is_some_and(|x| x != sleep_event.source)
+            || !in_window(&rule, sleep_event.creation_time)
+        {
+            continue;
+        }
+
+        let actions = sleep_rule_action_service::get_by_sleep_rule_id(con, rule.sleep_rule_id)
+            .await
+            .map_err(handlers::report_postgres_err)?;
+
+        for action in actions {
+            match action.action {
+                SleepRuleActionKind::Push => {
+                    let Some(target_user_id) = action.target_user_id else {
+                        continue;
+                    };
+                    match manage_contact::check_can_message(con, user_id, target_user_id).await {
+                        Ok(()) => events.push((
+                            target_user_id,
+                            PushEvent::SleepRuleNotice {
+                                sleep_rule_id: rule.sleep_rule_id,
+                                user_id,
+                                sleep_event_kind: sleep_event.kind,
+                                sleep_event_time: sleep_event.creation_time,
+                                text_body: action.text_body,
+                            },
+                        )),
+                        Err(AppError::Forbidden) => {}
+                        Err(e) => return Err(e),
+                    }
+                }
+                SleepRuleActionKind::DeliverUserMessage => {
+                    let Some(user_message_id) = action.user_message_id else {
+                        continue;
+                    };
+                    // only plays once it has reached the user, the sender's schedule is left alone
+                    let um = user_message_service::get_by_user_message_id(con, user_message_id)
+                        .await
+                        .map_err(handlers::report_postgres_err)?;
+                    if um.is_some_and(|x| {
+                        x.target_user_id == user_id
+                            && manage_user_message::is_visible_to_target(
+                                &x,
+                                utils::current_time_millis(),
+                            )
+                    }) {
+                        events.push((
+                            user_id,
+                            PushEvent::PlayUserMessage {
+                                sleep_rule_id: rule.sleep_rule_id,
+                                user_message_id,
+                            },
+                        ));
+                    }
+                }
+                SleepRuleActionKind::DoNotDisturb => {
+                    do_not_disturb_service::add(
+                        con,
+                        user_id,
+                        Some(rule.sleep_rule_id),
+                        action
+                            .duration_millis
+                            .map(|x| sleep_event.creation_time + x),
+                    )
+                    .await
+                    .map_err(handlers::report_postgres_err)?;
+                }
+            }
+        }
+    }
+    Ok(events)
+}
+
+// the user's do not disturb, if it's on right now
+pub async fn get_active_do_not_disturb(
+    con: &mut impl GenericClient,
+    user_id: i64,
+    current_time: i64,
+) -> Result<Option<DoNotDisturb>, tokio_postgres::Error> {
+    let Some(dnd) = do_not_disturb_service::get_recent_by_creator_user_id(con, user_id).await?
+    else {
+        return Ok(None);
+    };
+
+    let active = match dnd.end_time {
+        Some(end_time) => end_time > current_time,
+        // lasts until the user wakes up
+        None => {
+            let last_wake = sleep_event_service::get_recent_by_user_id_and_kind_before(
+                con,
+                user_id,
+                SleepEventKind::Wake,
+                i64::MAX,
+            )
+            .await?;
+            last_wake.is_none_or(|x| x.creation_time < dnd.creation_time)
+        }
+    };
+    Ok(Some(dnd).filter(|_| active))
+}
+
+// messages that would reach the target right away wait out their do not disturb instead.
+// messages the sender scheduled are left alone.
+pub async fn hold_deliver_at(
+    con: &mut impl GenericClient,
+    target_user_id: i64,
+    deliver_at: Option<i64>,
+) -> Result<Option<i64>, AppError> {
+    let now = utils::current_time_millis();
+    if deliver_at.is_some_and(|x| x <= now) {
+        if let Some(dnd) = get_active_do_not_disturb(con, target_user_id, now)
+            .await
+            .map_err(handlers::report_postgres_err)?
+        {
+            // none waits for the target's next wake event, like any other message
+            return Ok(dnd.end_time);
+        }
+    }
+    Ok(deliver_at)
+}
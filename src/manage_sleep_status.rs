@@ -0,0 +1,138 @@
+use tokio_postgres::GenericClient;
+
+use crate::{
+    db_types::{SleepEventKind, SleepStatusShare},
+    handlers::{self, AppError},
+    manage_contact,
+    manage_push::PushHub,
+    response::{PushEvent, SleepStatus},
+    sleep_event_service, sleep_status_share_service,
+};
+
+// sleep status events only go out over the sleep status feed
+pub fn is_sleep_status_event(x: &PushEvent) -> bool {
+    matches!(
+        x,
+        PushEvent::SleepStatusSnapshot { .. }
+            | PushEvent::SleepStatusChanged { .. }
+            | PushEvent::SleepStatusUnshared { .. }
+    )
+}
+
+// the user is asleep if their latest event is going to sleep
+async fn get_status(
+    con: &mut impl GenericClient,
+    user_id: i64,
+) -> Result<SleepStatus, tokio_postgres::Error> {
+    let latest = sleep_event_service::get_recent_by_user_id(con, user_id).await?;
+    Ok(SleepStatus {
+        user_id,
+        asleep: latest
+            .as_ref()
+            .is_some_and(|x| x.kind == SleepEventKind::Sleep),
+        since: latest.as_ref().map(|x| x.creation_time),
+        sleep_event_id: latest.as_ref().map(|x| x.sleep_event_id),
+    })
+}
+
+// tells everyone the user shares with what their status is now.
+// called once the change is committed, so failing here only means the feed misses it.
+pub async fn publish_status(con: &mut impl GenericClient, push_hub: &PushHub, user_id: i64) {
+    let result = async {
+        let sleep_status = get_status(con, user_id).await?;
+        let target_user_ids =
+            sleep_status_share_service::get_visible_target_user_ids(con, user_id).await?;
+        Ok::<_, tokio_postgres::Error>((sleep_status, target_user_ids))
+    }
+    .await;
+
+    match result {
+        Ok((sleep_status, target_user_ids)) => {
+            for target_user_id in target_user_ids {
+                push_hub.push(
+                    target_user_id,
+                    PushEvent::SleepStatusChanged {
+                        sleep_status: sleep_status.clone(),
+                    },
+                );
+            }
+        }
+        Err(e) => log::error!("couldn't publish sleep status of user {}: {}", user_id, e),
+    }
+}
+
+// starts or stops sharing the user's status with the target.
+// users may share with anybody they could message, and a share only counts while they still could.
+pub async fn set_share(
+    con: &mut impl GenericClient,
+    push_hub: &PushHub,
+    creator_user_id: i64,
+    target_user_id: i64,
+    active: bool,
+) -> Result<SleepStatusShare, AppError> {
+    if creator_user_id == target_user_id {
+        return Err(AppError::BadRequest);
+    }
+
+    if active {
+        manage_contact::check_can_message(con, creator_user_id, target_user_id).await?;
+    }
+
+    let share = sleep_status_share_service::add(con, creator_user_id, target_user_id, active)
+        .await
+        .map_err(handlers::report_postgres_err)?;
+
+    if active {
+        let sleep_status = get_status(con, creator_user_id)
+            .await
+            .map_err(handlers::report_postgres_err)?;
+        push_hub.push(
+            target_user_id,
+            PushEvent::SleepStatusChanged { sleep_status },
+        );
+    } else {
+        push_hub.push(
+            target_user_id,
+            PushEvent::SleepStatusUnshared {
+                user_id: creator_user_id,
+            },
+        );
+    }
+
+    Ok(share)
+}
+
+// a block hides shares in both directions
+pub fn on_blocked(push_hub: &PushHub, creator_user_id: i64, target_user_id: i64) {
+    for (user_id, other_user_id) in [
+        (creator_user_id, target_user_id),
+        (target_user_id, creator_user_id),
+    ] {
+        push_hub.push(
+            user_id,
+            PushEvent::SleepStatusUnshared {
+                user_id: other_user_id,
+            },
+        );
+    }
+}
+
+// the status of everyone sharing with the user, sent when their feed opens
+pub async fn get_shared_statuses(
+    con: &mut impl GenericClient,
+    user_id: i64,
+) -> Result<Vec<SleepStatus>, AppError> {
+    let shares = sleep_status_share_service::get_active_by_target_user_id(con, user_id)
+        .await
+        .map_err(handlers::report_postgres_err)?;
+
+    let mut sleep_statuses = vec![];
+    for share in shares {
+        sleep_statuses.push(
+            get_status(con, share.creator_user_id)
+                .await
+                .map_err(handlers::report_postgres_err)?,
+        );
+    }
+    Ok(sleep_statuses)
+}
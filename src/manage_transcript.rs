@@ -0,0 +1,82 @@
+use tokio_postgres::GenericClient;
+
+use crate::{
+    audio_blob_service, audio_transcript_service,
+    blob_store::BlobStore,
+    handlers::{self, AppError},
+    manage_job::{self, JobPayload},
+    transcriber::{Transcribe, Transcriber},
+    utils,
+};
+
+// queues up a transcription of the audio with this content.
+// call this inside the transaction that stores the message.
+pub async fn enqueue_transcription(
+    con: &mut impl GenericClient,
+    audio_sha256: String,
+) -> Result<(), AppError> {
+    manage_job::enqueue(
+        con,
+        &JobPayload::TranscribeAudio { audio_sha256 },
+        utils::current_time_millis(),
+    )
+    .await?;
+    Ok(())
+}
+
+// works out what was said in the audio with this content and stores it,
+// unless the audio has been deleted or was already transcribed
+pub async fn transcribe_audio(
+    pool: &deadpool_postgres::Pool,
+    blob_store: &BlobStore,
+    transcriber: &Transcriber,
+    audio_sha256: &str,
+) -> Result<(), AppError> {
+    let audio_blob = {
+        let con: &mut tokio_postgres::Client =
+            &mut *pool.get().await.map_err(handlers::report_pool_err)?;
+
+        let transcript =
+            audio_transcript_service::get_by_audio_sha256(con, audio_sha256.to_string())
+                .await
+                .map_err(handlers::report_postgres_err)?;
+        if transcript.is_some() {
+            return Ok(());
+        }
+
+        match audio_blob_service::get_by_audio_sha256(con, audio_sha256.to_string())
+            .await
+            .map_err(handlers::report_postgres_err)?
+        {
+            Some(audio_blob) => audio_blob,
+            None => return Ok(()),
+        }
+    };
+
+    // the connection goes back to the pool while the backend works, which can take a while
+    let audio = blob_store
+        .reader(&audio_blob.audio_blob_key)
+        .await
+        .map_err(handlers::report_blob_store_err)?;
+
+    let transcript = transcriber
+        .transcribe(audio)
+        .await
+        .map_err(handlers::report_transcription_err)?;
+
+    let con: &mut tokio_postgres::Client =
+        &mut *pool.get().await.map_err(handlers::report_pool_err)?;
+
+    audio_transcript_service::add(
+        con,
+        audio_blob.audio_sha256,
+        transcriber.name().to_string(),
+        transcript.text,
+        transcript.language,
+        transcript.confidence,
+    )
+    .await
+    .map_err(handlers::report_postgres_err)?;
+
+    Ok(())
+}
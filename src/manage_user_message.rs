@@ -3,14 +3,22 @@ use std::time::{Duration, Instant};
 use actix_web::web;
 use actix_ws::{CloseCode, CloseReason, Message, ProtocolError};
 
-
-use base64::Engine;
 use futures_util::StreamExt;
-use tokio_stream::wrappers::IntervalStream;
+use tokio_postgres::GenericClient;
+use tokio_stream::wrappers::{BroadcastStream, IntervalStream};
 
 use crate::{
+    audio_blob_service,
+    blob_store::{self, BlobInfo, BlobWriter},
+    conversation_service,
+    db_types::{AudioBlob, UserMessage},
     handlers::{self, AppError},
-    request, user_message_service, AppData,
+    manage_contact, manage_household,
+    manage_push::PushHub,
+    manage_sleep_rule, manage_transcript, request,
+    response::PushEvent,
+    upload_limits::UploadBudget,
+    user_message_service, utils, AppData,
 };
 
 /// How often heartbeat pings are sent.
@@ -21,13 +29,840 @@ const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
 /// How long before lack of client response causes a timeout.
 const CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
 
+// how many messages of a conversation are returned at once, unless the client asks for fewer
+const CONVERSATION_PAGE_SIZE: i64 = 50;
+const MAX_CONVERSATION_PAGE_SIZE: i64 = 200;
+
+// how many search results are returned, unless the client asks for fewer
+const SEARCH_PAGE_SIZE: i64 = 50;
+const MAX_SEARCH_PAGE_SIZE: i64 = 200;
+
+// a sender can always unsend within this long of sending, and afterwards only while it's unplayed
+const UNSEND_WINDOW_MILLIS: i64 = 5 * 60 * 1000;
+
+// text is meant for short notes, anything longer should be recorded
+const MAX_TEXT_BODY_CHARS: usize = 2000;
+
+// a message that has been checked and may be sent
+pub struct Submission {
+    pub creator_user_id: i64,
+    // everyone who gets a copy of the message
+    pub target_user_ids: Vec<i64>,
+    pub audio_duration_millis: Option<i64>,
+    // checksum the client says the audio has
+    pub audio_sha256: Option<String>,
+    // the whole message if there's no audio, a caption otherwise
+    pub text_body: Option<String>,
+    // none means on the target's next wake event
+    pub deliver_at: Option<i64>,
+    // deleted once the target has played it
+    pub ephemeral: bool,
+    pub reply_to_user_message_id: Option<i64>,
+    pub budget: UploadBudget,
+    // whether the audio should be transcribed once it's stored
+    pub transcribe: bool,
+}
+
+impl Submission {
+    // audio that doesn't match the client's checksum got corrupted along the way
+    pub fn check_checksum(&self, audio_sha256: &str) -> Result<(), AppError> {
+        match &self.audio_sha256 {
+            Some(expected) if !expected.eq_ignore_ascii_case(audio_sha256) => {
+                Err(AppError::ChecksumMismatch)
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+// merges a single target and a list of targets into one list without duplicates
+pub fn collect_target_user_ids(
+    target_user_id: Option<i64>,
+    target_user_ids: Option<Vec<i64>>,
+) -> Vec<i64> {
+    let mut ids = vec![];
+    for id in target_user_id
+        .into_iter()
+        .chain(target_user_ids.into_iter().flatten())
+    {
+        if !ids.contains(&id) {
+            ids.push(id);
+        }
+    }
+    ids
+}
+
+// parses a comma separated list of user ids from a query string
+pub fn parse_user_id_list(s: Option<&str>) -> Result<Option<Vec<i64>>, AppError> {
+    match s {
+        None => Ok(None),
+        Some(s) => s
+            .split(',')
+            .filter(|x| !x.is_empty())
+            .map(|x| x.trim().parse::<i64>().map_err(|_| AppError::BadRequest))
+            .collect::<Result<Vec<i64>, AppError>>()
+            .map(Some),
+    }
+}
+
+// what a client asked to send, before any of it has been checked
+pub struct SubmissionRequest {
+    pub api_key: String,
+    pub target_user_ids: Vec<i64>,
+    // every other member of this household gets a copy too
+    pub household_id: Option<i64>,
+    pub audio_duration_millis: Option<i64>,
+    pub audio_sha256: Option<String>,
+    pub text_body: Option<String>,
+    pub deliver_at: Option<i64>,
+    pub deliver_on_wake: bool,
+    pub ephemeral: bool,
+    pub reply_to_user_message_id: Option<i64>,
+}
+
+impl From<&request::UserMessageNewProps> for SubmissionRequest {
+    fn from(props: &request::UserMessageNewProps) -> SubmissionRequest {
+        SubmissionRequest {
+            api_key: props.api_key.clone(),
+            target_user_ids: vec![props.target_user_id],
+            household_id: None,
+            audio_duration_millis: props.duration_millis,
+            audio_sha256: props.audio_sha256.clone(),
+            text_body: props.text_body.clone(),
+            deliver_at: props.deliver_at,
+            deliver_on_wake: props.deliver_on_wake.unwrap_or(false),
+            ephemeral: props.ephemeral.unwrap_or(false),
+            reply_to_user_message_id: props.reply_to_user_message_id,
+        }
+    }
+}
+
+impl From<&request::UserMessageBroadcastProps> for SubmissionRequest {
+    fn from(props: &request::UserMessageBroadcastProps) -> SubmissionRequest {
+        SubmissionRequest {
+            api_key: props.api_key.clone(),
+            target_user_ids: collect_target_user_ids(None, props.target_user_ids.clone()),
+            household_id: props.household_id,
+            audio_duration_millis: props.duration_millis,
+            audio_sha256: props.audio_sha256.clone(),
+            text_body: props.text_body.clone(),
+            deliver_at: props.deliver_at,
+            deliver_on_wake: props.deliver_on_wake.unwrap_or(false),
+            ephemeral: props.ephemeral.unwrap_or(false),
+            reply_to_user_message_id: props.reply_to_user_message_id,
+        }
+    }
+}
+
+impl From<&request::UserMessageUploadProps> for SubmissionRequest {
+    fn from(props: &request::UserMessageUploadProps) -> SubmissionRequest {
+        SubmissionRequest {
+            api_key: props.api_key.clone(),
+            target_user_ids: vec![props.target_user_id],
+            household_id: None,
+            audio_duration_millis: props.duration_millis,
+            audio_sha256: props.audio_sha256.clone(),
+            text_body: props.text_body.clone(),
+            deliver_at: props.deliver_at,
+            deliver_on_wake: props.deliver_on_wake.unwrap_or(false),
+            ephemeral: props.ephemeral.unwrap_or(false),
+            reply_to_user_message_id: props.reply_to_user_message_id,
+        }
+    }
+}
+
+impl TryFrom<&request::UserMessageSubmitProps> for SubmissionRequest {
+    type Error = AppError;
+    fn try_from(props: &request::UserMessageSubmitProps) -> Result<SubmissionRequest, AppError> {
+        Ok(SubmissionRequest {
+            api_key: props.api_key.clone(),
+            target_user_ids: collect_target_user_ids(
+                props.target_user_id,
+                parse_user_id_list(props.target_user_ids.as_deref())?,
+            ),
+            household_id: props.household_id,
+            audio_duration_millis: props.duration_millis,
+            audio_sha256: props.audio_sha256.clone(),
+            text_body: props.text_body.clone(),
+            deliver_at: props.deliver_at,
+            deliver_on_wake: props.deliver_on_wake.unwrap_or(false),
+            ephemeral: props.ephemeral.unwrap_or(false),
+            reply_to_user_message_id: props.reply_to_user_message_id,
+        })
+    }
+}
+
+// works out when a message should be delivered.
+// none means on the target's next wake event, and times in the past mean right away.
+fn resolve_deliver_at(
+    deliver_at: Option<i64>,
+    deliver_on_wake: bool,
+) -> Result<Option<i64>, AppError> {
+    let now = utils::current_time_millis();
+    match (deliver_at, deliver_on_wake) {
+        (Some(_), true) => Err(AppError::BadRequest),
+        (None, true) => Ok(None),
+        (Some(deliver_at), false) => Ok(Some(deliver_at.max(now))),
+        (None, false) => Ok(Some(now)),
+    }
+}
+
+// whether the target may see the message yet
+pub fn is_delivered(user_message: &UserMessage, current_time: i64) -> bool {
+    matches!(user_message.deliver_at, Some(deliver_at) if deliver_at <= current_time)
+}
+
+// whether the target may see the message: it has to be delivered and still in their inbox
+pub fn is_visible_to_target(user_message: &UserMessage, current_time: i64) -> bool {
+    is_delivered(user_message, current_time) && user_message.target_deleted_time.is_none()
+}
+
+// surrounding whitespace is dropped, and what's left has to be short and not empty
+fn check_text_body(text_body: Option<String>) -> Result<Option<String>, AppError> {
+    match text_body.as_deref().map(str::trim) {
+        None => Ok(None),
+        Some(x) if x.is_empty() || x.chars().count() > MAX_TEXT_BODY_CHARS => {
+            Err(AppError::BadRequest)
+        }
+        Some(x) => Ok(Some(x.to_string())),
+    }
+}
+
+// the message, as long as the user sent it or it's in their inbox
+pub async fn get_visible_user_message(
+    con: &mut impl GenericClient,
+    user_id: i64,
+    user_message_id: i64,
+) -> Result<UserMessage, AppError> {
+    let um = user_message_service::get_by_user_message_id(con, user_message_id)
+        .await
+        .map_err(handlers::report_postgres_err)?
+        .ok_or(AppError::NotFound)?;
+
+    let is_target =
+        um.target_user_id == user_id && is_visible_to_target(&um, utils::current_time_millis());
+    if um.creator_user_id != user_id && !is_target {
+        return Err(AppError::NotFound);
+    }
+    Ok(um)
+}
+
+// a reply has to answer a message the user can see, in a conversation with the targets
+async fn check_reply_to(
+    con: &mut impl GenericClient,
+    user_id: i64,
+    target_user_ids: &[i64],
+    reply_to_user_message_id: i64,
+) -> Result<(), AppError> {
+    let um = get_visible_user_message(con, user_id, reply_to_user_message_id).await?;
+
+    let in_conversation = |x: &i64| *x == um.creator_user_id || *x == um.target_user_id;
+    if !target_user_ids.iter().all(in_conversation) {
+        return Err(AppError::BadRequest);
+    }
+    Ok(())
+}
+
+// every ingest path goes through here before accepting any audio
+pub async fn authorize_submission(
+    data: &AppData,
+    req: SubmissionRequest,
+) -> Result<Submission, AppError> {
+    // validate api key
+    let user = handlers::get_user_if_api_key_valid(&data.auth_service, req.api_key).await?;
+
+    let deliver_at = resolve_deliver_at(req.deliver_at, req.deliver_on_wake)?;
+
+    let text_body = check_text_body(req.text_body)?;
+
+    let con: &mut tokio_postgres::Client =
+        &mut *data.pool.get().await.map_err(handlers::report_pool_err)?;
+
+    let mut target_user_ids = req.target_user_ids;
+
+    // sending to a household sends to everyone else in it
+    if let Some(household_id) = req.household_id {
+        let member_ids =
+            manage_household::get_other_member_ids(con, household_id, user.user_id).await?;
+        target_user_ids =
+            collect_target_user_ids(None, Some([target_user_ids, member_ids].concat()));
+    }
+
+    // a message has to go to somebody
+    if target_user_ids.is_empty() {
+        return Err(AppError::BadRequest);
+    }
+
+    // validate that the other users exist in the first place
+    for target_user_id in target_user_ids.iter() {
+        data.auth_service
+            .get_user_by_id(*target_user_id)
+            .await
+            .map_err(handlers::report_auth_err)?;
+    }
+
+    // and that they want to hear from us
+    for target_user_id in target_user_ids.iter() {
+        manage_contact::check_can_message(con, user.user_id, *target_user_id).await?;
+    }
+
+    if let Some(reply_to_user_message_id) = req.reply_to_user_message_id {
+        check_reply_to(
+            con,
+            user.user_id,
+            &target_user_ids,
+            reply_to_user_message_id,
+        )
+        .await?;
+    }
+
+    let budget = data.upload_limits.budget_for(con, user.user_id).await?;
+
+    // reject up front if the client already told us it's too long
+    if let Some(duration_millis) = req.audio_duration_millis {
+        budget.check_duration(duration_millis)?;
+    }
+
+    Ok(Submission {
+        creator_user_id: user.user_id,
+        target_user_ids,
+        audio_duration_millis: req.audio_duration_millis,
+        audio_sha256: req.audio_sha256,
+        text_body,
+        deliver_at,
+        ephemeral: req.ephemeral,
+        reply_to_user_message_id: req.reply_to_user_message_id,
+        budget,
+        transcribe: data.transcriber.is_some(),
+    })
+}
+
+// records one message per target in the database, all pointing at the audio blob with this content.
+// the caller must already hold one reference to the blob per target.
+async fn record_user_messages(
+    con: &mut impl GenericClient,
+    submission: &Submission,
+    audio_blob: Option<AudioBlob>,
+    audio_duration_millis: Option<i64>,
+) -> Result<Vec<UserMessage>, AppError> {
+    let new = user_message_service::NewUserMessage {
+        creator_user_id: submission.creator_user_id,
+        audio_blob: audio_blob.as_ref(),
+        audio_duration_millis,
+        text_body: submission.text_body.as_deref(),
+        deliver_at: submission.deliver_at,
+        ephemeral: submission.ephemeral,
+        reply_to_user_message_id: submission.reply_to_user_message_id,
+    };
+    let mut user_messages = vec![];
+    for target_user_id in submission.target_user_ids.iter() {
+        let new = user_message_service::NewUserMessage {
+            deliver_at: manage_sleep_rule::hold_deliver_at(con, *target_user_id, new.deliver_at)
+                .await?,
+            ..new
+        };
+        let um = user_message_service::add(con, &new, *target_user_id)
+            .await
+            .map_err(handlers::report_postgres_err)?;
+        user_messages.push(um);
+    }
+
+    if let Some(audio_blob) = audio_blob.filter(|_| submission.transcribe) {
+        manage_transcript::enqueue_transcription(con, audio_blob.audio_sha256).await?;
+    }
+
+    Ok(user_messages)
+}
+
+// gives back the message's reference to its audio, if it has any
+pub async fn release_audio(
+    con: &mut impl GenericClient,
+    user_message: &UserMessage,
+) -> Result<(), tokio_postgres::Error> {
+    match &user_message.audio_sha256 {
+        Some(audio_sha256) => audio_blob_service::release(con, audio_sha256.clone()).await,
+        None => Ok(()),
+    }
+}
+
+// audio being received for a submission, written out to the blob store as it arrives
+pub struct AudioUpload {
+    submission: Submission,
+    audio_blob_key: String,
+    writer: BlobWriter,
+}
+
+impl AudioUpload {
+    pub async fn start(data: &AppData, submission: Submission) -> Result<AudioUpload, AppError> {
+        let audio_blob_key = blob_store::new_audio_key();
+        let writer = data
+            .blob_store
+            .writer(&audio_blob_key)
+            .await
+            .map_err(handlers::report_blob_store_err)?;
+        Ok(AudioUpload {
+            submission,
+            audio_blob_key,
+            writer,
+        })
+    }
+
+    pub fn submission(&self) -> &Submission {
+        &self.submission
+    }
+
+    pub async fn write(&mut self, chunk: &[u8]) -> Result<(), AppError> {
+        self.submission
+            .budget
+            .check_size(self.writer.size() + chunk.len() as i64)?;
+        self.writer
+            .write(chunk)
+            .await
+            .map_err(handlers::report_blob_store_err)
+    }
+
+    // measured_duration_millis is used if the client didn't say how long the audio is
+    pub async fn finish(
+        self,
+        data: &AppData,
+        measured_duration_millis: Option<i64>,
+    ) -> Result<Vec<UserMessage>, AppError> {
+        let audio_duration_millis = self
+            .submission
+            .audio_duration_millis
+            .or(measured_duration_millis);
+
+        if let Some(duration_millis) = audio_duration_millis {
+            if let Err(e) = self.submission.budget.check_duration(duration_millis) {
+                self.abort().await;
+                return Err(e);
+            }
+        }
+
+        let audio = self
+            .writer
+            .finish()
+            .await
+            .map_err(handlers::report_blob_store_err)?;
+
+        let result = match self.submission.check_checksum(&audio.sha256) {
+            Ok(()) => match data.pool.get().await {
+                Ok(mut obj) => {
+                    let con: &mut tokio_postgres::Client = &mut *obj;
+                    AudioUpload::record(
+                        con,
+                        &self.submission,
+                        self.audio_blob_key.clone(),
+                        audio,
+                        audio_duration_millis,
+                    )
+                    .await
+                }
+                Err(e) => Err(handlers::report_pool_err(e)),
+            },
+            Err(e) => Err(e),
+        };
+
+        // our copy of the audio isn't needed if we already had it, or if the row couldn't be written
+        match result.as_ref().map(|ums| ums.first()) {
+            Ok(Some(um)) if um.audio_blob_key.as_ref() == Some(&self.audio_blob_key) => {}
+            _ => {
+                let _ = data.blob_store.delete(&self.audio_blob_key).await;
+            }
+        }
+
+        result
+    }
+
+    async fn record(
+        con: &mut tokio_postgres::Client,
+        submission: &Submission,
+        audio_blob_key: String,
+        audio: BlobInfo,
+        audio_duration_millis: Option<i64>,
+    ) -> Result<Vec<UserMessage>, AppError> {
+        let mut tx = con
+            .transaction()
+            .await
+            .map_err(handlers::report_postgres_err)?;
+        let audio_blob = audio_blob_service::acquire(
+            &mut tx,
+            audio.sha256,
+            audio_blob_key,
+            audio.size,
+            submission.target_user_ids.len() as i64,
+        )
+        .await
+        .map_err(handlers::report_postgres_err)?;
+        let ums =
+            record_user_messages(&mut tx, submission, Some(audio_blob), audio_duration_millis)
+                .await?;
+        tx.commit().await.map_err(handlers::report_postgres_err)?;
+        Ok(ums)
+    }
+
+    pub async fn abort(self) {
+        let _ = self.writer.abort().await;
+    }
+}
+
+// stores audio we already have in memory
+pub async fn add_user_message(
+    data: &AppData,
+    submission: Submission,
+    audio_data: &[u8],
+) -> Result<Vec<UserMessage>, AppError> {
+    submission.budget.check_size(audio_data.len() as i64)?;
+
+    let audio_sha256 = utils::sha256_hex(audio_data);
+    submission.check_checksum(&audio_sha256)?;
+
+    // if we already have this exact audio, there's no need to store it again
+    {
+        let con: &mut tokio_postgres::Client =
+            &mut *data.pool.get().await.map_err(handlers::report_pool_err)?;
+        let mut tx = con
+            .transaction()
+            .await
+            .map_err(handlers::report_postgres_err)?;
+        if let Some(audio_blob) = audio_blob_service::acquire_existing(
+            &mut tx,
+            audio_sha256,
+            submission.target_user_ids.len() as i64,
+        )
+        .await
+        .map_err(handlers::report_postgres_err)?
+        {
+            let ums = record_user_messages(
+                &mut tx,
+                &submission,
+                Some(audio_blob),
+                submission.audio_duration_millis,
+            )
+            .await?;
+            tx.commit().await.map_err(handlers::report_postgres_err)?;
+            return Ok(ums);
+        }
+    }
+
+    let mut upload = AudioUpload::start(data, submission).await?;
+    if let Err(e) = upload.write(audio_data).await {
+        upload.abort().await;
+        return Err(e);
+    }
+    upload.finish(data, None).await
+}
+
+// a message that is only text, so there's nothing to store besides the rows
+pub async fn add_text_user_message(
+    data: &AppData,
+    submission: Submission,
+) -> Result<Vec<UserMessage>, AppError> {
+    if submission.text_body.is_none() {
+        return Err(AppError::BadRequest);
+    }
+
+    let con: &mut tokio_postgres::Client =
+        &mut *data.pool.get().await.map_err(handlers::report_pool_err)?;
+    let mut tx = con
+        .transaction()
+        .await
+        .map_err(handlers::report_postgres_err)?;
+    let ums = record_user_messages(&mut tx, &submission, None, None).await?;
+    tx.commit().await.map_err(handlers::report_postgres_err)?;
+    Ok(ums)
+}
+
+// only the sender may change when a message they scheduled is delivered
+pub async fn reschedule_user_message(
+    con: &mut impl GenericClient,
+    user_id: i64,
+    user_message_id: i64,
+    deliver_at: Option<i64>,
+    deliver_on_wake: bool,
+) -> Result<UserMessage, AppError> {
+    let um = user_message_service::get_by_user_message_id(con, user_message_id)
+        .await
+        .map_err(handlers::report_postgres_err)?
+        .ok_or(AppError::NotFound)?;
+
+    if um.creator_user_id != user_id {
+        return Err(AppError::Forbidden);
+    }
+
+    let deliver_at = resolve_deliver_at(deliver_at, deliver_on_wake)?;
+
+    // too late once it has been delivered
+    user_message_service::reschedule(
+        con,
+        user_message_id,
+        deliver_at,
+        utils::current_time_millis(),
+    )
+    .await
+    .map_err(handlers::report_postgres_err)?
+    .ok_or(AppError::BadRequest)
+}
+
+// withdraws a scheduled message before it's delivered
+pub async fn cancel_user_message(
+    con: &mut tokio_postgres::Client,
+    user_id: i64,
+    user_message_id: i64,
+) -> Result<UserMessage, AppError> {
+    let mut tx = con
+        .transaction()
+        .await
+        .map_err(handlers::report_postgres_err)?;
+
+    let um = user_message_service::get_by_user_message_id(&mut tx, user_message_id)
+        .await
+        .map_err(handlers::report_postgres_err)?
+        .ok_or(AppError::NotFound)?;
+
+    if um.creator_user_id != user_id {
+        return Err(AppError::Forbidden);
+    }
+
+    // too late once it has been delivered
+    let um = user_message_service::delete_scheduled(
+        &mut tx,
+        user_message_id,
+        utils::current_time_millis(),
+    )
+    .await
+    .map_err(handlers::report_postgres_err)?
+    .ok_or(AppError::BadRequest)?;
+
+    release_audio(&mut tx, &um)
+        .await
+        .map_err(handlers::report_postgres_err)?;
+
+    tx.commit().await.map_err(handlers::report_postgres_err)?;
+    Ok(um)
+}
+
+// pages through the messages between the user and somebody else, in either direction
+pub async fn get_conversation_page(
+    con: &mut impl GenericClient,
+    user_id: i64,
+    props: &request::ConversationPageProps,
+) -> Result<Vec<UserMessage>, AppError> {
+    if props.before_user_message_id.is_some() && props.after_user_message_id.is_some() {
+        return Err(AppError::BadRequest);
+    }
+
+    let limit = props
+        .limit
+        .unwrap_or(CONVERSATION_PAGE_SIZE)
+        .clamp(1, MAX_CONVERSATION_PAGE_SIZE);
+
+    conversation_service::get_page(
+        con,
+        user_id,
+        props.other_user_id,
+        utils::current_time_millis(),
+        props.before_user_message_id,
+        props.after_user_message_id,
+        limit,
+    )
+    .await
+    .map_err(handlers::report_postgres_err)
+}
+
+// finds messages by what they say, among those the user can see
+pub async fn search_user_messages(
+    con: &mut impl GenericClient,
+    user_id: i64,
+    props: &request::UserMessageSearchProps,
+) -> Result<Vec<UserMessage>, AppError> {
+    if props.query.trim().is_empty() {
+        return Err(AppError::BadRequest);
+    }
+
+    let limit = props
+        .limit
+        .unwrap_or(SEARCH_PAGE_SIZE)
+        .clamp(1, MAX_SEARCH_PAGE_SIZE);
+
+    user_message_service::search(con, user_id, utils::current_time_millis(), props, limit)
+        .await
+        .map_err(handlers::report_postgres_err)
+}
+
+// the target tells us they've listened to the whole message.
+// ephemeral messages are deleted right away.
+pub async fn play_user_message(
+    con: &mut tokio_postgres::Client,
+    user_id: i64,
+    user_message_id: i64,
+) -> Result<UserMessage, AppError> {
+    let mut tx = con
+        .transaction()
+        .await
+        .map_err(handlers::report_postgres_err)?;
+
+    let um = user_message_service::get_by_user_message_id(&mut tx, user_message_id)
+        .await
+        .map_err(handlers::report_postgres_err)?
+        .ok_or(AppError::NotFound)?;
+
+    // scheduled messages stay hidden until they're due
+    if um.target_user_id != user_id || !is_visible_to_target(&um, utils::current_time_millis()) {
+        return Err(AppError::NotFound);
+    }
+
+    let um =
+        user_message_service::mark_played(&mut tx, user_message_id, utils::current_time_millis())
+            .await
+            .map_err(handlers::report_postgres_err)?
+            .ok_or(AppError::NotFound)?;
+
+    if um.ephemeral {
+        user_message_service::delete(&mut tx, user_message_id)
+            .await
+            .map_err(handlers::report_postgres_err)?;
+        release_audio(&mut tx, &um)
+            .await
+            .map_err(handlers::report_postgres_err)?;
+    }
+
+    tx.commit().await.map_err(handlers::report_postgres_err)?;
+    Ok(um)
+}
+
+// removes the message for good and releases its audio.
+// every device of both users is told to stop playing it.
+async fn delete_user_message(
+    con: &mut tokio_postgres::Client,
+    push_hub: &PushHub,
+    um: &UserMessage,
+) -> Result<(), AppError> {
+    let mut tx = con
+        .transaction()
+        .await
+        .map_err(handlers::report_postgres_err)?;
+
+    // somebody else got to it first
+    user_message_service::delete(&mut tx, um.user_message_id)
+        .await
+        .map_err(handlers::report_postgres_err)?
+        .ok_or(AppError::NotFound)?;
+
+    release_audio(&mut tx, um)
+        .await
+        .map_err(handlers::report_postgres_err)?;
+
+    tx.commit().await.map_err(handlers::report_postgres_err)?;
+
+    for user_id in [um.creator_user_id, um.target_user_id] {
+        push_hub.push(
+            user_id,
+            PushEvent::UserMessageRevoked {
+                user_message_id: um.user_message_id,
+            },
+        );
+    }
+    Ok(())
+}
+
+// the sender takes a message back, as long as the target hasn't played it or it was only just sent
+pub async fn unsend_user_message(
+    con: &mut tokio_postgres::Client,
+    push_hub: &PushHub,
+    user_id: i64,
+    user_message_id: i64,
+) -> Result<UserMessage, AppError> {
+    let um = user_message_service::get_by_user_message_id(con, user_message_id)
+        .await
+        .map_err(handlers::report_postgres_err)?
+        .ok_or(AppError::NotFound)?;
+
+    if um.creator_user_id != user_id {
+        return Err(AppError::Forbidden);
+    }
+
+    let in_window = utils::current_time_millis() - um.creation_time <= UNSEND_WINDOW_MILLIS;
+    if um.played_time.is_some() && !in_window {
+        return Err(AppError::BadRequest);
+    }
+
+    delete_user_message(con, push_hub, &um).await?;
+    Ok(um)
+}
+
+// the target removes a message from their inbox. the sender still has it.
+pub async fn delete_user_message_from_inbox(
+    con: &mut impl GenericClient,
+    push_hub: &PushHub,
+    user_id: i64,
+    user_message_id: i64,
+) -> Result<UserMessage, AppError> {
+    let um = user_message_service::get_by_user_message_id(con, user_message_id)
+        .await
+        .map_err(handlers::report_postgres_err)?
+        .ok_or(AppError::NotFound)?;
+
+    if um.target_user_id != user_id || !is_visible_to_target(&um, utils::current_time_millis()) {
+        return Err(AppError::NotFound);
+    }
+
+    let um =
+        user_message_service::delete_from_inbox(con, user_message_id, utils::current_time_millis())
+            .await
+            .map_err(handlers::report_postgres_err)?
+            .ok_or(AppError::NotFound)?;
+
+    // the target's other devices shouldn't keep playing it
+    push_hub.push(
+        um.target_user_id,
+        PushEvent::UserMessageRevoked {
+            user_message_id: um.user_message_id,
+        },
+    );
+    Ok(um)
+}
+
+// removes any message, for moderation. the caller must already have checked the user is an admin.
+pub async fn admin_delete_user_message(
+    con: &mut tokio_postgres::Client,
+    push_hub: &PushHub,
+    user_message_id: i64,
+) -> Result<UserMessage, AppError> {
+    let um = user_message_service::get_by_user_message_id(con, user_message_id)
+        .await
+        .map_err(handlers::report_postgres_err)?
+        .ok_or(AppError::NotFound)?;
+
+    delete_user_message(con, push_hub, &um).await?;
+    Ok(um)
+}
+
+// tells the client which limit it ran into
+fn close_reason_for(e: AppError) -> CloseReason {
+    let code = match e {
+        AppError::MessageTooLarge | AppError::QuotaExceeded => CloseCode::Size,
+        AppError::MessageTooLong => CloseCode::Policy,
+        AppError::ChecksumMismatch => CloseCode::Invalid,
+        _ => CloseCode::Error,
+    };
+    CloseReason {
+        code,
+        description: Some(e.to_string()),
+    }
+}
+
 pub async fn submit_user_message_ws(
     data: web::Data<AppData>,
     mut session: actix_ws::Session,
     msg_stream: actix_ws::MessageStream,
-    query: web::Query<request::UserMessageSubmitProps>,
+    mut upload: AudioUpload,
 ) {
-    let mut audio_data: Vec<u8> = vec![];
+    // how long the client has been sending audio for
+    let mut started: Option<Instant> = None;
+
+    // only a message that the client finished sending is kept
+    let mut completed = false;
 
     let mut last_heartbeat = Instant::now();
 
@@ -58,9 +893,18 @@ pub async fn submit_user_message_ws(
                     }
                     Message::Binary(data) => {
                         last_heartbeat = Instant::now();
-                        audio_data.extend(data);
+                        let started = *started.get_or_insert_with(Instant::now);
+                        if let Err(e) = upload.write(&data).await {
+                            break Some(close_reason_for(e));
+                        }
+                        if let Err(e) = check_recording_duration(&upload, started) {
+                            break Some(close_reason_for(e));
+                        }
+                    }
+                    Message::Close(_) => {
+                        completed = true;
+                        break None;
                     }
-                    Message::Close(_) => break None,
                     Message::Ping(bytes) => {
                         last_heartbeat = Instant::now();
                         let _ = session.pong(&bytes).await;
@@ -93,24 +937,56 @@ pub async fn submit_user_message_ws(
                         description: Some(String::from("server: timed out")),
                     });
                 }
+                if let Some(started) = started {
+                    if let Err(e) = check_recording_duration(&upload, started) {
+                        break Some(close_reason_for(e));
+                    }
+                }
                 // send heartbeat ping
                 let _ = session.ping(b"").await;
             }
         }
     };
 
-    // open db connection
-    if let Ok(mut obj) = data.pool.get().await.map_err(handlers::report_pool_err) {
-        let conn: &mut tokio_postgres::Client = &mut *obj;
-        let _ = user_message_service::add(&mut *conn, 1, 1, audio_data)
-            .await
-            .map_err(handlers::report_postgres_err);
-    }
+    let reason = if completed {
+        let measured_duration_millis = started.map(|s| s.elapsed().as_millis() as i64);
+        match upload.finish(&data, measured_duration_millis).await {
+            Ok(ums) => {
+                // let the client know which messages were created
+                let resp: Vec<_> = ums
+                    .into_iter()
+                    .map(|x| {
+                        handlers::fill_user_message(x, handlers::UserMessageMetadata::default())
+                    })
+                    .collect();
+                if let Ok(text) = serde_json::to_string(&resp) {
+                    let _ = session.text(text).await;
+                }
+                reason
+            }
+            Err(e) => Some(close_reason_for(e)),
+        }
+    } else {
+        upload.abort().await;
+        reason
+    };
 
     // attempt to close connection gracefully
     let _ = session.close(reason).await;
 }
 
+// a live recording is cut off once it runs past the duration limit,
+// unless the client told us up front how long its audio is
+fn check_recording_duration(upload: &AudioUpload, started: Instant) -> Result<(), AppError> {
+    match upload.submission().audio_duration_millis {
+        Some(_) => Ok(()),
+        None => upload
+            .submission()
+            .budget
+            .check_duration(started.elapsed().as_millis() as i64),
+    }
+}
+
 const BLOCK_INTERVAL: Duration = Duration::from_millis(10);
 const BLOCK_SIZE: usize = 1024;
 
@@ -119,8 +995,12 @@ pub async fn receive_user_message_ws(
     data: web::Data<AppData>,
     mut session: actix_ws::Session,
     msg_stream: actix_ws::MessageStream,
+    user_id: i64,
     query: web::Query<request::UserMessageReceiveProps>,
 ) {
+    // subscribe before looking the message up, so we can't miss it being revoked
+    let revocations = data.push_hub.subscribe(user_id);
+
     // open db connection
     let val = match data.pool.get().await {
         Ok(mut obj) => {
@@ -128,7 +1008,17 @@ pub async fn receive_user_message_ws(
             match user_message_service::get_by_user_message_id(&mut *conn, query.user_message_id)
                 .await
             {
-                Ok(Some(v)) => Ok(v),
+                // only the people in the conversation may listen, and the target only while
+                // it's in their inbox: scheduled messages stay hidden until they're due
+                Ok(Some(v)) => {
+                    let is_target = v.target_user_id == user_id
+                        && is_visible_to_target(&v, utils::current_time_millis());
+                    if v.creator_user_id == user_id || is_target {
+                        Ok(v)
+                    } else {
+                        Err(AppError::Unauthorized)
+                    }
+                }
                 Ok(None) => Err(AppError::NotFound),
                 Err(e) => Err(handlers::report_postgres_err(e)),
             }
@@ -136,8 +1026,38 @@ pub async fn receive_user_message_ws(
         Err(e) => Err(handlers::report_pool_err(e)),
     };
 
-    let audio_data = match val {
-        Ok(a) => a.audio_data,
+    // any text goes out first, so it can be shown while the audio plays.
+    // then open the audio in the blob store, chunks are read from it as they're sent
+    let val = match val {
+        Ok(um) => {
+            if let Some(text_body) = um.text_body {
+                let _ = session.text(text_body).await;
+            }
+            match um.audio_blob_key {
+                Some(audio_blob_key) if !query.display_only.unwrap_or(false) => data
+                    .blob_store
+                    .reader(&audio_blob_key)
+                    .await
+                    .map(Some)
+                    .map_err(handlers::report_blob_store_err),
+                // text messages, and devices that can't play audio, get nothing more
+                _ => Ok(None),
+            }
+        }
+        Err(e) => Err(e),
+    };
+
+    let mut reader = match val {
+        Ok(Some(r)) => r,
+        Ok(None) => {
+            let _ = session
+                .close(Some(CloseReason {
+                    code: CloseCode::Normal,
+                    description: None,
+                }))
+                .await;
+            return;
+        }
         Err(e) => {
             let _ = session
                 .close(Some(CloseReason {
@@ -149,13 +1069,13 @@ pub async fn receive_user_message_ws(
         }
     };
 
-    let mut audio_data = audio_data.chunks(BLOCK_SIZE);
-
     enum TaskUpdateKind {
         // we received a message from the client
         ClientMessage(Result<Message, ProtocolError>),
         // we need to
         NeedToSendData,
+        // something happened that devices should hear about
+        Push(Option<PushEvent>),
     }
 
     let heartbeat_stream = IntervalStream::new(tokio::time::interval(BLOCK_INTERVAL))
@@ -163,7 +1083,10 @@ pub async fn receive_user_message_ws(
 
     let client_message_stream = msg_stream.map(|x| TaskUpdateKind::ClientMessage(x));
 
-    let mut joint_stream = futures_util::stream_select!(heartbeat_stream, client_message_stream,);
+    let push_stream = BroadcastStream::new(revocations).map(|x| TaskUpdateKind::Push(x.ok()));
+
+    let mut joint_stream =
+        futures_util::stream_select!(heartbeat_stream, client_message_stream, push_stream,);
 
     let reason = loop {
         match joint_stream.next().await.unwrap() {
@@ -190,15 +1113,29 @@ pub async fn receive_user_message_ws(
                 log::error!("{}", err);
                 break None;
             }
+            // stop sending a message that was unsent or deleted meanwhile
+            TaskUpdateKind::Push(Some(PushEvent::UserMessageRevoked { user_message_id }))
+                if user_message_id == query.user_message_id =>
+            {
+                break Some(CloseReason {
+                    code: CloseCode::Normal,
+                    description: Some(AppError::NotFound.to_string()),
+                });
+            }
+            TaskUpdateKind::Push(_) => {}
             // heartbeat interval ticked
-            TaskUpdateKind::NeedToSendData => {
-                if let Some(chunk) = audio_data.next() {
-                    let chunk: Vec<u8> = chunk.into();
+            TaskUpdateKind::NeedToSendData => match reader.read(BLOCK_SIZE).await {
+                Ok(Some(chunk)) => {
                     let _ = session.binary(chunk).await;
-                } else {
-                    break None;
                 }
-            }
+                Ok(None) => break None,
+                Err(e) => {
+                    break Some(CloseReason {
+                        code: CloseCode::Error,
+                        description: Some(handlers::report_blob_store_err(e).to_string()),
+                    });
+                }
+            },
         }
     };
 
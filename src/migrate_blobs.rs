@@ -0,0 +1,67 @@
+use crate::blob_store::{self, BlobStore};
+
+// how many messages to move per round trip
+const BATCH_SIZE: i64 = 16;
+
+// moves audio that still lives in the legacy `audio_data` column out into the blob store.
+// each message is updated in its own statement, so the migration can be interrupted and resumed.
+pub async fn migrate_blobs(
+    pool: &deadpool_postgres::Pool,
+    blob_store: &BlobStore,
+) -> Result<(), Box<dyn std::error::Error + 'static>> {
+    let con: &mut tokio_postgres::Client = &mut *pool.get().await?;
+
+    let mut migrated: u64 = 0;
+
+    loop {
+        let rows = con
+            .query(
+                "SELECT user_message_id, audio_data
+                 FROM user_message
+                 WHERE audio_blob_key IS NULL
+                 ORDER BY user_message_id
+                 LIMIT $1",
+                &[&BATCH_SIZE],
+            )
+            .await?;
+
+        if rows.is_empty() {
+            break;
+        }
+
+        for row in rows {
+            let user_message_id: i64 = row.get("user_message_id");
+            let audio_data: Vec<u8> = row.get("audio_data");
+
+            let audio_blob_key = blob_store::new_audio_key();
+            let audio = blob_store.put(&audio_blob_key, &audio_data).await?;
+
+            con.execute(
+                "UPDATE user_message
+                 SET audio_blob_key = $2,
+                     audio_size = $3,
+                     audio_sha256 = $4,
+                     audio_data = NULL
+                 WHERE user_message_id = $1",
+                &[
+                    &user_message_id,
+                    &audio_blob_key,
+                    &audio.size,
+                    &audio.sha256,
+                ],
+            )
+            .await?;
+
+            migrated += 1;
+        }
+
+        log::info!("migrated {} user messages so far", migrated);
+    }
+
+    log::info!(
+        "finished migrating {} user messages, it is now safe to drop user_message.audio_data",
+        migrated
+    );
+
+    Ok(())
+}
@@ -0,0 +1,284 @@
+use std::str::FromStr;
+
+use chrono::{DateTime, Datelike, Duration, NaiveDate, TimeZone, Timelike, Utc, Weekday};
+use chrono_tz::Tz;
+
+// bounds how far ahead next_after has to search
+const MAX_INTERVAL: u32 = 366;
+
+// a subset of the icalendar RRULE format, for example:
+// FREQ=WEEKLY;BYDAY=MO,TU,WE,TH,FR;BYHOUR=6;BYMINUTE=45
+//
+// supported parts are FREQ (DAILY or WEEKLY), INTERVAL, BYDAY, BYHOUR, BYMINUTE and UNTIL.
+// times that aren't given default to the ones of the start time.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Recurrence {
+    pub freq: Freq,
+    pub interval: u32,
+    pub by_day: Vec<Weekday>,
+    pub by_hour: Vec<u32>,
+    pub by_minute: Vec<u32>,
+    pub until: Option<DateTime<Utc>>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Freq {
+    Daily,
+    Weekly,
+}
+
+fn parse_weekday(s: &str) -> Result<Weekday, String> {
+    match s {
+        "MO" => Ok(Weekday::Mon),
+        "TU" => Ok(Weekday::Tue),
+        "WE" => Ok(Weekday::Wed),
+        "TH" => Ok(Weekday::Thu),
+        "FR" => Ok(Weekday::Fri),
+        "SA" => Ok(Weekday::Sat),
+        "SU" => Ok(Weekday::Sun),
+        _ => Err(format!("invalid weekday: {}", s)),
+    }
+}
+
+fn parse_list(s: &str, max: u32) -> Result<Vec<u32>, String> {
+    let mut values = s
+        .split(',')
+        .map(|x| match x.parse::<u32>() {
+            Ok(v) if v <= max => Ok(v),
+            _ => Err(format!("invalid value: {}", x)),
+        })
+        .collect::<Result<Vec<u32>, String>>()?;
+    values.sort_unstable();
+    values.dedup();
+    Ok(values)
+}
+
+fn parse_until(s: &str) -> Result<DateTime<Utc>, String> {
+    let err = || format!("invalid until: {}", s);
+    let naive = match s.strip_suffix('Z') {
+        Some(s) => chrono::NaiveDateTime::parse_from_str(s, "%Y%m%dT%H%M%S").map_err(|_| err())?,
+        None => NaiveDate::parse_from_str(s, "%Y%m%d")
+            .map_err(|_| err())?
+            .and_hms_opt(23, 59, 59)
+            .ok_or_else(err)?,
+    };
+    Ok(Utc.from_utc_datetime(&naive))
+}
+
+impl FromStr for Recurrence {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Recurrence, String> {
+        let mut freq = None;
+        let mut recurrence = Recurrence {
+            freq: Freq::Daily,
+            interval: 1,
+            by_day: vec![],
+            by_hour: vec![],
+            by_minute: vec![],
+            until: None,
+        };
+
+        for part in s.trim().trim_start_matches("RRULE:").split(';') {
+            let (key, value) = part
+                .split_once('=')
+                .ok_or_else(|| format!("invalid part: {}", part))?;
+            match key {
+                "FREQ" => {
+                    freq = Some(match value {
+                        "DAILY" => Freq::Daily,
+                        "WEEKLY" => Freq::Weekly,
+                        _ => return Err(format!("unsupported frequency: {}", value)),
+                    })
+                }
+                "INTERVAL" => {
+                    recurrence.interval = match value.parse::<u32>() {
+                        Ok(v) if v > 0 && v <= MAX_INTERVAL => v,
+                        _ => return Err(format!("invalid interval: {}", value)),
+                    }
+                }
+                "BYDAY" => {
+                    recurrence.by_day = value.split(',').map(parse_weekday).collect::<Result<
+                        Vec<Weekday>,
+                        String,
+                    >>(
+                    )?
+                }
+                "BYHOUR" => recurrence.by_hour = parse_list(value, 23)?,
+                "BYMINUTE" => recurrence.by_minute = parse_list(value, 59)?,
+                "UNTIL" => recurrence.until = Some(parse_until(value)?),
+                _ => return Err(format!("unsupported part: {}", key)),
+            }
+        }
+
+        recurrence.freq = freq.ok_or_else(|| String::from("missing FREQ"))?;
+        Ok(recurrence)
+    }
+}
+
+impl Recurrence {
+    // whether a day (in the alarm's time zone) has occurrences on it
+    fn matches_day(&self, start: NaiveDate, day: NaiveDate) -> bool {
+        let days = (day - start).num_days();
+        if days < 0 {
+            return false;
+        }
+
+        let in_interval = match self.freq {
+            Freq::Daily => days % self.interval as i64 == 0,
+            Freq::Weekly => {
+                // weeks start on monday
+                let start_week =
+                    start - Duration::days(start.weekday().num_days_from_monday() as i64);
+                ((day - start_week).num_days() / 7) % self.interval as i64 == 0
+            }
+        };
+
+        let on_day = if !self.by_day.is_empty() {
+            self.by_day.contains(&day.weekday())
+        } else {
+            match self.freq {
+                Freq::Daily => true,
+                Freq::Weekly => day.weekday() == start.weekday(),
+            }
+        };
+
+        in_interval && on_day
+    }
+
+    // the first occurrence strictly after the given time, if there is one
+    pub fn next_after(&self, start: DateTime<Tz>, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let tz = start.timezone();
+
+        let hours = if self.by_hour.is_empty() {
+            vec![start.hour()]
+        } else {
+            self.by_hour.clone()
+        };
+        let minutes = if self.by_minute.is_empty() {
+            vec![start.minute()]
+        } else {
+            self.by_minute.clone()
+        };
+
+        let start_day = start.date_naive();
+        let first_day = after.with_timezone(&tz).date_naive().max(start_day);
+
+        // long enough to find an occurrence of any rule that has one
+        let max_days = 7 * (self.interval as i64 + 1);
+
+        for offset in 0..=max_days {
+            let day = first_day.checked_add_signed(Duration::days(offset))?;
+            if !self.matches_day(start_day, day) {
+                continue;
+            }
+            for hour in hours.iter() {
+                for minute in minutes.iter() {
+                    // times that don't exist because of daylight saving are skipped
+                    let time = match day
+                        .and_hms_opt(*hour, *minute, 0)
+                        .and_then(|t| tz.from_local_datetime(&t).earliest())
+                    {
+                        Some(time) => time.with_timezone(&Utc),
+                        None => continue,
+                    };
+                    if time <= after || time < start {
+                        continue;
+                    }
+                    if matches!(self.until, Some(until) if time > until) {
+                        return None;
+                    }
+                    return Some(time);
+                }
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    fn start(tz: Tz, y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<Tz> {
+        tz.with_ymd_and_hms(y, m, d, h, min, 0).unwrap()
+    }
+
+    #[test]
+    fn rejects_huge_intervals() {
+        assert!("FREQ=DAILY;INTERVAL=366".parse::<Recurrence>().is_ok());
+        assert!("FREQ=DAILY;INTERVAL=367".parse::<Recurrence>().is_err());
+        assert!("FREQ=DAILY;INTERVAL=4294967295"
+            .parse::<Recurrence>()
+            .is_err());
+        assert!("FREQ=DAILY;INTERVAL=0".parse::<Recurrence>().is_err());
+    }
+
+    #[test]
+    fn weekdays_in_a_named_zone() {
+        let rule: Recurrence = "FREQ=WEEKLY;BYDAY=MO,TU,WE,TH,FR;BYHOUR=6;BYMINUTE=45"
+            .parse()
+            .unwrap();
+        // a thursday
+        let start = start(chrono_tz::America::New_York, 2024, 1, 4, 22, 0);
+
+        let next = rule.next_after(start, start.with_timezone(&Utc)).unwrap();
+        assert_eq!(next, utc("2024-01-05T06:45:00-05:00"));
+        // skips the weekend
+        let next = rule.next_after(start, next).unwrap();
+        assert_eq!(next, utc("2024-01-08T06:45:00-05:00"));
+    }
+
+    #[test]
+    fn skips_the_dst_gap() {
+        let rule: Recurrence = "FREQ=DAILY;BYHOUR=2;BYMINUTE=30".parse().unwrap();
+        let start = start(chrono_tz::America::New_York, 2024, 3, 1, 0, 0);
+
+        // 02:30 doesn't exist on 2024-03-10
+        let next = rule
+            .next_after(start, utc("2024-03-09T12:00:00-05:00"))
+            .unwrap();
+        assert_eq!(next, utc("2024-03-11T02:30:00-04:00"));
+    }
+
+    #[test]
+    fn stops_at_until() {
+        let rule: Recurrence = "FREQ=DAILY;BYHOUR=7;BYMINUTE=0;UNTIL=20240103T120000Z"
+            .parse()
+            .unwrap();
+        let start = start(chrono_tz::UTC, 2024, 1, 1, 0, 0);
+
+        let next = rule.next_after(start, utc("2024-01-02T08:00:00Z")).unwrap();
+        assert_eq!(next, utc("2024-01-03T07:00:00Z"));
+        assert_eq!(rule.next_after(start, next), None);
+    }
+
+    #[test]
+    fn daily_interval() {
+        let rule: Recurrence = "FREQ=DAILY;INTERVAL=3".parse().unwrap();
+        let start = start(chrono_tz::Europe::Berlin, 2024, 1, 1, 7, 0);
+
+        let next = rule.next_after(start, start.with_timezone(&Utc)).unwrap();
+        assert_eq!(next, utc("2024-01-04T07:00:00+01:00"));
+        let next = rule.next_after(start, next).unwrap();
+        assert_eq!(next, utc("2024-01-07T07:00:00+01:00"));
+    }
+
+    #[test]
+    fn weekly_interval() {
+        let rule: Recurrence = "FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,FR".parse().unwrap();
+        // a monday
+        let start = start(chrono_tz::Europe::Berlin, 2024, 1, 1, 7, 0);
+
+        let next = rule.next_after(start, start.with_timezone(&Utc)).unwrap();
+        assert_eq!(next, utc("2024-01-05T07:00:00+01:00"));
+        // the week in between is skipped
+        let next = rule.next_after(start, next).unwrap();
+        assert_eq!(next, utc("2024-01-15T07:00:00+01:00"));
+    }
+}
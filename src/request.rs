@@ -1,16 +1,45 @@
 use serde::{Deserialize, Serialize};
 
+use crate::db_types::{HouseholdRole, SleepEventKind, SleepEventSource, SleepRuleActionKind};
+use crate::manage_sleep_export::SleepExportFormat;
+use crate::sleep_import::SleepImportFormat;
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct UserMessageNewProps {
     pub target_user_id: i64,
-    pub audio_data: String,
+    pub audio_data: Option<String>,
+    pub duration_millis: Option<i64>,
+    pub audio_sha256: Option<String>,
+    pub text_body: Option<String>,
+    pub deliver_at: Option<i64>,
+    pub deliver_on_wake: Option<bool>,
+    pub ephemeral: Option<bool>,
+    pub reply_to_user_message_id: Option<i64>,
+    pub api_key: String,
+}
+
+// the same message sent to several targets at once
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserMessageBroadcastProps {
+    pub target_user_ids: Option<Vec<i64>>,
+    pub household_id: Option<i64>,
+    pub audio_data: Option<String>,
+    pub duration_millis: Option<i64>,
+    pub audio_sha256: Option<String>,
+    pub text_body: Option<String>,
+    pub deliver_at: Option<i64>,
+    pub deliver_on_wake: Option<bool>,
+    pub ephemeral: Option<bool>,
+    pub reply_to_user_message_id: Option<i64>,
     pub api_key: String,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SleepEventNewProps {
+    pub kind: Option<SleepEventKind>,
     pub api_key: String,
 }
 
@@ -26,6 +55,19 @@ pub struct UserMessageViewProps {
     pub api_key: String,
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserMessageSearchProps {
+    // words to look for in text bodies and transcripts, in web search syntax
+    pub query: String,
+    pub min_creation_time: Option<i64>,
+    pub max_creation_time: Option<i64>,
+    pub creator_user_id: Option<Vec<i64>>,
+    pub target_user_id: Option<Vec<i64>>,
+    pub limit: Option<i64>,
+    pub api_key: String,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SleepEventViewProps {
@@ -38,14 +80,105 @@ pub struct SleepEventViewProps {
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-pub struct UserMessageSubmitProps {
+pub struct SleepEventBackfillProps {
+    pub kind: SleepEventKind,
+    // when it actually happened
+    pub creation_time: i64,
+    pub api_key: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SleepEventUpdateProps {
+    pub sleep_event_id: i64,
+    // unchanged if not given
+    pub kind: Option<SleepEventKind>,
+    pub creation_time: Option<i64>,
+    pub api_key: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SleepEventDeleteProps {
+    pub sleep_event_id: i64,
+    pub api_key: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SleepEventHistoryProps {
+    // every event's edits if not given
+    pub sleep_event_id: Option<i64>,
+    pub api_key: String,
+}
+
+// sent in the query string, the export is the body
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SleepEventImportProps {
+    pub format: SleepImportFormat,
+    // what times without an offset are in, utc if not given
+    pub time_zone: Option<String>,
+    pub api_key: String,
+}
+
+// sent in the query string, so the export can be downloaded with a link
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SleepEventExportProps {
+    pub format: SleepExportFormat,
+    // YYYY-MM-DD, both days included. everything if not given
+    pub start_date: Option<String>,
+    pub end_date: Option<String>,
+    // what the dates and exported times are in, utc if not given
+    pub time_zone: Option<String>,
+    pub api_key: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserMessageUploadProps {
     pub target_user_id: i64,
+    pub duration_millis: Option<i64>,
+    pub audio_sha256: Option<String>,
+    pub text_body: Option<String>,
+    pub deliver_at: Option<i64>,
+    pub deliver_on_wake: Option<bool>,
+    pub ephemeral: Option<bool>,
+    pub reply_to_user_message_id: Option<i64>,
+    pub api_key: String,
+}
+
+// submitting to several targets at once, as a binary body or over a websocket
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserMessageSubmitProps {
+    pub target_user_id: Option<i64>,
+    // comma separated, since query strings can't carry lists
+    pub target_user_ids: Option<String>,
+    pub household_id: Option<i64>,
+    pub duration_millis: Option<i64>,
+    pub audio_sha256: Option<String>,
+    pub text_body: Option<String>,
+    pub deliver_at: Option<i64>,
+    pub deliver_on_wake: Option<bool>,
+    pub ephemeral: Option<bool>,
+    pub reply_to_user_message_id: Option<i64>,
     pub api_key: String,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct UserMessageReceiveProps {
+    pub user_message_id: i64,
+    // only send the text, for devices that can't play audio
+    pub display_only: Option<bool>,
+    pub api_key: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserMessageAudioProps {
     pub user_message_id: i64,
     pub api_key: String,
 }
@@ -59,5 +192,403 @@ pub struct GetRecentUserMessageIdProps {
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct QueryParamsSleepEventProps {
-    pub creator_user_id: i64,
+    // devices without an api key only know the user's id
+    pub creator_user_id: Option<i64>,
+    pub kind: Option<SleepEventKind>,
+    // with one, the event also runs the user's rules and is shared
+    pub api_key: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HouseholdNewProps {
+    pub name: String,
+    pub api_key: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HouseholdViewProps {
+    pub api_key: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HouseholdLeaveProps {
+    pub household_id: i64,
+    pub api_key: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HouseholdMembershipNewProps {
+    pub household_id: i64,
+    pub user_id: i64,
+    pub role: HouseholdRole,
+    pub active: bool,
+    pub api_key: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HouseholdMembershipViewProps {
+    pub household_id: i64,
+    pub api_key: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HouseholdInviteNewProps {
+    pub household_id: i64,
+    pub role: Option<HouseholdRole>,
+    pub api_key: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HouseholdInviteAcceptProps {
+    pub household_invite_code: String,
+    pub api_key: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContactRequestNewProps {
+    pub target_user_id: i64,
+    pub api_key: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContactRequestRespondProps {
+    pub contact_request_id: i64,
+    pub accepted: bool,
+    pub api_key: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContactRequestViewProps {
+    pub api_key: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContactViewProps {
+    pub api_key: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContactBlockNewProps {
+    pub target_user_id: i64,
+    pub active: bool,
+    pub api_key: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContactBlockViewProps {
+    pub api_key: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduledUserMessageViewProps {
+    pub api_key: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduledUserMessageRescheduleProps {
+    pub user_message_id: i64,
+    pub deliver_at: Option<i64>,
+    pub deliver_on_wake: Option<bool>,
+    pub api_key: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduledUserMessageCancelProps {
+    pub user_message_id: i64,
+    pub api_key: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConversationViewProps {
+    pub api_key: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConversationPageProps {
+    pub other_user_id: i64,
+    // at most one of these two
+    pub before_user_message_id: Option<i64>,
+    pub after_user_message_id: Option<i64>,
+    pub limit: Option<i64>,
+    pub api_key: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserMessagePlayedProps {
+    pub user_message_id: i64,
+    pub api_key: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserMessageUnsendProps {
+    pub user_message_id: i64,
+    pub api_key: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserMessageDeleteProps {
+    pub user_message_id: i64,
+    pub api_key: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AdminUserMessageDeleteProps {
+    pub user_message_id: i64,
+    pub api_key: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserMessageReactionNewProps {
+    pub user_message_id: i64,
+    pub reaction: String,
+    pub api_key: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserMessageReactionDeleteProps {
+    pub user_message_reaction_id: i64,
+    pub api_key: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RetentionPolicyNewProps {
+    // the household's policy instead of your own
+    pub household_id: Option<i64>,
+    pub keep_days: Option<i64>,
+    pub keep_last: Option<i64>,
+    pub delete_after_played: Option<bool>,
+    pub api_key: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RetentionPolicyViewProps {
+    pub household_id: Option<i64>,
+    pub api_key: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AlarmNewProps {
+    pub target_user_id: i64,
+    pub recurrence: String,
+    pub time_zone: String,
+    pub start_time: Option<i64>,
+    pub user_message_ids: Vec<i64>,
+    pub api_key: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AlarmUpdateProps {
+    pub alarm_id: i64,
+    pub recurrence: Option<String>,
+    pub time_zone: Option<String>,
+    pub start_time: Option<i64>,
+    pub active: Option<bool>,
+    pub user_message_ids: Option<Vec<i64>>,
+    pub api_key: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AlarmViewProps {
+    pub api_key: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AlarmDeleteProps {
+    pub alarm_id: i64,
+    pub api_key: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AlarmSnoozeProps {
+    pub alarm_id: i64,
+    pub snooze_millis: Option<i64>,
+    pub api_key: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AlarmDismissProps {
+    pub alarm_id: i64,
+    pub api_key: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SleepStatusShareNewProps {
+    pub target_user_id: i64,
+    pub active: bool,
+    pub api_key: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SleepStatusShareViewProps {
+    pub api_key: String,
+}
+
+// only the fields the action uses may be given
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SleepRuleActionProps {
+    pub action: SleepRuleActionKind,
+    pub target_user_id: Option<i64>,
+    pub text_body: Option<String>,
+    pub user_message_id: Option<i64>,
+    pub duration_millis: Option<i64>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SleepRuleNewProps {
+    pub kind: SleepEventKind,
+    // any source if not given
+    pub source: Option<SleepEventSource>,
+    // a window needs all three, minutes since local midnight. all day if not given
+    pub time_zone: Option<String>,
+    pub window_start_minute: Option<i64>,
+    pub window_end_minute: Option<i64>,
+    pub actions: Vec<SleepRuleActionProps>,
+    pub api_key: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SleepRuleViewProps {
+    pub api_key: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SleepRuleDeleteProps {
+    pub sleep_rule_id: i64,
+    pub api_key: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DoNotDisturbViewProps {
+    pub api_key: String,
+}
+
+// also used by /public/ws/sleep_status
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PushProps {
+    pub api_key: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LiveTaskProps {
+    pub live_task_id: String,
+    pub value: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FinishedTaskProps {
+    pub finished_task_id: String,
+    pub value: String,
+    pub status: i64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CheckpointNewProps {
+    pub live_tasks: Vec<LiveTaskProps>,
+    pub finished_tasks: Vec<FinishedTaskProps>,
+    pub api_key: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CheckpointViewProps {
+    // the latest checkpoint if not given
+    pub checkpoint_id: Option<i64>,
+    pub api_key: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CheckpointListProps {
+    pub min_creation_time: Option<i64>,
+    pub max_creation_time: Option<i64>,
+    pub limit: Option<i64>,
+    pub api_key: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CheckpointDiffProps {
+    pub from_checkpoint_id: i64,
+    pub to_checkpoint_id: i64,
+    pub api_key: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskHistoryProps {
+    pub live_task_id: String,
+    pub api_key: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BedtimeRoutineTaskProps {
+    // the id the task has on the task list
+    pub bedtime_routine_task_id: String,
+    pub value: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BedtimeRoutineNewProps {
+    pub tasks: Vec<BedtimeRoutineTaskProps>,
+    pub api_key: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BedtimeRoutineViewProps {
+    pub api_key: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BedtimeRoutineStatsProps {
+    // of the sleep events nights start with
+    pub min_creation_time: Option<i64>,
+    pub max_creation_time: Option<i64>,
+    pub api_key: String,
 }
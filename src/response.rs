@@ -1,20 +1,449 @@
 use derive_more::Display;
 use serde::{Deserialize, Serialize};
 
+use crate::db_types::{
+    HouseholdRole, SleepEventEditAction, SleepEventKind, SleepEventSource, SleepRuleActionKind,
+    UserMessageKind,
+};
+use crate::sleep_import::SkipReason;
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct UserMessage {
+    pub user_message_id: i64,
     pub creation_time: i64,
     pub creator_user_id: i64,
     pub target_user_id: i64,
-    pub audio_data: String,
+    pub kind: UserMessageKind,
+    pub audio_size: Option<i64>,
+    pub audio_sha256: Option<String>,
+    pub audio_duration_millis: Option<i64>,
+    pub text_body: Option<String>,
+    pub deliver_at: Option<i64>,
+    pub ephemeral: bool,
+    pub played_time: Option<i64>,
+    pub reply_to_user_message_id: Option<i64>,
+    pub reactions: Vec<UserMessageReaction>,
+    pub transcript: Option<AudioTranscript>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserMessageReaction {
+    pub user_message_reaction_id: i64,
+    pub creation_time: i64,
+    pub creator_user_id: i64,
+    pub user_message_id: i64,
+    pub reaction: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AudioTranscript {
+    pub creation_time: i64,
+    pub transcriber: String,
+    pub text: String,
+    pub language: Option<String>,
+    pub confidence: Option<f64>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Conversation {
+    pub other_user_id: i64,
+    pub last_user_message: UserMessage,
+    pub unread_count: i64,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SleepEvent {
+    pub sleep_event_id: i64,
+    pub creation_time: i64,
+    pub creator_user_id: i64,
+    pub kind: SleepEventKind,
+    pub source: SleepEventSource,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SleepEventEdit {
+    pub sleep_event_edit_id: i64,
+    pub creation_time: i64,
+    pub creator_user_id: i64,
+    pub sleep_event_id: i64,
+    pub action: SleepEventEditAction,
+    pub old_kind: Option<SleepEventKind>,
+    pub old_time: Option<i64>,
+    pub new_kind: Option<SleepEventKind>,
+    pub new_time: Option<i64>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportedSleepSession {
+    pub start_time: i64,
+    pub end_time: i64,
+    pub sleep_event_id: i64,
+    pub wake_event_id: i64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SkippedSleepSession {
+    pub start_time: i64,
+    pub end_time: i64,
+    pub reason: SkipReason,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SleepImportReport {
+    pub imported_count: i64,
+    pub skipped_count: i64,
+    pub imported: Vec<ImportedSleepSession>,
+    pub skipped: Vec<SkippedSleepSession>,
+}
+
+// a night's sleep, or a sleep or wake event without the other half
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SleepExportEntry {
+    pub sleep_event_id: Option<i64>,
+    pub wake_event_id: Option<i64>,
+    pub sleep_time: Option<i64>,
+    pub wake_time: Option<i64>,
+    pub sleep_local_time: Option<String>,
+    pub wake_local_time: Option<String>,
+    pub duration_millis: Option<i64>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Household {
+    pub household_id: i64,
+    pub creation_time: i64,
+    pub creator_user_id: i64,
+    pub name: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HouseholdMembership {
+    pub household_membership_id: i64,
+    pub creation_time: i64,
+    pub creator_user_id: i64,
+    pub household_id: i64,
+    pub user_id: i64,
+    pub role: HouseholdRole,
+    pub active: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HouseholdInvite {
+    pub household_invite_code: String,
     pub creation_time: i64,
     pub creator_user_id: i64,
+    pub household_id: i64,
+    pub role: HouseholdRole,
+    pub expiry_time: i64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContactRequest {
+    pub contact_request_id: i64,
+    pub creation_time: i64,
+    pub creator_user_id: i64,
+    pub target_user_id: i64,
+    pub response_time: Option<i64>,
+    pub accepted: Option<bool>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Contact {
+    pub contact_user_id: i64,
+    pub creation_time: i64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContactBlock {
+    pub contact_block_id: i64,
+    pub creation_time: i64,
+    pub creator_user_id: i64,
+    pub target_user_id: i64,
+    pub active: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SleepStatusShare {
+    pub sleep_status_share_id: i64,
+    pub creation_time: i64,
+    pub creator_user_id: i64,
+    pub target_user_id: i64,
+    pub active: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SleepRuleAction {
+    pub action: SleepRuleActionKind,
+    pub target_user_id: Option<i64>,
+    pub text_body: Option<String>,
+    pub user_message_id: Option<i64>,
+    pub duration_millis: Option<i64>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SleepRule {
+    pub sleep_rule_id: i64,
+    pub creation_time: i64,
+    pub creator_user_id: i64,
+    pub kind: SleepEventKind,
+    pub source: Option<SleepEventSource>,
+    pub time_zone: Option<String>,
+    pub window_start_minute: Option<i64>,
+    pub window_end_minute: Option<i64>,
+    pub active: bool,
+    // in the order they're carried out
+    pub actions: Vec<SleepRuleAction>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DoNotDisturb {
+    pub do_not_disturb_id: i64,
+    pub creation_time: i64,
+    pub creator_user_id: i64,
+    pub sleep_rule_id: Option<i64>,
+    // none means until the user wakes up
+    pub end_time: Option<i64>,
+}
+
+// whether a user is asleep right now, going by their latest sleep event
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SleepStatus {
+    pub user_id: i64,
+    pub asleep: bool,
+    // when they went to sleep or woke up, none if they have never recorded either
+    pub since: Option<i64>,
+    pub sleep_event_id: Option<i64>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Alarm {
+    pub alarm_id: i64,
+    pub creation_time: i64,
+    pub creator_user_id: i64,
+    pub target_user_id: i64,
+    pub recurrence: String,
+    pub time_zone: String,
+    pub start_time: i64,
+    pub active: bool,
+    pub next_fire_time: Option<i64>,
+    pub snooze_until: Option<i64>,
+    pub last_fire_time: Option<i64>,
+    pub user_message_ids: Vec<i64>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RetentionPolicy {
+    pub retention_policy_id: i64,
+    pub creation_time: i64,
+    pub creator_user_id: i64,
+    pub user_id: Option<i64>,
+    pub household_id: Option<i64>,
+    pub keep_days: Option<i64>,
+    pub keep_last: Option<i64>,
+    pub delete_after_played: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Checkpoint {
+    pub checkpoint_id: i64,
+    pub creation_time: i64,
+    pub creator_user_id: i64,
+    pub live_tasks: Vec<LiveTask>,
+    pub finished_tasks: Vec<FinishedTask>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LiveTask {
+    pub live_task_id: String,
+    pub position: i64,
+    pub value: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FinishedTask {
+    pub finished_task_id: String,
+    pub position: i64,
+    pub value: String,
+    pub status: i64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskState {
+    pub task_id: String,
+    pub position: i64,
+    pub value: String,
+    // null while the task is live
+    pub status: Option<i64>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskMove {
+    pub task_id: String,
+    pub from_position: i64,
+    pub to_position: i64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskEdit {
+    pub from: TaskState,
+    pub to: TaskState,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CheckpointDiff {
+    pub from_checkpoint_id: i64,
+    pub to_checkpoint_id: i64,
+    pub added: Vec<TaskState>,
+    pub removed: Vec<TaskState>,
+    pub completed: Vec<TaskState>,
+    pub reopened: Vec<TaskState>,
+    pub reordered: Vec<TaskMove>,
+    pub edited: Vec<TaskEdit>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskHistoryEntry {
+    pub checkpoint_id: i64,
+    pub creation_time: i64,
+    pub state: TaskState,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskHistory {
+    pub task_id: String,
+    pub entries: Vec<TaskHistoryEntry>,
+    pub finished_time: Option<i64>,
+    pub time_to_finish_millis: Option<i64>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BedtimeRoutine {
+    pub bedtime_routine_id: i64,
+    pub creation_time: i64,
+    pub creator_user_id: i64,
+    pub tasks: Vec<BedtimeRoutineTask>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BedtimeRoutineTask {
+    pub bedtime_routine_task_id: String,
+    pub position: i64,
+    pub value: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RoutineNight {
+    pub sleep_event_id: i64,
+    pub sleep_time: i64,
+    pub wake_time: Option<i64>,
+    pub sleep_millis: Option<i64>,
+    pub bedtime_routine_id: i64,
+    pub checkpoint_id: Option<i64>,
+    pub task_count: i64,
+    pub finished_count: i64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RoutineBucket {
+    pub night_count: i64,
+    pub average_sleep_millis: Option<i64>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RoutineStats {
+    pub nights: Vec<RoutineNight>,
+    pub complete: RoutineBucket,
+    pub partial: RoutineBucket,
+    pub skipped: RoutineBucket,
+}
+
+// sent to a user's devices over /public/ws/push, or /public/ws/sleep_status for the sleep status ones
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum PushEvent {
+    // play these messages now, in order
+    #[serde(rename_all = "camelCase")]
+    PlayAlarm {
+        alarm_id: i64,
+        user_message_ids: Vec<i64>,
+        fire_time: i64,
+    },
+    // the alarm was snoozed or dismissed, every device should stop playing it
+    #[serde(rename_all = "camelCase")]
+    StopAlarm { alarm_id: i64 },
+    // the message was unsent or deleted, devices should stop playing it and drop any copy
+    #[serde(rename_all = "camelCase")]
+    UserMessageRevoked { user_message_id: i64 },
+    #[serde(rename_all = "camelCase")]
+    UserMessageReactionAdded {
+        user_message_reaction: UserMessageReaction,
+    },
+    #[serde(rename_all = "camelCase")]
+    UserMessageReactionRemoved {
+        user_message_reaction_id: i64,
+        user_message_id: i64,
+    },
+    // everyone sharing their status with the user, sent when the feed opens
+    #[serde(rename_all = "camelCase")]
+    SleepStatusSnapshot { sleep_statuses: Vec<SleepStatus> },
+    // someone sharing with the user went to sleep or woke up, or just started sharing
+    #[serde(rename_all = "camelCase")]
+    SleepStatusChanged { sleep_status: SleepStatus },
+    // the user can no longer see this user's status
+    #[serde(rename_all = "camelCase")]
+    SleepStatusUnshared { user_id: i64 },
+    // one of this user's rules went off, telling the target
+    #[serde(rename_all = "camelCase")]
+    SleepRuleNotice {
+        sleep_rule_id: i64,
+        user_id: i64,
+        sleep_event_kind: SleepEventKind,
+        sleep_event_time: i64,
+        text_body: Option<String>,
+    },
+    // one of the user's rules delivered this message, play it now
+    #[serde(rename_all = "camelCase")]
+    PlayUserMessage {
+        sleep_rule_id: i64,
+        user_message_id: i64,
+    },
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -0,0 +1,94 @@
+use super::db_types::*;
+use tokio_postgres::GenericClient;
+
+impl From<tokio_postgres::row::Row> for RetentionPolicy {
+    // select * from retention_policy order only, otherwise it will fail
+    fn from(row: tokio_postgres::Row) -> RetentionPolicy {
+        RetentionPolicy {
+            retention_policy_id: row.get("retention_policy_id"),
+            creation_time: row.get("creation_time"),
+            creator_user_id: row.get("creator_user_id"),
+            user_id: row.get("user_id"),
+            household_id: row.get("household_id"),
+            keep_days: row.get("keep_days"),
+            keep_last: row.get("keep_last"),
+            delete_after_played: row.get("delete_after_played"),
+        }
+    }
+}
+
+// exactly one of user_id and household_id must be given
+pub async fn add(
+    con: &mut impl GenericClient,
+    creator_user_id: i64,
+    user_id: Option<i64>,
+    household_id: Option<i64>,
+    keep_days: Option<i64>,
+    keep_last: Option<i64>,
+    delete_after_played: bool,
+) -> Result<RetentionPolicy, tokio_postgres::Error> {
+    let row = con
+        .query_one(
+            "INSERT INTO
+             retention_policy(
+                 creator_user_id,
+                 user_id,
+                 household_id,
+                 keep_days,
+                 keep_last,
+                 delete_after_played
+             )
+             VALUES($1, $2, $3, $4, $5, $6)
+             RETURNING retention_policy_id, creation_time
+            ",
+            &[
+                &creator_user_id,
+                &user_id,
+                &household_id,
+                &keep_days,
+                &keep_last,
+                &delete_after_played,
+            ],
+        )
+        .await?;
+
+    // return retention policy
+    Ok(RetentionPolicy {
+        retention_policy_id: row.get(0),
+        creation_time: row.get(1),
+        creator_user_id,
+        user_id,
+        household_id,
+        keep_days,
+        keep_last,
+        delete_after_played,
+    })
+}
+
+pub async fn get_recent_by_user_id(
+    con: &mut impl GenericClient,
+    user_id: i64,
+) -> Result<Option<RetentionPolicy>, tokio_postgres::Error> {
+    let result = con
+        .query_opt(
+            "SELECT * FROM recent_retention_policy WHERE user_id=$1",
+            &[&user_id],
+        )
+        .await?
+        .map(|x| x.into());
+    Ok(result)
+}
+
+pub async fn get_recent_by_household_id(
+    con: &mut impl GenericClient,
+    household_id: i64,
+) -> Result<Option<RetentionPolicy>, tokio_postgres::Error> {
+    let result = con
+        .query_opt(
+            "SELECT * FROM recent_retention_policy WHERE household_id=$1",
+            &[&household_id],
+        )
+        .await?
+        .map(|x| x.into());
+    Ok(result)
+}
@@ -0,0 +1,103 @@
+use super::db_types::*;
+use tokio_postgres::GenericClient;
+
+impl From<tokio_postgres::row::Row> for SleepEventEdit {
+    // select * from sleep_event_edit order only, otherwise it will fail
+    fn from(row: tokio_postgres::Row) -> SleepEventEdit {
+        let kind = |column: &str| {
+            row.get::<_, Option<i64>>(column)
+                .map(|x| x.try_into().expect("invalid sleep event kind"))
+        };
+        SleepEventEdit {
+            sleep_event_edit_id: row.get("sleep_event_edit_id"),
+            creation_time: row.get("creation_time"),
+            creator_user_id: row.get("creator_user_id"),
+            sleep_event_id: row.get("sleep_event_id"),
+            action: row
+                .get::<_, i64>("action")
+                .try_into()
+                .expect("invalid sleep event edit action"),
+            old_kind: kind("old_kind"),
+            old_time: row.get("old_time"),
+            new_kind: kind("new_kind"),
+            new_time: row.get("new_time"),
+        }
+    }
+}
+
+// old is what the event was before, new what it is now
+pub async fn add(
+    con: &mut impl GenericClient,
+    creator_user_id: i64,
+    sleep_event_id: i64,
+    action: SleepEventEditAction,
+    old: Option<&SleepEvent>,
+    new: Option<&SleepEvent>,
+) -> Result<SleepEventEdit, tokio_postgres::Error> {
+    let old_kind = old.map(|x| x.kind);
+    let old_time = old.map(|x| x.creation_time);
+    let new_kind = new.map(|x| x.kind);
+    let new_time = new.map(|x| x.creation_time);
+
+    let row = con
+        .query_one(
+            "INSERT INTO
+             sleep_event_edit(
+                 creator_user_id,
+                 sleep_event_id,
+                 action,
+                 old_kind,
+                 old_time,
+                 new_kind,
+                 new_time
+             )
+             VALUES($1, $2, $3, $4, $5, $6, $7)
+             RETURNING sleep_event_edit_id, creation_time
+            ",
+            &[
+                &creator_user_id,
+                &sleep_event_id,
+                &i64::from(action),
+                &old_kind.map(i64::from),
+                &old_time,
+                &new_kind.map(i64::from),
+                &new_time,
+            ],
+        )
+        .await?;
+
+    // return sleep event edit
+    Ok(SleepEventEdit {
+        sleep_event_edit_id: row.get(0),
+        creation_time: row.get(1),
+        creator_user_id,
+        sleep_event_id,
+        action,
+        old_kind,
+        old_time,
+        new_kind,
+        new_time,
+    })
+}
+
+// the creator's edits, oldest first, optionally only those to one event
+pub async fn get_by_creator_user_id(
+    con: &mut impl GenericClient,
+    creator_user_id: i64,
+    sleep_event_id: Option<i64>,
+) -> Result<Vec<SleepEventEdit>, tokio_postgres::Error> {
+    let result = con
+        .query(
+            "SELECT * FROM sleep_event_edit
+             WHERE creator_user_id=$1
+             AND ($2::bigint IS NULL OR sleep_event_id = $2)
+             ORDER BY sleep_event_edit_id
+            ",
+            &[&creator_user_id, &sleep_event_id],
+        )
+        .await?
+        .into_iter()
+        .map(|x| x.into())
+        .collect();
+    Ok(result)
+}
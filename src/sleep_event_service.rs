@@ -1,4 +1,5 @@
 use super::db_types::*;
+use super::sleep_status_share_service;
 use tokio_postgres::GenericClient;
 
 impl From<tokio_postgres::row::Row> for SleepEvent {
@@ -8,6 +9,14 @@ impl From<tokio_postgres::row::Row> for SleepEvent {
             sleep_event_id: row.get("sleep_event_id"),
             creation_time: row.get("creation_time"),
             creator_user_id: row.get("creator_user_id"),
+            kind: row
+                .get::<_, i64>("kind")
+                .try_into()
+                .expect("invalid sleep event kind"),
+            source: row
+                .get::<_, i64>("source")
+                .try_into()
+                .expect("invalid sleep event source"),
         }
     }
 }
@@ -15,17 +24,21 @@ impl From<tokio_postgres::row::Row> for SleepEvent {
 pub async fn add(
     con: &mut impl GenericClient,
     creator_user_id: i64,
+    kind: SleepEventKind,
+    source: SleepEventSource,
 ) -> Result<SleepEvent, tokio_postgres::Error> {
     let row = con
         .query_one(
             "INSERT INTO
              sleep_event(
-                 creator_user_id
+                 creator_user_id,
+                 kind,
+                 source
              )
-             VALUES($1)
+             VALUES($1, $2, $3)
              RETURNING sleep_event_id, creation_time
             ",
-            &[&creator_user_id],
+            &[&creator_user_id, &i64::from(kind), &i64::from(source)],
         )
         .await?;
 
@@ -34,9 +47,82 @@ pub async fn add(
         sleep_event_id: row.get(0),
         creation_time: row.get(1),
         creator_user_id,
+        kind,
+        source,
+    })
+}
+
+// an event the user forgot to record when it happened
+pub async fn add_backfilled(
+    con: &mut impl GenericClient,
+    creator_user_id: i64,
+    kind: SleepEventKind,
+    source: SleepEventSource,
+    creation_time: i64,
+) -> Result<SleepEvent, tokio_postgres::Error> {
+    let row = con
+        .query_one(
+            "INSERT INTO
+             sleep_event(
+                 creator_user_id,
+                 kind,
+                 source,
+                 creation_time
+             )
+             VALUES($1, $2, $3, $4)
+             RETURNING sleep_event_id
+            ",
+            &[
+                &creator_user_id,
+                &i64::from(kind),
+                &i64::from(source),
+                &creation_time,
+            ],
+        )
+        .await?;
+
+    // return sleepEvent
+    Ok(SleepEvent {
+        sleep_event_id: row.get(0),
+        creation_time,
+        creator_user_id,
+        kind,
+        source,
     })
 }
 
+pub async fn update(
+    con: &mut impl GenericClient,
+    sleep_event: &SleepEvent,
+) -> Result<(), tokio_postgres::Error> {
+    con.execute(
+        "UPDATE sleep_event
+         SET kind = $2,
+             creation_time = $3
+         WHERE sleep_event_id = $1
+        ",
+        &[
+            &sleep_event.sleep_event_id,
+            &i64::from(sleep_event.kind),
+            &sleep_event.creation_time,
+        ],
+    )
+    .await?;
+    Ok(())
+}
+
+pub async fn delete(
+    con: &mut impl GenericClient,
+    sleep_event_id: i64,
+) -> Result<(), tokio_postgres::Error> {
+    con.execute(
+        "DELETE FROM sleep_event WHERE sleep_event_id=$1",
+        &[&sleep_event_id],
+    )
+    .await?;
+    Ok(())
+}
+
 pub async fn get_by_sleep_event_id(
     con: &mut impl GenericClient,
     sleep_event_id: i64,
@@ -64,19 +150,110 @@ pub async fn get_recent_by_user_id(
         .map(|x| x.into());
     Ok(result)
 }
+
+// the user's last event of this kind from before max_creation_time
+pub async fn get_recent_by_user_id_and_kind_before(
+    con: &mut impl GenericClient,
+    creator_user_id: i64,
+    kind: SleepEventKind,
+    max_creation_time: i64,
+) -> Result<Option<SleepEvent>, tokio_postgres::Error> {
+    let result = con
+        .query_opt(
+            "SELECT * FROM sleep_event
+             WHERE creator_user_id=$1
+             AND kind=$2
+             AND creation_time < $3
+             ORDER BY creation_time DESC, sleep_event_id DESC
+             LIMIT 1
+            ",
+            &[&creator_user_id, &i64::from(kind), &max_creation_time],
+        )
+        .await?
+        .map(|x| x.into());
+    Ok(result)
+}
+
+// the user's events between the two times, oldest first
+pub async fn get_by_creator_user_id_between(
+    con: &mut impl GenericClient,
+    creator_user_id: i64,
+    min_creation_time: i64,
+    max_creation_time: i64,
+) -> Result<Vec<SleepEvent>, tokio_postgres::Error> {
+    let result = con
+        .query(
+            "SELECT * FROM sleep_event
+             WHERE creator_user_id=$1
+             AND creation_time >= $2
+             AND creation_time <= $3
+             ORDER BY creation_time, sleep_event_id
+            ",
+            &[&creator_user_id, &min_creation_time, &max_creation_time],
+        )
+        .await?
+        .into_iter()
+        .map(|x| x.into())
+        .collect();
+    Ok(result)
+}
+
+// a page of the user's events within the times, oldest first, after the (creation_time, sleep_event_id) given
+pub async fn get_page_by_creator_user_id(
+    con: &mut impl GenericClient,
+    creator_user_id: i64,
+    min_creation_time: Option<i64>,
+    max_creation_time: Option<i64>,
+    after: Option<(i64, i64)>,
+    limit: i64,
+) -> Result<Vec<SleepEvent>, tokio_postgres::Error> {
+    let result = con
+        .query(
+            "SELECT * FROM sleep_event
+             WHERE creator_user_id=$1
+             AND ($2::bigint IS NULL OR creation_time >= $2)
+             AND ($3::bigint IS NULL OR creation_time < $3)
+             AND ($4::bigint IS NULL OR (creation_time, sleep_event_id) > ($4, $5::bigint))
+             ORDER BY creation_time, sleep_event_id
+             LIMIT $6
+            ",
+            &[
+                &creator_user_id,
+                &min_creation_time,
+                &max_creation_time,
+                &after.map(|x| x.0),
+                &after.map(|x| x.1),
+                &limit,
+            ],
+        )
+        .await?
+        .into_iter()
+        .map(|x| x.into())
+        .collect();
+    Ok(result)
+}
+
+// only the viewer's own events, and those of users currently sharing their status with them, are returned
 pub async fn query(
     con: &mut impl GenericClient,
+    viewer_user_id: i64,
     props: crate::request::SleepEventViewProps,
 ) -> Result<Vec<SleepEvent>, tokio_postgres::Error> {
     let sql = [
-        "SELECT se.* FROM sleep_event_t se",
+        "SELECT se.* FROM sleep_event se",
         " WHERE 1 = 1",
-        " AND ($1::bigint[] IS NULL OR se.sleep_event_id IN $1)",
+        " AND ($1::bigint[] IS NULL OR se.sleep_event_id = ANY($1))",
         " AND ($2::bigint   IS NULL OR se.creation_time >= $2)",
         " AND ($3::bigint   IS NULL OR se.creation_time <= $3)",
-        " AND ($4::bigint[] IS NULL OR se.creator_user_id IN $4)",
-        " AND ($5::bigint[] IS NULL OR se.target_user_id IN $5)",
-        " ORDER BY se.sleep_event_id",
+        " AND ($4::bigint[] IS NULL OR se.creator_user_id = ANY($4))",
+        " AND (se.creator_user_id = $5 OR EXISTS(",
+        "   SELECT 1 FROM recent_sleep_status_share sss",
+        "   WHERE sss.creator_user_id = se.creator_user_id",
+        "   AND sss.target_user_id = $5 AND sss.active",
+        "   AND ",
+        sleep_status_share_service::SHARE_IS_VISIBLE,
+        " ))",
+        " ORDER BY se.creation_time, se.sleep_event_id",
     ]
     .join("");
 
@@ -90,6 +267,7 @@ pub async fn query(
                 &props.min_creation_time,
                 &props.max_creation_time,
                 &props.creator_user_id,
+                &viewer_user_id,
             ],
         )
         .await?
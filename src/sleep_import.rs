@@ -0,0 +1,96 @@
+use std::io::Read;
+
+use chrono::{NaiveDateTime, TimeZone};
+use chrono_tz::Tz;
+use derive_more::Display;
+use serde::{Deserialize, Serialize};
+
+use crate::{apple_health_sleep_import, csv_sleep_import, fitbit_sleep_import};
+
+#[derive(Debug, Display)]
+pub enum SleepImportError {
+    #[display(fmt = "malformed json: {}", _0)]
+    Json(serde_json::Error),
+    #[display(fmt = "malformed xml: {}", _0)]
+    Xml(quick_xml::Error),
+    #[display(fmt = "malformed csv: {}", _0)]
+    Csv(csv::Error),
+    #[display(fmt = "{}", _0)]
+    Malformed(String),
+}
+
+impl std::error::Error for SleepImportError {}
+
+impl From<serde_json::Error> for SleepImportError {
+    fn from(e: serde_json::Error) -> SleepImportError {
+        SleepImportError::Json(e)
+    }
+}
+
+impl From<quick_xml::Error> for SleepImportError {
+    fn from(e: quick_xml::Error) -> SleepImportError {
+        SleepImportError::Xml(e)
+    }
+}
+
+impl From<csv::Error> for SleepImportError {
+    fn from(e: csv::Error) -> SleepImportError {
+        SleepImportError::Csv(e)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum SleepImportFormat {
+    // the sleep-*.json files in a Fitbit data export
+    Fitbit,
+    // the export.xml file in an Apple Health export
+    AppleHealth,
+    // one session per row, with start and end columns
+    Csv,
+}
+
+// why a session in an export wasn't imported
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum SkipReason {
+    // ends before it starts, lasts longer than a day, or hasn't ended yet
+    Invalid,
+    // overlaps an earlier session in the same export
+    Overlapping,
+    // the user already has sleep events during it
+    Duplicate,
+}
+
+// one stretch of sleep, in milliseconds since the epoch
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SleepSession {
+    pub start_time: i64,
+    pub end_time: i64,
+}
+
+// pulls the sleep sessions out of an export, in the order they appear, reading it as it goes.
+// times without an offset are taken to be in time_zone.
+pub fn parse(
+    format: SleepImportFormat,
+    data: impl Read,
+    time_zone: Tz,
+) -> Result<Vec<SleepSession>, SleepImportError> {
+    match format {
+        SleepImportFormat::Fitbit => fitbit_sleep_import::parse(data, time_zone),
+        SleepImportFormat::AppleHealth => apple_health_sleep_import::parse(data),
+        SleepImportFormat::Csv => csv_sleep_import::parse(data, time_zone),
+    }
+}
+
+// a wall clock time in time_zone. times repeated when the clocks go back are taken the first time round,
+// times skipped when they go forward don't exist.
+pub fn local_time_millis(naive: NaiveDateTime, time_zone: Tz) -> Result<i64, SleepImportError> {
+    time_zone
+        .from_local_datetime(&naive)
+        .earliest()
+        .map(|x| x.timestamp_millis())
+        .ok_or_else(|| {
+            SleepImportError::Malformed(format!("{} doesn't exist in {}", naive, time_zone))
+        })
+}
@@ -0,0 +1,71 @@
+use super::db_types::*;
+use tokio_postgres::GenericClient;
+
+impl From<tokio_postgres::row::Row> for SleepRuleAction {
+    // select * from sleep_rule_action order only, otherwise it will fail
+    fn from(row: tokio_postgres::Row) -> SleepRuleAction {
+        SleepRuleAction {
+            sleep_rule_id: row.get("sleep_rule_id"),
+            position: row.get("position"),
+            action: row
+                .get::<_, i64>("action")
+                .try_into()
+                .expect("invalid sleep rule action"),
+            target_user_id: row.get("target_user_id"),
+            text_body: row.get("text_body"),
+            user_message_id: row.get("user_message_id"),
+            duration_millis: row.get("duration_millis"),
+        }
+    }
+}
+
+pub async fn add(
+    con: &mut impl GenericClient,
+    action: &SleepRuleAction,
+) -> Result<(), tokio_postgres::Error> {
+    con.execute(
+        "INSERT INTO
+         sleep_rule_action(
+             sleep_rule_id,
+             position,
+             action,
+             target_user_id,
+             text_body,
+             user_message_id,
+             duration_millis
+         )
+         VALUES($1, $2, $3, $4, $5, $6, $7)
+        ",
+        &[
+            &action.sleep_rule_id,
+            &action.position,
+            &i64::from(action.action),
+            &action.target_user_id,
+            &action.text_body,
+            &action.user_message_id,
+            &action.duration_millis,
+        ],
+    )
+    .await?;
+    Ok(())
+}
+
+// what the rule does, in order
+pub async fn get_by_sleep_rule_id(
+    con: &mut impl GenericClient,
+    sleep_rule_id: i64,
+) -> Result<Vec<SleepRuleAction>, tokio_postgres::Error> {
+    let result = con
+        .query(
+            "SELECT * FROM sleep_rule_action
+             WHERE sleep_rule_id=$1
+             ORDER BY position
+            ",
+            &[&sleep_rule_id],
+        )
+        .await?
+        .into_iter()
+        .map(|x| x.into())
+        .collect();
+    Ok(result)
+}
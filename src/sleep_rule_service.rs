@@ -0,0 +1,129 @@
+use super::db_types::*;
+use tokio_postgres::GenericClient;
+
+impl From<tokio_postgres::row::Row> for SleepRule {
+    // select * from sleep_rule order only, otherwise it will fail
+    fn from(row: tokio_postgres::Row) -> SleepRule {
+        SleepRule {
+            sleep_rule_id: row.get("sleep_rule_id"),
+            creation_time: row.get("creation_time"),
+            creator_user_id: row.get("creator_user_id"),
+            kind: row
+                .get::<_, i64>("kind")
+                .try_into()
+                .expect("invalid sleep event kind"),
+            source: row
+                .get::<_, Option<i64>>("source")
+                .map(|x| x.try_into().expect("invalid sleep event source")),
+            time_zone: row.get("time_zone"),
+            window_start_minute: row.get("window_start_minute"),
+            window_end_minute: row.get("window_end_minute"),
+            active: row.get("active"),
+        }
+    }
+}
+
+pub async fn add(
+    con: &mut impl GenericClient,
+    creator_user_id: i64,
+    kind: SleepEventKind,
+    source: Option<SleepEventSource>,
+    time_zone: Option<String>,
+    window_start_minute: Option<i64>,
+    window_end_minute: Option<i64>,
+) -> Result<SleepRule, tokio_postgres::Error> {
+    let row = con
+        .query_one(
+            "INSERT INTO
+             sleep_rule(
+                 creator_user_id,
+                 kind,
+                 source,
+                 time_zone,
+                 window_start_minute,
+                 window_end_minute,
+                 active
+             )
+             VALUES($1, $2, $3, $4, $5, $6, TRUE)
+             RETURNING *
+            ",
+            &[
+                &creator_user_id,
+                &i64::from(kind),
+                &source.map(i64::from),
+                &time_zone,
+                &window_start_minute,
+                &window_end_minute,
+            ],
+        )
+        .await?;
+
+    Ok(row.into())
+}
+
+pub async fn get_by_sleep_rule_id(
+    con: &mut impl GenericClient,
+    sleep_rule_id: i64,
+) -> Result<Option<SleepRule>, tokio_postgres::Error> {
+    let result = con
+        .query_opt(
+            "SELECT * FROM sleep_rule WHERE sleep_rule_id=$1",
+            &[&sleep_rule_id],
+        )
+        .await?
+        .map(|x| x.into());
+    Ok(result)
+}
+
+// the user's rules, oldest first
+pub async fn get_by_creator_user_id(
+    con: &mut impl GenericClient,
+    creator_user_id: i64,
+) -> Result<Vec<SleepRule>, tokio_postgres::Error> {
+    let result = con
+        .query(
+            "SELECT * FROM sleep_rule
+             WHERE creator_user_id=$1
+             ORDER BY sleep_rule_id
+            ",
+            &[&creator_user_id],
+        )
+        .await?
+        .into_iter()
+        .map(|x| x.into())
+        .collect();
+    Ok(result)
+}
+
+// the user's active rules set off by this kind of event, oldest first
+pub async fn get_active_by_creator_user_id_and_kind(
+    con: &mut impl GenericClient,
+    creator_user_id: i64,
+    kind: SleepEventKind,
+) -> Result<Vec<SleepRule>, tokio_postgres::Error> {
+    let result = con
+        .query(
+            "SELECT * FROM sleep_rule
+             WHERE creator_user_id=$1 AND kind=$2 AND active
+             ORDER BY sleep_rule_id
+            ",
+            &[&creator_user_id, &i64::from(kind)],
+        )
+        .await?
+        .into_iter()
+        .map(|x| x.into())
+        .collect();
+    Ok(result)
+}
+
+pub async fn delete(
+    con: &mut impl GenericClient,
+    sleep_rule_id: i64,
+) -> Result<(), tokio_postgres::Error> {
+    con.execute(
+        "DELETE FROM sleep_rule WHERE sleep_rule_id=$1",
+        &[&sleep_rule_id],
+    )
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,139 @@
+use super::db_types::*;
+use tokio_postgres::GenericClient;
+
+// whether the share in sss still counts, by the same rules as manage_contact::check_can_message:
+// the pair must still be contacts or share a household, and neither may have blocked the other.
+// a share that stops counting is hidden without being turned off, so it's back if they reconnect.
+pub const SHARE_IS_VISIBLE: &str = "
+    NOT EXISTS(
+        SELECT 1 FROM recent_contact_block cb
+        WHERE cb.active
+        AND ((cb.creator_user_id=sss.creator_user_id AND cb.target_user_id=sss.target_user_id)
+          OR (cb.creator_user_id=sss.target_user_id AND cb.target_user_id=sss.creator_user_id))
+    )
+    AND (
+        sss.creator_user_id=sss.target_user_id
+        OR EXISTS(
+            SELECT 1 FROM contact c
+            WHERE c.user_id=sss.creator_user_id AND c.contact_user_id=sss.target_user_id
+        )
+        OR EXISTS(
+            SELECT 1 FROM recent_household_membership a
+            INNER JOIN recent_household_membership b ON a.household_id = b.household_id
+            WHERE a.user_id=sss.creator_user_id AND a.active
+            AND b.user_id=sss.target_user_id AND b.active
+        )
+    )
+";
+
+impl From<tokio_postgres::row::Row> for SleepStatusShare {
+    // select * from sleep_status_share order only, otherwise it will fail
+    fn from(row: tokio_postgres::Row) -> SleepStatusShare {
+        SleepStatusShare {
+            sleep_status_share_id: row.get("sleep_status_share_id"),
+            creation_time: row.get("creation_time"),
+            creator_user_id: row.get("creator_user_id"),
+            target_user_id: row.get("target_user_id"),
+            active: row.get("active"),
+        }
+    }
+}
+
+pub async fn add(
+    con: &mut impl GenericClient,
+    creator_user_id: i64,
+    target_user_id: i64,
+    active: bool,
+) -> Result<SleepStatusShare, tokio_postgres::Error> {
+    let row = con
+        .query_one(
+            "INSERT INTO
+             sleep_status_share(
+                 creator_user_id,
+                 target_user_id,
+                 active
+             )
+             VALUES($1, $2, $3)
+             RETURNING sleep_status_share_id, creation_time
+            ",
+            &[&creator_user_id, &target_user_id, &active],
+        )
+        .await?;
+
+    // return sleep status share
+    Ok(SleepStatusShare {
+        sleep_status_share_id: row.get(0),
+        creation_time: row.get(1),
+        creator_user_id,
+        target_user_id,
+        active,
+    })
+}
+
+// the users this user currently shares their status with
+pub async fn get_active_by_creator_user_id(
+    con: &mut impl GenericClient,
+    creator_user_id: i64,
+) -> Result<Vec<SleepStatusShare>, tokio_postgres::Error> {
+    let result = con
+        .query(
+            "SELECT * FROM recent_sleep_status_share
+             WHERE creator_user_id=$1 AND active
+             ORDER BY sleep_status_share_id
+            ",
+            &[&creator_user_id],
+        )
+        .await?
+        .into_iter()
+        .map(|x| x.into())
+        .collect();
+    Ok(result)
+}
+
+// the users currently sharing their status with this user
+pub async fn get_active_by_target_user_id(
+    con: &mut impl GenericClient,
+    target_user_id: i64,
+) -> Result<Vec<SleepStatusShare>, tokio_postgres::Error> {
+    let result = con
+        .query(
+            &format!(
+                "SELECT sss.* FROM recent_sleep_status_share sss
+                 WHERE sss.target_user_id=$1 AND sss.active
+                 AND {}
+                 ORDER BY sss.sleep_status_share_id
+                ",
+                SHARE_IS_VISIBLE
+            ),
+            &[&target_user_id],
+        )
+        .await?
+        .into_iter()
+        .map(|x| x.into())
+        .collect();
+    Ok(result)
+}
+
+// the users who should hear about this user's status changing
+pub async fn get_visible_target_user_ids(
+    con: &mut impl GenericClient,
+    creator_user_id: i64,
+) -> Result<Vec<i64>, tokio_postgres::Error> {
+    let result = con
+        .query(
+            &format!(
+                "SELECT sss.target_user_id FROM recent_sleep_status_share sss
+                 WHERE sss.creator_user_id=$1 AND sss.active
+                 AND {}
+                 ORDER BY sss.sleep_status_share_id
+                ",
+                SHARE_IS_VISIBLE
+            ),
+            &[&creator_user_id],
+        )
+        .await?
+        .into_iter()
+        .map(|x| x.get(0))
+        .collect();
+    Ok(result)
+}
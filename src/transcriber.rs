@@ -0,0 +1,88 @@
+use derive_more::Display;
+
+use crate::blob_store::{BlobReader, BlobStoreError};
+use crate::command_transcriber::CommandTranscriber;
+
+#[derive(Debug, Display)]
+pub enum TranscriptionError {
+    #[display(fmt = "io: {}", _0)]
+    Io(std::io::Error),
+    #[display(fmt = "reading audio: {}", _0)]
+    Read(BlobStoreError),
+    #[display(fmt = "command failed with {}: {}", _0, _1)]
+    Failed(std::process::ExitStatus, String),
+    #[display(fmt = "malformed output: {}", _0)]
+    Malformed(String),
+}
+
+impl std::error::Error for TranscriptionError {}
+
+impl From<std::io::Error> for TranscriptionError {
+    fn from(e: std::io::Error) -> TranscriptionError {
+        TranscriptionError::Io(e)
+    }
+}
+
+impl From<BlobStoreError> for TranscriptionError {
+    fn from(e: BlobStoreError) -> TranscriptionError {
+        TranscriptionError::Read(e)
+    }
+}
+
+// what a backend heard in a recording
+#[derive(Clone, Debug)]
+pub struct Transcript {
+    pub text: String,
+    pub language: Option<String>,
+    pub confidence: Option<f64>,
+}
+
+// turns recorded speech into text.
+// the audio is streamed from storage, so backends never need to hold all of it in memory.
+pub trait Transcribe {
+    // stored alongside every transcript, so we know where it came from
+    fn name(&self) -> &'static str;
+
+    async fn transcribe(&self, audio: BlobReader) -> Result<Transcript, TranscriptionError>;
+}
+
+// hears nothing, so the rest of the pipeline can be exercised without a speech model
+#[derive(Clone, Debug)]
+pub struct NoopTranscriber;
+
+impl Transcribe for NoopTranscriber {
+    fn name(&self) -> &'static str {
+        "noop"
+    }
+
+    async fn transcribe(&self, _audio: BlobReader) -> Result<Transcript, TranscriptionError> {
+        Ok(Transcript {
+            text: String::new(),
+            language: None,
+            confidence: None,
+        })
+    }
+}
+
+// the backend chosen on the command line
+#[derive(Clone, Debug)]
+pub enum Transcriber {
+    Noop(NoopTranscriber),
+    Command(CommandTranscriber),
+}
+
+impl Transcribe for Transcriber {
+    fn name(&self) -> &'static str {
+        match self {
+            Transcriber::Noop(t) => t.name(),
+            Transcriber::Command(t) => t.name(),
+        }
+    }
+
+    async fn transcribe(&self, audio: BlobReader) -> Result<Transcript, TranscriptionError> {
+        match self {
+            Transcriber::Noop(t) => t.transcribe(audio).await,
+            Transcriber::Command(t) => t.transcribe(audio).await,
+        }
+    }
+}
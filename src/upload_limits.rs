@@ -0,0 +1,61 @@
+use crate::handlers::{self, AppError};
+use crate::user_message_service;
+
+// configured limits on how much audio users may send
+#[derive(Clone, Debug)]
+pub struct UploadLimits {
+    pub max_message_bytes: i64,
+    pub max_message_duration_millis: i64,
+    pub user_quota_bytes: i64,
+}
+
+// how much a particular user may still upload in a single message
+#[derive(Clone, Debug)]
+pub struct UploadBudget {
+    max_message_bytes: i64,
+    quota_remaining_bytes: i64,
+    max_message_duration_millis: i64,
+}
+
+impl UploadLimits {
+    pub async fn budget_for(
+        &self,
+        con: &mut tokio_postgres::Client,
+        creator_user_id: i64,
+    ) -> Result<UploadBudget, AppError> {
+        let used = user_message_service::get_total_audio_size_by_creator(con, creator_user_id)
+            .await
+            .map_err(handlers::report_postgres_err)?;
+
+        let budget = UploadBudget {
+            max_message_bytes: self.max_message_bytes,
+            quota_remaining_bytes: self.user_quota_bytes - used,
+            max_message_duration_millis: self.max_message_duration_millis,
+        };
+
+        // no point in accepting an upload if the user is already out of space
+        budget.check_size(1)?;
+        Ok(budget)
+    }
+}
+
+impl UploadBudget {
+    // the audio is stored once however many targets it goes to, so it only counts once
+    pub fn check_size(&self, size: i64) -> Result<(), AppError> {
+        if size > self.max_message_bytes {
+            Err(AppError::MessageTooLarge)
+        } else if size > self.quota_remaining_bytes {
+            Err(AppError::QuotaExceeded)
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn check_duration(&self, duration_millis: i64) -> Result<(), AppError> {
+        if duration_millis > self.max_message_duration_millis {
+            Err(AppError::MessageTooLong)
+        } else {
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use super::db_types::*;
+use tokio_postgres::GenericClient;
+
+impl From<tokio_postgres::row::Row> for UserMessageReaction {
+    // select * from user_message_reaction order only, otherwise it will fail
+    fn from(row: tokio_postgres::Row) -> UserMessageReaction {
+        UserMessageReaction {
+            user_message_reaction_id: row.get("user_message_reaction_id"),
+            creation_time: row.get("creation_time"),
+            creator_user_id: row.get("creator_user_id"),
+            user_message_id: row.get("user_message_id"),
+            reaction: row.get("reaction"),
+        }
+    }
+}
+
+// reacting the same way twice gives back the reaction that's already there
+pub async fn add(
+    con: &mut impl GenericClient,
+    creator_user_id: i64,
+    user_message_id: i64,
+    reaction: String,
+) -> Result<UserMessageReaction, tokio_postgres::Error> {
+    let row = con
+        .query_one(
+            "INSERT INTO
+             user_message_reaction(
+                 creator_user_id,
+                 user_message_id,
+                 reaction
+             )
+             VALUES($1, $2, $3)
+             ON CONFLICT (user_message_id, creator_user_id, reaction)
+             DO UPDATE SET reaction = EXCLUDED.reaction
+             RETURNING *
+            ",
+            &[&creator_user_id, &user_message_id, &reaction],
+        )
+        .await?;
+    Ok(row.into())
+}
+
+pub async fn get_by_user_message_reaction_id(
+    con: &mut impl GenericClient,
+    user_message_reaction_id: i64,
+) -> Result<Option<UserMessageReaction>, tokio_postgres::Error> {
+    let result = con
+        .query_opt(
+            "SELECT * FROM user_message_reaction WHERE user_message_reaction_id=$1",
+            &[&user_message_reaction_id],
+        )
+        .await?
+        .map(|x| x.into());
+    Ok(result)
+}
+
+pub async fn get_by_user_message_ids(
+    con: &mut impl GenericClient,
+    user_message_ids: &[i64],
+) -> Result<Vec<UserMessageReaction>, tokio_postgres::Error> {
+    let result = con
+        .query(
+            "SELECT * FROM user_message_reaction
+             WHERE user_message_id = ANY($1)
+             ORDER BY user_message_reaction_id
+            ",
+            &[&user_message_ids],
+        )
+        .await?
+        .into_iter()
+        .map(|x| x.into())
+        .collect();
+    Ok(result)
+}
+
+pub async fn delete(
+    con: &mut impl GenericClient,
+    user_message_reaction_id: i64,
+) -> Result<Option<UserMessageReaction>, tokio_postgres::Error> {
+    let result = con
+        .query_opt(
+            "DELETE FROM user_message_reaction
+             WHERE user_message_reaction_id = $1
+             RETURNING *
+            ",
+            &[&user_message_reaction_id],
+        )
+        .await?
+        .map(|x| x.into());
+    Ok(result)
+}
@@ -9,29 +9,78 @@ impl From<tokio_postgres::row::Row> for UserMessage {
             creation_time: row.get("creation_time"),
             creator_user_id: row.get("creator_user_id"),
             target_user_id: row.get("target_user_id"),
-            audio_data: row.get("audio_data"),
+            kind: row
+                .get::<_, i64>("kind")
+                .try_into()
+                .expect("invalid user message kind"),
+            audio_blob_key: row.get("audio_blob_key"),
+            audio_size: row.get("audio_size"),
+            audio_sha256: row.get("audio_sha256"),
+            audio_duration_millis: row.get("audio_duration_millis"),
+            text_body: row.get("text_body"),
+            deliver_at: row.get("deliver_at"),
+            ephemeral: row.get("ephemeral"),
+            played_time: row.get("played_time"),
+            target_deleted_time: row.get("target_deleted_time"),
+            reply_to_user_message_id: row.get("reply_to_user_message_id"),
         }
     }
 }
 
+// what every copy of a new message has in common.
+// without audio it's a text message, which must have a text body.
+pub struct NewUserMessage<'a> {
+    pub creator_user_id: i64,
+    pub audio_blob: Option<&'a AudioBlob>,
+    pub audio_duration_millis: Option<i64>,
+    pub text_body: Option<&'a str>,
+    pub deliver_at: Option<i64>,
+    pub ephemeral: bool,
+    pub reply_to_user_message_id: Option<i64>,
+}
+
 pub async fn add(
     con: &mut impl GenericClient,
-    creator_user_id: i64,
+    new: &NewUserMessage<'_>,
     target_user_id: i64,
-    audio_data: Vec<u8>,
 ) -> Result<UserMessage, tokio_postgres::Error> {
+    let kind = match new.audio_blob {
+        Some(_) => UserMessageKind::Audio,
+        None => UserMessageKind::Text,
+    };
+
     let row = con
         .query_one(
             "INSERT INTO
              user_message(
                  creator_user_id,
                  target_user_id,
-                 audio_data
+                 kind,
+                 audio_blob_key,
+                 audio_size,
+                 audio_sha256,
+                 audio_duration_millis,
+                 text_body,
+                 deliver_at,
+                 ephemeral,
+                 reply_to_user_message_id
              )
-             VALUES($1, $2, $3)
+             VALUES($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
              RETURNING user_message_id, creation_time
             ",
-            &[&creator_user_id, &target_user_id, &audio_data],
+            &[
+                &new.creator_user_id,
+                &target_user_id,
+                &i64::from(kind),
+                &new.audio_blob.map(|x| &x.audio_blob_key),
+                &new.audio_blob.map(|x| x.audio_size),
+                &new.audio_blob.map(|x| &x.audio_sha256),
+                &new.audio_duration_millis,
+                &new.text_body,
+                &new.deliver_at,
+                &new.ephemeral,
+                &new.reply_to_user_message_id,
+            ],
         )
         .await?;
 
@@ -39,9 +88,19 @@ pub async fn add(
     Ok(UserMessage {
         user_message_id: row.get(0),
         creation_time: row.get(1),
-        creator_user_id,
+        creator_user_id: new.creator_user_id,
         target_user_id,
-        audio_data,
+        kind,
+        audio_blob_key: new.audio_blob.map(|x| x.audio_blob_key.clone()),
+        audio_size: new.audio_blob.map(|x| x.audio_size),
+        audio_sha256: new.audio_blob.map(|x| x.audio_sha256.clone()),
+        audio_duration_millis: new.audio_duration_millis,
+        text_body: new.text_body.map(String::from),
+        deliver_at: new.deliver_at,
+        ephemeral: new.ephemeral,
+        played_time: None,
+        target_deleted_time: None,
+        reply_to_user_message_id: new.reply_to_user_message_id,
     })
 }
 
@@ -59,6 +118,26 @@ pub async fn get_by_user_message_id(
     Ok(result)
 }
 
+// how many bytes of audio a user has stored in total.
+// audio sent to several targets is stored once, so each distinct recording is only counted once.
+pub async fn get_total_audio_size_by_creator(
+    con: &mut impl GenericClient,
+    creator_user_id: i64,
+) -> Result<i64, tokio_postgres::Error> {
+    let row = con
+        .query_one(
+            "SELECT COALESCE(SUM(audio_size), 0)::bigint FROM (
+                 SELECT DISTINCT ON (audio_sha256) audio_size
+                 FROM user_message
+                 WHERE creator_user_id=$1
+                 AND audio_sha256 IS NOT NULL
+             ) blobs",
+            &[&creator_user_id],
+        )
+        .await?;
+    Ok(row.get(0))
+}
+
 pub async fn get_recent_by_target_id(
     con: &mut impl GenericClient,
     target_user_id: i64,
@@ -69,7 +148,7 @@ pub async fn get_recent_by_target_id(
                 SELECT *
                 FROM recent_user_message_by_creator_target_id
                 WHERE target_user_id=$1
-                ORDER BY creation_time
+                ORDER BY deliver_at DESC, user_message_id DESC
                 LIMIT 1
             ",
             &[&target_user_id],
@@ -79,22 +158,27 @@ pub async fn get_recent_by_target_id(
     Ok(result)
 }
 
+// only messages the viewer sent, or that have been delivered to them and are still in their inbox, are returned
 pub async fn query(
     con: &mut impl GenericClient,
+    viewer_user_id: i64,
+    current_time: i64,
     props: crate::request::UserMessageViewProps,
 ) -> Result<Vec<UserMessage>, tokio_postgres::Error> {
     let sql = [
         if props.only_recent {
             "SELECT um.* FROM recent_user_message_by_creator_target_id um"
         } else {
-            "SELECT um.* FROM user_message_t um"
+            "SELECT um.* FROM user_message um"
         },
         " WHERE 1 = 1",
-        " AND ($1::bigint[] IS NULL OR um.user_message_id IN $1)",
+        " AND ($1::bigint[] IS NULL OR um.user_message_id = ANY($1))",
         " AND ($2::bigint   IS NULL OR um.creation_time >= $2)",
         " AND ($3::bigint   IS NULL OR um.creation_time <= $3)",
-        " AND ($4::bigint[] IS NULL OR um.creator_user_id IN $4)",
-        " AND ($5::bigint[] IS NULL OR um.target_user_id IN $5)",
+        " AND ($4::bigint[] IS NULL OR um.creator_user_id = ANY($4))",
+        " AND ($5::bigint[] IS NULL OR um.target_user_id = ANY($5))",
+        " AND (um.creator_user_id = $6",
+        "   OR (um.target_user_id = $6 AND um.deliver_at <= $7 AND um.target_deleted_time IS NULL))",
         " ORDER BY um.user_message_id",
     ]
     .join("");
@@ -110,6 +194,69 @@ pub async fn query(
                 &props.max_creation_time,
                 &props.creator_user_id,
                 &props.target_user_id,
+                &viewer_user_id,
+                &current_time,
+            ],
+        )
+        .await?
+        .into_iter()
+        .map(|x| x.into())
+        .collect();
+
+    Ok(results)
+}
+
+// messages whose text body or transcript matches the query, best matches first.
+// only messages the viewer sent, or that have been delivered to them and are still in their inbox, are searched.
+pub async fn search(
+    con: &mut impl GenericClient,
+    viewer_user_id: i64,
+    current_time: i64,
+    props: &crate::request::UserMessageSearchProps,
+    limit: i64,
+) -> Result<Vec<UserMessage>, tokio_postgres::Error> {
+    let sql = [
+        "WITH q AS (SELECT websearch_to_tsquery('english', $1) AS q)",
+        " SELECT um.* FROM q, user_message um",
+        " LEFT JOIN audio_transcript tr ON tr.audio_sha256 = um.audio_sha256",
+        " WHERE (",
+        "   um.user_message_id IN (",
+        "     SELECT user_message_id FROM user_message, q",
+        "     WHERE to_tsvector('english', text_body) @@ q.q",
+        "   )",
+        "   OR um.audio_sha256 IN (",
+        "     SELECT audio_sha256 FROM audio_transcript, q",
+        "     WHERE to_tsvector('english', text) @@ q.q",
+        "   )",
+        " )",
+        " AND ($2::bigint   IS NULL OR um.creation_time >= $2)",
+        " AND ($3::bigint   IS NULL OR um.creation_time <= $3)",
+        " AND ($4::bigint[] IS NULL OR um.creator_user_id = ANY($4))",
+        " AND ($5::bigint[] IS NULL OR um.target_user_id = ANY($5))",
+        " AND (um.creator_user_id = $6",
+        "   OR (um.target_user_id = $6 AND um.deliver_at <= $7 AND um.target_deleted_time IS NULL))",
+        " ORDER BY GREATEST(",
+        "   ts_rank(to_tsvector('english', um.text_body), q.q),",
+        "   ts_rank(to_tsvector('english', tr.text), q.q)",
+        " ) DESC, um.user_message_id DESC",
+        " LIMIT $8",
+    ]
+    .join("");
+
+    let stmnt = con.prepare(&sql).await?;
+
+    let results = con
+        .query(
+            &stmnt,
+            &[
+                &props.query,
+                &props.min_creation_time,
+                &props.max_creation_time,
+                &props.creator_user_id,
+                &props.target_user_id,
+                &viewer_user_id,
+                &current_time,
+                &limit,
             ],
         )
         .await?
@@ -119,3 +266,251 @@ pub async fn query(
 
     Ok(results)
 }
+
+// messages the creator scheduled that haven't been delivered yet
+pub async fn get_scheduled_by_creator(
+    con: &mut impl GenericClient,
+    creator_user_id: i64,
+    current_time: i64,
+) -> Result<Vec<UserMessage>, tokio_postgres::Error> {
+    let result = con
+        .query(
+            "SELECT * FROM user_message
+             WHERE creator_user_id=$1
+             AND (deliver_at IS NULL OR deliver_at > $2)
+             ORDER BY deliver_at NULLS LAST, user_message_id
+            ",
+            &[&creator_user_id, &current_time],
+        )
+        .await?
+        .into_iter()
+        .map(|x| x.into())
+        .collect();
+    Ok(result)
+}
+
+// changes when a message is delivered, only if it hasn't been delivered yet
+pub async fn reschedule(
+    con: &mut impl GenericClient,
+    user_message_id: i64,
+    deliver_at: Option<i64>,
+    current_time: i64,
+) -> Result<Option<UserMessage>, tokio_postgres::Error> {
+    let result = con
+        .query_opt(
+            "UPDATE user_message
+             SET deliver_at = $2
+             WHERE user_message_id = $1
+             AND (deliver_at IS NULL OR deliver_at > $3)
+             RETURNING *
+            ",
+            &[&user_message_id, &deliver_at, &current_time],
+        )
+        .await?
+        .map(|x| x.into());
+    Ok(result)
+}
+
+// removes a message, only if it hasn't been delivered yet
+pub async fn delete_scheduled(
+    con: &mut impl GenericClient,
+    user_message_id: i64,
+    current_time: i64,
+) -> Result<Option<UserMessage>, tokio_postgres::Error> {
+    let result = con
+        .query_opt(
+            "DELETE FROM user_message
+             WHERE user_message_id = $1
+             AND (deliver_at IS NULL OR deliver_at > $2)
+             RETURNING *
+            ",
+            &[&user_message_id, &current_time],
+        )
+        .await?
+        .map(|x| x.into());
+    Ok(result)
+}
+
+// delivers everything that was waiting for the target to wake up
+pub async fn deliver_waiting_for_wake(
+    con: &mut impl GenericClient,
+    target_user_id: i64,
+    deliver_at: i64,
+) -> Result<u64, tokio_postgres::Error> {
+    con.execute(
+        "UPDATE user_message
+         SET deliver_at = $2
+         WHERE target_user_id = $1
+         AND deliver_at IS NULL
+        ",
+        &[&target_user_id, &deliver_at],
+    )
+    .await
+}
+
+// records when the target finished playing the message, keeping the first time if played again
+pub async fn mark_played(
+    con: &mut impl GenericClient,
+    user_message_id: i64,
+    played_time: i64,
+) -> Result<Option<UserMessage>, tokio_postgres::Error> {
+    let result = con
+        .query_opt(
+            "UPDATE user_message
+             SET played_time = COALESCE(played_time, $2)
+             WHERE user_message_id = $1
+             RETURNING *
+            ",
+            &[&user_message_id, &played_time],
+        )
+        .await?
+        .map(|x| x.into());
+    Ok(result)
+}
+
+// hides the message from the target, leaving it for the sender
+pub async fn delete_from_inbox(
+    con: &mut impl GenericClient,
+    user_message_id: i64,
+    target_deleted_time: i64,
+) -> Result<Option<UserMessage>, tokio_postgres::Error> {
+    let result = con
+        .query_opt(
+            "UPDATE user_message
+             SET target_deleted_time = $2
+             WHERE user_message_id = $1
+             AND target_deleted_time IS NULL
+             RETURNING *
+            ",
+            &[&user_message_id, &target_deleted_time],
+        )
+        .await?
+        .map(|x| x.into());
+    Ok(result)
+}
+
+pub async fn delete(
+    con: &mut impl GenericClient,
+    user_message_id: i64,
+) -> Result<Option<UserMessage>, tokio_postgres::Error> {
+    let result = con
+        .query_opt(
+            "DELETE FROM user_message WHERE user_message_id = $1 RETURNING *",
+            &[&user_message_id],
+        )
+        .await?
+        .map(|x| x.into());
+    Ok(result)
+}
+
+// for every delivered message, whether the retention policies of its sender, of its target,
+// and of a household they share (or being a played ephemeral message) no longer keep it.
+// a user's policy only speaks for their own side of the conversation.
+const EXPIRED_USER_MESSAGE: &str = "
+    WITH um AS (
+        SELECT
+            user_message_id,
+            creator_user_id,
+            target_user_id,
+            deliver_at,
+            ephemeral,
+            played_time,
+            target_deleted_time,
+            row_number() OVER (
+                PARTITION BY
+                    LEAST(creator_user_id, target_user_id),
+                    GREATEST(creator_user_id, target_user_id)
+                ORDER BY deliver_at DESC, user_message_id DESC
+            ) AS position
+        FROM user_message
+        WHERE deliver_at <= $1
+    ),
+    expired AS (
+        SELECT
+            um.user_message_id,
+            um.target_deleted_time,
+            (um.ephemeral AND um.played_time IS NOT NULL) OR EXISTS (
+                SELECT 1 FROM recent_retention_policy rp
+                INNER JOIN recent_household_membership a ON a.household_id = rp.household_id
+                INNER JOIN recent_household_membership b ON b.household_id = rp.household_id
+                WHERE a.user_id = um.creator_user_id AND a.active
+                AND b.user_id = um.target_user_id AND b.active
+                AND (
+                    um.deliver_at < $1 - rp.keep_days * 24 * 60 * 60 * 1000
+                    OR um.position > rp.keep_last
+                    OR (rp.delete_after_played AND um.played_time IS NOT NULL)
+                )
+            ) AS by_household,
+            EXISTS (
+                SELECT 1 FROM recent_retention_policy rp
+                WHERE rp.user_id = um.creator_user_id
+                AND (
+                    um.deliver_at < $1 - rp.keep_days * 24 * 60 * 60 * 1000
+                    OR um.position > rp.keep_last
+                    OR (rp.delete_after_played AND um.played_time IS NOT NULL)
+                )
+            ) AS by_creator,
+            EXISTS (
+                SELECT 1 FROM recent_retention_policy rp
+                WHERE rp.user_id = um.target_user_id
+                AND (
+                    um.deliver_at < $1 - rp.keep_days * 24 * 60 * 60 * 1000
+                    OR um.position > rp.keep_last
+                    OR (rp.delete_after_played AND um.played_time IS NOT NULL)
+                )
+            ) AS by_target
+        FROM um
+    )
+";
+
+// removes messages from the inbox of targets whose own policy no longer keeps them.
+// the sender keeps their copy until their policy lets it go too.
+pub async fn hide_expired_from_targets(
+    con: &mut impl GenericClient,
+    current_time: i64,
+    limit: i64,
+) -> Result<u64, tokio_postgres::Error> {
+    let sql = [
+        EXPIRED_USER_MESSAGE,
+        "UPDATE user_message
+         SET target_deleted_time = $1
+         WHERE user_message_id IN (
+             SELECT user_message_id FROM expired
+             WHERE by_target AND target_deleted_time IS NULL
+             LIMIT $2
+         )
+        ",
+    ]
+    .join("");
+    con.execute(&sql, &[&current_time, &limit]).await
+}
+
+// removes messages that neither side keeps any more: both users' policies, or a household
+// policy covering both of them, let them go, or the target already removed theirs.
+// played ephemeral messages are removed too, in case deleting them on play didn't go through.
+pub async fn delete_expired(
+    con: &mut impl GenericClient,
+    current_time: i64,
+    limit: i64,
+) -> Result<Vec<UserMessage>, tokio_postgres::Error> {
+    let sql = [
+        EXPIRED_USER_MESSAGE,
+        "DELETE FROM user_message
+         WHERE user_message_id IN (
+             SELECT user_message_id FROM expired
+             WHERE by_household
+             OR (by_creator AND (by_target OR target_deleted_time IS NOT NULL))
+             LIMIT $2
+         )
+         RETURNING *
+        ",
+    ]
+    .join("");
+    let result = con
+        .query(&sql, &[&current_time, &limit])
+        .await?
+        .into_iter()
+        .map(|x| x.into())
+        .collect();
+    Ok(result)
+}
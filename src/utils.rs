@@ -1,5 +1,6 @@
 use rand::distributions::Alphanumeric;
 use rand::Rng;
+use sha2::{Digest, Sha256};
 use std::time::{SystemTime, UNIX_EPOCH};
 
 pub fn current_time_millis() -> i64 {
@@ -19,3 +20,8 @@ pub fn random_string() -> String {
         .collect();
     return s;
 }
+
+// hex encoded sha256 digest of some data
+pub fn sha256_hex(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}